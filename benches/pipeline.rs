@@ -0,0 +1,53 @@
+//! Benchmarks of collecting and filtering the connections, run them with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use somo::connections::{Connection, State};
+use somo::{ConnectionSource, FilterOptions, FixtureSource, ProcfsSource};
+
+// the fixture builder of the tests refers to these modules through the crate root
+use somo::{address_checkers, connections, string_utils};
+#[path = "../src/fixtures.rs"]
+mod fixtures;
+use fixtures::ConnectionFixture;
+
+/// Builds a fixture with many connections of different programs, ports and states.
+fn get_fixture(amount: usize) -> FixtureSource {
+    let connections: Vec<Connection> = (0..amount).map(|idx| ConnectionFixture::new()
+        .proto(if idx % 3 == 0 { "udp" } else { "tcp" })
+        .local(if idx % 4 == 0 { "[::]" } else { "10.0.0.2" }, (1024 + idx % 60000) as u16)
+        .remote(&format!("192.168.{}.{}", idx / 256 % 256, idx % 256), 443)
+        .program(&format!("program{}", idx % 50), (idx % 5000 + 1) as i32)
+        .state(if idx % 5 == 0 { State::Listen } else { State::Established })
+        .user(1000, "user")
+        .build()
+    ).collect();
+    FixtureSource::from_json(&serde_json::to_string(&connections).unwrap()).unwrap()
+}
+
+fn bench_collection(c: &mut Criterion) {
+    let all = FilterOptions::new();
+    let by_pid = FilterOptions::new().pid("1");
+    let without_processes = FilterOptions::new().skip_processes(true);
+
+    let mut group = c.benchmark_group("collection");
+    group.sample_size(20);
+    group.bench_function("all", |b| b.iter(|| ProcfsSource.get_connections(black_box(&all))));
+    group.bench_function("by pid", |b| b.iter(|| ProcfsSource.get_connections(black_box(&by_pid))));
+    group.bench_function("without processes", |b| b.iter(|| ProcfsSource.get_connections(black_box(&without_processes))));
+    group.finish();
+}
+
+fn bench_filtering(c: &mut Criterion) {
+    let fixture = get_fixture(10_000);
+    let by_program = FilterOptions::new().program("program7");
+    let combined = FilterOptions::new().proto("tcp").remote_address("192.168.1.7").open(true).exclude_ipv6(true);
+
+    let mut group = c.benchmark_group("filtering");
+    group.bench_function("none", |b| b.iter(|| fixture.get_connections(black_box(&FilterOptions::new()))));
+    group.bench_function("by program", |b| b.iter(|| fixture.get_connections(black_box(&by_program))));
+    group.bench_function("combined", |b| b.iter(|| fixture.get_connections(black_box(&combined))));
+    group.finish();
+}
+
+criterion_group!(benches, bench_collection, bench_filtering);
+criterion_main!(benches);
@@ -0,0 +1,68 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Gets the short hash of the checked out git commit, "unknown" if somo isn't built from a git checkout (e.g. from crates.io).
+fn get_git_commit() -> String {
+    Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+
+/// Gets the date of the build as YYYY-MM-DD (in UTC), `$SOURCE_DATE_EPOCH` is used for reproducible builds.
+fn get_build_date() -> String {
+    let seconds: u64 = env::var("SOURCE_DATE_EPOCH").ok().and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0));
+
+    // converts the days since 1970-01-01 to a date of the proleptic Gregorian calendar
+    let days: i64 = (seconds / 86400) as i64 + 719468;
+    let era: i64 = days.div_euclid(146097);
+    let day_of_era: i64 = days.rem_euclid(146097);
+    let year_of_era: i64 = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month: i64 = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year: i64 = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+
+/// Compiles the fallback services database `data/services` (in the format of `/etc/services`) into a Rust array,
+/// so that port names are available without reading (or shipping) a file at runtime.
+fn generate_fallback_services() {
+    let content: String = fs::read_to_string("data/services").expect("data/services should be readable");
+    let mut entries: Vec<String> = Vec::new();
+    for line in content.lines().map(|line| line.split('#').next().unwrap_or_default().trim()).filter(|line| !line.is_empty()) {
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some((port, proto))) = (fields.next(), fields.next().and_then(|port_proto| port_proto.split_once('/'))) else {
+            panic!("invalid entry in data/services: '{}'", line);
+        };
+        let port: u16 = port.parse().unwrap_or_else(|_| panic!("invalid port in data/services: '{}'", line));
+        entries.push(format!("    ({}, {:?}, {:?}),\n", port, proto, name));
+    }
+
+    let code: String = format!(
+        "/// Common IANA port assignments compiled from `data/services`, as port, protocol and name.\nstatic FALLBACK_SERVICES: [(u16, &str, &str); {}] = [\n{}];\n",
+        entries.len(), entries.concat()
+    );
+    let path: PathBuf = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR is set by cargo")).join("fallback_services.rs");
+    fs::write(path, code).expect("the fallback services should be writable");
+}
+
+
+fn main() {
+    generate_fallback_services();
+    println!("cargo:rustc-env=SOMO_GIT_COMMIT={}", get_git_commit());
+    println!("cargo:rustc-env=SOMO_BUILD_DATE={}", get_build_date());
+    println!("cargo:rerun-if-changed=data/services");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
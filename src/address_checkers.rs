@@ -1,61 +1,253 @@
-
 use reqwest::{self};
+use serde::{Deserialize, Serialize};
 use serde_json::{Value};
-use std::{error::Error, env};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, path::PathBuf};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use crate::connections;
 use crate::string_utils;
 
+/// Maximum amount of AbuseIPDB requests running at the same time.
+static MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// Minimum time between the starts of two AbuseIPDB requests, so that the API isn't flooded with lookups.
+static REQUEST_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time after which connecting to AbuseIPDB is given up, e.g. if the route to it is blackholed.
+static CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time after which an AbuseIPDB request is given up, including connecting to it and reading the response.
+static REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time in seconds after which a cached abuse score is requested again.
+static CACHE_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+
+/// Represents why an abuse score couldn't be requested.
+#[derive(Debug)]
+pub enum AbuseCheckError {
+    /// The rate limit of the API key was hit (HTTP 429), with the time after which requests are allowed again if the API sent it
+    RateLimited(Option<Duration>),
+    /// The request failed, e.g. because the host is offline or it timed out
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for AbuseCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbuseCheckError::RateLimited(Some(retry_after)) => write!(f, "rate limit reached, retry after {}s", retry_after.as_secs()),
+            AbuseCheckError::RateLimited(None) => f.write_str("rate limit reached"),
+            AbuseCheckError::Request(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AbuseCheckError {
+    fn from(error: reqwest::Error) -> Self {
+        AbuseCheckError::Request(error)
+    }
+}
+
 
 /// Requests an abuse score from the AbuseIPDB.com /check endpoint given an IP address.
-/// The function expects that the environment variable `ABUSEIPDB_API_KEY` is set with an AbuseIPDB.com API key.
 /// 
 /// # Arguments
+/// * `client`: The HTTP client used for the request.
+/// * `api_key`: The AbuseIPDB.com API key.
 /// * `remote_address`: The address to be checked.
-/// * `verbose`: Print information about the API request if set to `true`.
 /// 
 /// # Returns
-/// If the request is successful the abuse sore is returned, if not `Some(None)` is returned.
-pub async fn check_address_for_abuse(remote_address: &String, verbose: bool) -> Result<Option<i64>, Box<dyn Error>> {
-    let abuseipdb_api_key: String = match env::var("ABUSEIPDB_API_KEY") {
-        Ok(val) => val,
-        Err(_e) => {
-            if verbose {
-                string_utils::pretty_print_warning(
-                    "Couldn't find AbuseIPDB API key. If you want to use this feature make sure to put the API key into the environment variable `ABUSEIPDB_API_KEY`.*"
-                );
-            }
-            return Ok(None);
-        },
-    };
-
-    let client = reqwest::Client::new();
+/// The abuse score (`None` if the response didn't contain one) or an error if the request failed, e.g. because the host is offline or the rate limit was hit.
+pub async fn check_address_for_abuse(client: &reqwest::Client, api_key: &str, remote_address: &str) -> Result<Option<i64>, AbuseCheckError> {
     let url = "https://api.abuseipdb.com/api/v2/check";
     let params = [
         ("ipAddress", remote_address),
-        ("maxAgeInDays", &("40".to_string())),
+        ("maxAgeInDays", "40"),
     ];
     let response = client
         .get(url)
-        .header("Key", abuseipdb_api_key)
+        .header("Key", api_key)
         .header("Accept", "application/json")
         .query(&params)
         .send()
         .await?;
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let retry_after: Option<Duration> = response.headers().get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        return Err(AbuseCheckError::RateLimited(retry_after));
+    }
+    let response = response.error_for_status()?;
+
+    let json_response: Value = response.json().await?;
+    Ok(json_response["data"]["abuseConfidenceScore"].as_i64())
+}
 
-    // check if the request was successful
-    if response.status().is_success() {
-        let json_response: Value = response.json().await?;
-        let abuse_confidence_score: Option<i64> = json_response["data"]["abuseConfidenceScore"].as_i64();
 
-        Ok(abuse_confidence_score)
+/// Gets the path of the file caching abuse scores between runs, i.e. `$XDG_CACHE_HOME/somo/abuseipdb` or `~/.cache/somo/abuseipdb`.
+/// 
+/// # Arguments
+/// None
+/// 
+/// # Returns
+/// The path of the cache file or `None` if neither `XDG_CACHE_HOME` nor `HOME` is set.
+fn get_cache_path() -> Option<PathBuf> {
+    let cache_directory = match env::var("XDG_CACHE_HOME") {
+        Ok(directory) => PathBuf::from(directory),
+        Err(_) => PathBuf::from(env::var("HOME").ok()?).join(".cache"),
+    };
+    Some(cache_directory.join("somo").join("abuseipdb"))
+}
+
+
+/// Gets the current time as seconds since the UNIX epoch.
+fn now_seconds() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+
+/// Reads the abuse scores cached by previous runs which haven't expired yet.
+/// Every line of the cache file has the format `<address> <score> <unix timestamp>`.
+/// 
+/// # Arguments
+/// None
+/// 
+/// # Returns
+/// A map from addresses to their cached abuse score and the time they were requested.
+fn read_cache() -> HashMap<String, (i64, u64)> {
+    let content = get_cache_path().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+    let now = now_seconds();
+
+    content.lines().filter_map(|line| {
+        let mut parts = line.split_whitespace();
+        let address = parts.next()?.to_string();
+        let score: i64 = parts.next()?.parse().ok()?;
+        let timestamp: u64 = parts.next()?.parse().ok()?;
+        (now.saturating_sub(timestamp) < CACHE_TTL_SECONDS).then_some((address, (score, timestamp)))
+    }).collect()
+}
+
+
+/// Writes the abuse scores to the cache file. Failing to write the cache is not considered an error.
+/// 
+/// # Arguments
+/// * `cache`: A map from addresses to their abuse score and the time they were requested.
+/// 
+/// # Returns
+/// None
+fn write_cache(cache: &HashMap<String, (i64, u64)>) {
+    let Some(path) = get_cache_path() else { return };
+    let content: String = cache.iter()
+        .map(|(address, (score, timestamp))| format!("{} {} {}\n", address, score, timestamp))
+        .collect();
+
+    if let Some(directory) = path.parent() {
+        let _ = fs::create_dir_all(directory);
     }
-    else {
-        if verbose {
-            string_utils::pretty_print_error(
-                &format!("AbuseIPDB Request failed with status code: {}", response.status())
+    let _ = fs::write(path, content);
+}
+
+
+/// Adds a threat score from AbuseIPDB.com to every connection with a public remote address, private (e.g. LAN or CGNAT) addresses aren't sent.
+/// The function expects that the environment variable `ABUSEIPDB_API_KEY` is set with an AbuseIPDB.com API key.
+/// Every address is only requested once, at most `MAX_CONCURRENT_REQUESTS` requests run concurrently, they start at least `REQUEST_INTERVAL` apart
+/// and scores are cached for a day. Once the rate limit is hit, the remaining addresses aren't requested anymore.
+/// Failed requests (e.g. when offline or timed out) don't abort the check, the affected connections simply don't receive a score.
+/// 
+/// # Arguments
+/// * `connections`: The connections to check.
+/// 
+/// # Returns
+/// None
+pub async fn annotate_threat_scores(connections: &mut [connections::Connection]) {
+    let api_key: String = match env::var("ABUSEIPDB_API_KEY") {
+        Ok(val) => val,
+        Err(_e) => {
+            string_utils::pretty_print_warning(
+                "Couldn't find AbuseIPDB API key. If you want to use this feature make sure to put the API key into the environment variable `ABUSEIPDB_API_KEY`."
             );
+            return;
+        },
+    };
+
+    // the cache is keyed by the bracketed format of the addresses, independent of `--ip-format`
+    let mut cache = read_cache();
+    let mut unchecked_addresses: Vec<String> = connections.iter()
+        .filter(|connection| get_address_scope(&connection.remote_address) == AddressScope::Public)
+        .map(|connection| string_utils::format_address_as(&string_utils::normalize_address(&connection.remote_address), string_utils::IpFormat::Bracketed))
+        .filter(|address| !cache.contains_key(address))
+        .collect();
+    unchecked_addresses.sort();
+    unchecked_addresses.dedup();
+
+    let client = match reqwest::Client::builder().connect_timeout(CONNECT_TIMEOUT).timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(error) => {
+            string_utils::pretty_print_warning(&format!("Couldn't check the addresses using AbuseIPDB: {}", error));
+            return;
         }
-        Ok(None)
+    };
+
+    // request all unchecked addresses concurrently (limited by a semaphore) and paced, until the rate limit is hit
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS));
+    let next_request: Arc<Mutex<Instant>> = Arc::new(Mutex::new(Instant::now()));
+    let rate_limited: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let mut requests = JoinSet::new();
+    for address in unchecked_addresses {
+        let (client, api_key, semaphore, next_request, rate_limited) = (client.clone(), api_key.clone(), semaphore.clone(), next_request.clone(), rate_limited.clone());
+        requests.spawn(async move {
+            let _permit = semaphore.acquire().await;
+            let start: Instant = {
+                let mut next_request = next_request.lock().await;
+                let start: Instant = (*next_request).max(Instant::now());
+                *next_request = start + REQUEST_INTERVAL;
+                start
+            };
+            tokio::time::sleep_until(start).await;
+            if rate_limited.load(Ordering::Relaxed) {
+                return (address, None);
+            }
+            let result = check_address_for_abuse(&client, &api_key, address.trim_start_matches('[').trim_end_matches(']')).await;
+            if matches!(result, Err(AbuseCheckError::RateLimited(_))) {
+                rate_limited.store(true, Ordering::Relaxed);
+            }
+            (address, Some(result))
+        });
     }
+
+    let mut failed_requests: Vec<AbuseCheckError> = Vec::new();
+    let mut skipped_requests: usize = 0;
+    while let Some(Ok((address, result))) = requests.join_next().await {
+        match result {
+            Some(Ok(Some(score))) => { cache.insert(address, (score, now_seconds())); },
+            Some(Ok(None)) => { },
+            Some(Err(error)) => failed_requests.push(error),
+            None => skipped_requests += 1,
+        }
+    }
+
+    if let Some(error) = failed_requests.iter().find(|error| matches!(error, AbuseCheckError::RateLimited(_))) {
+        string_utils::pretty_print_warning(
+            &format!("Stopped checking addresses using AbuseIPDB ({}), {} address(es) weren't checked.", error, failed_requests.len() + skipped_requests)
+        );
+    } else if let Some(error) = failed_requests.first() {
+        string_utils::pretty_print_warning(
+            &format!("Couldn't check {} address(es) using AbuseIPDB: {}", failed_requests.len(), error)
+        );
+    }
+
+    for connection in connections.iter_mut() {
+        connection.threat_score = cache.get(&string_utils::format_address_as(&string_utils::normalize_address(&connection.remote_address), string_utils::IpFormat::Bracketed)).map(|(score, _)| *score);
+    }
+    write_cache(&cache);
 }
 
 
@@ -65,7 +257,8 @@ pub async fn check_address_for_abuse(remote_address: &String, verbose: bool) ->
 /// * `Localhost`: Represents the localhost/127.0.0.1 address.
 /// * `Unspecified`: Represents an unspecified or wildcard address.
 /// * `Extern`: Represents an external address.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum IPType {
     Localhost,
     Unspecified,
@@ -84,13 +277,243 @@ pub enum IPType {
 /// 
 /// # Returns
 /// The address-type as an IPType enum.
-pub fn check_address_type(remote_address: &str) -> IPType {
-    if remote_address == "127.0.0.1" || remote_address == "[::1]" {
+pub fn check_address_type(remote_address: &IpAddr) -> IPType {
+    let remote_address: IpAddr = string_utils::normalize_address(remote_address);
+    if remote_address.is_loopback() {
         return IPType::Localhost;
     }
-    else if remote_address == "0.0.0.0" || remote_address == "[::]" {
+    else if remote_address.is_unspecified() {
         return IPType::Unspecified;
     }
     IPType::Extern
 }
 
+
+/// Represents the scope of a remote address, i.e. in which kind of network the peer is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum AddressScope {
+    /// `127.0.0.0/8` or `::1`
+    Loopback,
+    /// `0.0.0.0` or `::`, e.g. the remote address of listeners
+    Unspecified,
+    /// `169.254.0.0/16` or `fe80::/10`
+    LinkLocal,
+    /// `10.0.0.0/8`, `172.16.0.0/12`, `192.168.0.0/16` or unique local addresses (`fc00::/7`)
+    Private,
+    /// The shared address space of carrier-grade NAT, `100.64.0.0/10`
+    Cgnat,
+    /// `224.0.0.0/4`, `255.255.255.255` or `ff00::/8`
+    Multicast,
+    /// Every other address, i.e. a peer on the internet
+    Public,
+}
+
+impl AddressScope {
+    /// Gets the name of the scope as displayed in the table and used by `--scope`, e.g. "link-local".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AddressScope::Loopback => "loopback",
+            AddressScope::Unspecified => "unspecified",
+            AddressScope::LinkLocal => "link-local",
+            AddressScope::Private => "private",
+            AddressScope::Cgnat => "cgnat",
+            AddressScope::Multicast => "multicast",
+            AddressScope::Public => "public",
+        }
+    }
+}
+
+
+/// Gets the scope of a remote address, IPv4-mapped addresses are treated like IPv4 addresses.
+///
+/// # Arguments
+/// * `remote_address`: The address to be checked.
+///
+/// # Example
+/// ```
+/// use somo::address_checkers::{get_address_scope, AddressScope};
+///
+/// assert_eq!(get_address_scope(&"100.72.1.9".parse().unwrap()), AddressScope::Cgnat);
+/// assert_eq!(get_address_scope(&"::ffff:192.168.1.20".parse().unwrap()), AddressScope::Private);
+/// assert_eq!(get_address_scope(&"2606:4700::1111".parse().unwrap()), AddressScope::Public);
+/// ```
+///
+/// # Returns
+/// The scope as an AddressScope enum.
+pub fn get_address_scope(remote_address: &IpAddr) -> AddressScope {
+    let remote_address: IpAddr = remote_address.to_canonical();
+    if remote_address.is_unspecified() {
+        return AddressScope::Unspecified;
+    }
+    else if remote_address.is_loopback() {
+        return AddressScope::Loopback;
+    }
+    else if remote_address.is_multicast() {
+        return AddressScope::Multicast;
+    }
+    match remote_address {
+        IpAddr::V4(address) if address.is_broadcast() => AddressScope::Multicast,
+        IpAddr::V4(address) if address.is_link_local() => AddressScope::LinkLocal,
+        IpAddr::V4(address) if address.is_private() => AddressScope::Private,
+        IpAddr::V4(address) if address.octets()[0] == 100 && address.octets()[1] & 0xc0 == 64 => AddressScope::Cgnat,
+        IpAddr::V6(address) if address.is_unicast_link_local() => AddressScope::LinkLocal,
+        IpAddr::V6(address) if address.is_unique_local() => AddressScope::Private,
+        _ => AddressScope::Public,
+    }
+}
+
+
+
+/// Represents a list of IP addresses and networks (in CIDR notation) loaded from a block- or allowlist file.
+#[derive(Debug)]
+pub struct AddressList {
+    networks: Vec<(IpAddr, u8)>
+}
+
+
+/// Parses an IP address or a network in CIDR notation, e.g "10.0.0.0/8" or "2001:db8::/32".
+/// A plain address is treated as a network with the full prefix length.
+/// 
+/// # Arguments
+/// * `network`: The address or network to parse.
+/// 
+/// # Returns
+/// The network address and the prefix length or `None` if the string is not a valid address or network.
+fn parse_network(network: &str) -> Option<(IpAddr, u8)> {
+    let (address, prefix_length) = match network.split_once('/') {
+        Some((address, prefix_length)) => (address, Some(prefix_length.parse::<u8>().ok()?)),
+        None => (network, None),
+    };
+    let address: IpAddr = string_utils::parse_remote_address(address)?;
+    let max_prefix_length: u8 = if address.is_ipv4() { 32 } else { 128 };
+
+    match prefix_length {
+        Some(prefix_length) if prefix_length > max_prefix_length => None,
+        Some(prefix_length) => Some((address, prefix_length)),
+        None => Some((address, max_prefix_length)),
+    }
+}
+
+
+/// Checks if an address is part of a network.
+/// 
+/// # Arguments
+/// * `address`: The address to check.
+/// * `network`: The network address and its prefix length.
+/// 
+/// # Returns
+/// `true` if the first `prefix_length` bits of both addresses are equal, `false` if not or if the address families differ.
+fn network_contains(address: &IpAddr, network: &(IpAddr, u8)) -> bool {
+    let mask = |bits: u32, prefix_length: u8| u128::MAX.checked_shl(bits - prefix_length as u32).unwrap_or(0);
+    match (address, network) {
+        (IpAddr::V4(address), (IpAddr::V4(network), prefix_length)) => {
+            let mask = mask(32, *prefix_length) as u32;
+            u32::from(*address) & mask == u32::from(*network) & mask
+        }
+        (IpAddr::V6(address), (IpAddr::V6(network), prefix_length)) => {
+            let mask = mask(128, *prefix_length);
+            u128::from(*address) & mask == u128::from(*network) & mask
+        }
+        _ => false
+    }
+}
+
+
+impl AddressList {
+    /// Checks if a remote address matches any address or network of the list.
+    /// 
+    /// # Arguments
+    /// * `remote_address`: The address to check.
+    /// 
+    /// # Returns
+    /// `true` if the address is on the list, `false` if not.
+    pub fn contains(&self, remote_address: &IpAddr) -> bool {
+        let remote_address: IpAddr = string_utils::normalize_address(remote_address);
+        self.networks.iter().any(|network| network_contains(&remote_address, network))
+    }
+}
+
+
+/// Loads a list of IP addresses and networks from a file containing one address or network in CIDR notation per line.
+/// Empty lines and comments (starting with `#` or `;`, as used by common threat feeds) are ignored, invalid lines are skipped with a warning.
+/// 
+/// # Arguments
+/// * `path`: The path to the file.
+/// 
+/// # Returns
+/// The loaded list or an error if the file couldn't be read.
+pub fn load_address_list(path: &str) -> Result<AddressList, std::io::Error> {
+    let content = fs::read_to_string(path)?;
+
+    let mut networks: Vec<(IpAddr, u8)> = Vec::new();
+    let mut invalid_lines: usize = 0;
+    for line in content.lines() {
+        let entry = line.split(['#', ';']).next().unwrap_or_default().trim();
+        if entry.is_empty() {
+            continue;
+        }
+        match parse_network(entry) {
+            Some(network) => networks.push(network),
+            None => invalid_lines += 1,
+        }
+    }
+
+    if invalid_lines > 0 {
+        string_utils::pretty_print_warning(&format!("Skipped {} invalid line(s) in '{}'.", invalid_lines, path));
+    }
+    Ok(AddressList { networks })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_network() {
+        assert_eq!(parse_network("10.0.0.0/8"), Some(("10.0.0.0".parse().unwrap(), 8)));
+        assert_eq!(parse_network("2001:db8::/32"), Some(("2001:db8::".parse().unwrap(), 32)));
+        assert_eq!(parse_network("1.2.3.4"), Some(("1.2.3.4".parse().unwrap(), 32)));
+        assert_eq!(parse_network("::1"), Some(("::1".parse().unwrap(), 128)));
+        assert_eq!(parse_network("10.0.0.0/33"), None);
+        assert_eq!(parse_network("10.0.0.0/"), None);
+        assert_eq!(parse_network("not an address"), None);
+    }
+
+    #[test]
+    fn test_network_contains() {
+        let network = parse_network("192.168.0.0/16").unwrap();
+        assert!(network_contains(&"192.168.42.1".parse().unwrap(), &network));
+        assert!(!network_contains(&"192.169.0.1".parse().unwrap(), &network));
+        assert!(!network_contains(&"::ffff:192.168.42.1".parse().unwrap(), &network));
+
+        let network = parse_network("2001:db8::/32").unwrap();
+        assert!(network_contains(&"2001:db8:1::1".parse().unwrap(), &network));
+        assert!(!network_contains(&"2001:db9::1".parse().unwrap(), &network));
+
+        // a prefix length of 0 matches every address of the family
+        assert!(network_contains(&"8.8.8.8".parse().unwrap(), &parse_network("0.0.0.0/0").unwrap()));
+        assert!(network_contains(&"1.2.3.4".parse().unwrap(), &parse_network("1.2.3.4").unwrap()));
+        assert!(!network_contains(&"1.2.3.5".parse().unwrap(), &parse_network("1.2.3.4").unwrap()));
+    }
+
+    #[test]
+    fn test_load_address_list() {
+        let path = env::temp_dir().join(format!("somo-address-list-{}.txt", std::process::id()));
+        fs::write(&path, "# a threat feed\n1.2.3.4\n\n10.0.0.0/8 ; private\n2001:db8::/32\nnot an address\n").unwrap();
+        let address_list = load_address_list(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let address_list = address_list.unwrap();
+        assert_eq!(address_list.networks.len(), 3);
+        assert!(address_list.contains(&"1.2.3.4".parse().unwrap()));
+        assert!(address_list.contains(&"10.20.30.40".parse().unwrap()));
+        assert!(address_list.contains(&"::ffff:10.0.0.1".parse().unwrap()));
+        assert!(address_list.contains(&"2001:db8::1".parse().unwrap()));
+        assert!(!address_list.contains(&"1.2.3.5".parse().unwrap()));
+
+        assert!(load_address_list("/nonexistent/somo-address-list").is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use maxminddb::{geoip2, Reader};
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::address_checkers;
+use crate::connections;
+use crate::string_utils;
+
+/// Default locations of the GeoLite2 City database used by common distribution packages and `geoipupdate`.
+static DEFAULT_GEOIP_DATABASES: [&str; 4] = [
+    "/usr/share/GeoIP/GeoLite2-City.mmdb",
+    "/var/lib/GeoIP/GeoLite2-City.mmdb",
+    "/usr/share/GeoIP/GeoLite2-Country.mmdb",
+    "/var/lib/GeoIP/GeoLite2-Country.mmdb",
+];
+
+/// Default locations of the GeoLite2 ASN database used by common distribution packages and `geoipupdate`.
+static DEFAULT_ASN_DATABASES: [&str; 2] = [
+    "/usr/share/GeoIP/GeoLite2-ASN.mmdb",
+    "/var/lib/GeoIP/GeoLite2-ASN.mmdb",
+];
+
+
+/// Opens a MaxMind database, falling back to the given default locations if no path is provided.
+///
+/// # Arguments
+/// * `database_path`: The path to the `.mmdb` database file provided by the user.
+/// * `default_paths`: The locations to try if no path was provided.
+/// * `flag`: The flag used to specify the database, mentioned in the error message.
+///
+/// # Returns
+/// The database reader if a database could be opened, if not `None`.
+fn open_database(database_path: &Option<String>, default_paths: &[&str], flag: &str) -> Option<Reader<Vec<u8>>> {
+    if let Some(path) = database_path {
+        return match Reader::open_readfile(path) {
+            Ok(reader) => Some(reader),
+            Err(error) => {
+                string_utils::pretty_print_error(&format!("Couldn't open database '{}': {}", path, error));
+                None
+            }
+        };
+    }
+
+    let default_database = default_paths.iter().find(|path| Path::new(path).exists());
+    match default_database {
+        Some(path) => Reader::open_readfile(path).ok(),
+        None => {
+            string_utils::pretty_print_error(&format!("Couldn't find a GeoLite2 database, specify one using `{}`.", flag));
+            None
+        }
+    }
+}
+
+
+/// Opens a MaxMind GeoLite2 (City or Country) database.
+///
+/// # Arguments
+/// * `database_path`: The path to the `.mmdb` database file provided by the user.
+///
+/// # Returns
+/// The database reader if a database could be opened, if not `None`.
+pub fn open_geoip_database(database_path: &Option<String>) -> Option<Reader<Vec<u8>>> {
+    open_database(database_path, &DEFAULT_GEOIP_DATABASES, "--geoip-db")
+}
+
+
+/// Opens a MaxMind GeoLite2 ASN database.
+///
+/// # Arguments
+/// * `database_path`: The path to the `.mmdb` database file provided by the user.
+///
+/// # Returns
+/// The database reader if a database could be opened, if not `None`.
+pub fn open_asn_database(database_path: &Option<String>) -> Option<Reader<Vec<u8>>> {
+    open_database(database_path, &DEFAULT_ASN_DATABASES, "--asn-db")
+}
+
+
+/// Looks up the country ISO code and the english city name of an IP address.
+///
+/// # Arguments
+/// * `reader`: The opened GeoLite2 database.
+/// * `remote_address`: The address to look up.
+///
+/// # Returns
+/// A tuple containing the country and the city, each `None` if the database doesn't contain the information.
+fn lookup_location(reader: &Reader<Vec<u8>>, remote_address: &IpAddr) -> (Option<String>, Option<String>) {
+    let location: Option<geoip2::City> = reader.lookup(*remote_address).ok();
+    match location {
+        Some(location) => {
+            let country = location.country.and_then(|country| country.iso_code).map(String::from);
+            let city = location.city
+                .and_then(|city| city.names)
+                .and_then(|names| names.get("en").map(|name| name.to_string()));
+            (country, city)
+        }
+        None => (None, None),
+    }
+}
+
+
+/// Adds the country and city of the remote address to every connection with an extern remote address.
+///
+/// # Arguments
+/// * `connections`: The connections to annotate.
+/// * `reader`: The opened GeoLite2 database.
+///
+/// # Returns
+/// None
+pub fn annotate_locations(connections: &mut [connections::Connection], reader: &Reader<Vec<u8>>) {
+    for connection in connections.iter_mut() {
+        if let address_checkers::IPType::Extern = connection.address_type {
+            (connection.country, connection.city) = lookup_location(reader, &connection.remote_address);
+        }
+    }
+}
+
+
+/// Looks up the autonomous system number and the organization owning an IP address.
+///
+/// # Arguments
+/// * `reader`: The opened GeoLite2 ASN database.
+/// * `remote_address`: The address to look up.
+///
+/// # Returns
+/// A tuple containing the AS number and the organization name, each `None` if the database doesn't contain the information.
+fn lookup_asn(reader: &Reader<Vec<u8>>, remote_address: &IpAddr) -> (Option<u32>, Option<String>) {
+    let asn: Option<geoip2::Asn> = reader.lookup(*remote_address).ok();
+    match asn {
+        Some(asn) => (asn.autonomous_system_number, asn.autonomous_system_organization.map(String::from)),
+        None => (None, None),
+    }
+}
+
+
+/// Adds the AS number and organization of the remote address to every connection with an extern remote address.
+///
+/// # Arguments
+/// * `connections`: The connections to annotate.
+/// * `reader`: The opened GeoLite2 ASN database.
+///
+/// # Returns
+/// None
+pub fn annotate_asns(connections: &mut [connections::Connection], reader: &Reader<Vec<u8>>) {
+    for connection in connections.iter_mut() {
+        if let address_checkers::IPType::Extern = connection.address_type {
+            (connection.asn, connection.org) = lookup_asn(reader, &connection.remote_address);
+        }
+    }
+}
@@ -1,16 +1,40 @@
-use clap::Parser;
-use inquire::Select;
+use clap::{ArgGroup, ArgMatches, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap::parser::ValueSource;
+use inquire::{Confirm, MultiSelect, Select, Text};
 use inquire::InquireError;
-use std::{process};
+use inquire::list_option::ListOption;
+use inquire::validator::Validation;
+use std::{env, process};
+use std::collections::HashSet;
+use log::info;
+use std::io::{IsTerminal, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
 use std::string::String;
+use crate::address_checkers;
+use crate::columns;
+use crate::completions;
+use crate::config;
 use crate::connections;
+use crate::firewall;
+use crate::pager;
+use crate::record;
 use crate::string_utils;
+use crate::system_log;
+use crate::table;
+use termimad::crossterm::style::Color;
 
 /// Used for parsing all the flags values provided by the user in the CLI.
 #[derive(Debug)]
 pub struct FlagValues {
-    pub check: bool,
+    pub check_abuse: bool,
     pub kill: bool,
+    pub signal: Option<String>,
+    pub kill_all: bool,
+    pub close_socket: bool,
+    pub kill_index: Option<usize>,
+    pub yes: bool,
+    pub dry_run: bool,
     pub proto: Option<String>,
     pub ip: Option<String>,
     pub port: Option<String>,
@@ -18,21 +42,101 @@ pub struct FlagValues {
     pub program: Option<String>,
     pub pid: Option<String>,
     pub open: bool,
-    pub exclude_ipv6: bool
+    pub state: Vec<connections::State>,
+    pub scope: Option<address_checkers::AddressScope>,
+    pub exclude_ipv6: bool,
+    pub collapse_v4mapped: bool,
+    pub merge_dual_stack: bool,
+    pub raw: bool,
+    pub sctp: bool,
+    pub dccp: bool,
+    pub geo: bool,
+    pub geoip_db: Option<String>,
+    pub asn: bool,
+    pub mdns: bool,
+    pub asn_db: Option<String>,
+    pub annotate_ports: bool,
+    pub services_file: Option<String>,
+    pub extended_ports: bool,
+    pub ephemeral_range: Option<PortRange>,
+    pub plugins: Vec<String>,
+    pub blocklist: Option<String>,
+    pub only_blocked: bool,
+    pub allowlist: Option<String>,
+    pub own_only: bool,
+    pub all_users: bool,
+    pub session: bool,
+    pub hints: bool,
+    pub columns: Vec<columns::Column>,
+    pub no_index: bool,
+    pub id: bool,
+    pub group_by: Option<columns::Column>,
+    pub group_sort: Option<columns::SortKey>,
+    pub tree: bool,
+    pub by_remote: bool,
+    pub sort: Option<columns::SortKey>,
+    pub reverse: bool,
+    pub limit: Option<usize>,
+    pub tail: Option<usize>,
+    pub count: bool,
+    pub fail_if_none: bool,
+    pub fail_if_any: bool,
+    pub quiet: bool,
+    pub color: ColorMode,
+    pub ip_format: string_utils::IpFormat,
+    pub paging: pager::PagingMode,
+    pub pager: Option<String>,
+    pub pager_min_lines: Option<usize>,
+    pub ascii: bool,
+    pub summary: table::SummaryMode,
+    pub state_colors: Vec<(connections::State, Color)>,
+    pub color_rows: bool,
+    pub zebra: bool,
+    pub width: Option<u16>,
+    pub truncate: Vec<(columns::Column, usize)>,
+    pub no_truncate: bool,
+    pub format: Option<String>,
+    pub format_file: Option<String>,
+    pub format_header: Option<String>,
+    pub format_footer: Option<String>,
+    pub json_envelope: bool,
+    pub replay: Option<String>,
+    pub no_permission_hint: bool,
+    pub timing: bool,
+    pub profile: Option<String>,
+    pub command: Option<Commands>
 }
 
 
 /// Represents all possible flags which can be provided by the user in the CLI.
 #[derive(Parser, Debug)] 
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_override_self = true)]
 struct Args {
-    #[arg(short = 'c', long, default_value_t = false)]
-    check: bool,
+    #[arg(short = 'c', long, alias = "check", default_value_t = false)]
+    check_abuse: bool,
 
     #[arg(short = 'k', long, default_value = None)]
     kill: bool,
 
-    #[arg(long, default_value = None)]
+    #[arg(long, default_value = None, value_parser = parse_signal)]
+    signal: Option<String>,
+
+    #[arg(long, default_value = None, conflicts_with = "kill")]
+    kill_all: bool,
+
+    #[arg(long, default_value = None, conflicts_with_all = ["kill", "kill_all"])]
+    close_socket: bool,
+
+    #[arg(long, default_value = None, conflicts_with_all = ["kill", "kill_all", "close_socket"])]
+    kill_index: Option<usize>,
+
+    #[arg(short = 'y', long, default_value_t = false, requires = "kill_index")]
+    yes: bool,
+
+    #[arg(long, default_value_t = false, requires = "kill_index")]
+    dry_run: bool,
+
+    #[arg(long, default_value = None, value_parser = connections::PROTOCOLS)]
     proto: Option<String>,
 
     #[arg(long, default_value = None)]
@@ -53,12 +157,689 @@ struct Args {
     #[arg(short = 'o', long, default_value_t = false)]
     open: bool,
 
+    #[arg(long, value_delimiter = ',', value_parser = |state: &str| state.parse::<connections::State>())]
+    state: Vec<connections::State>,
+
+    #[arg(long, default_value = None)]
+    scope: Option<address_checkers::AddressScope>,
+
     #[arg(short = 'e', long, default_value_t = false)]
     exclude_ipv6: bool,
+
+    #[arg(long, default_value_t = false)]
+    collapse_v4mapped: bool,
+
+    #[arg(long, default_value_t = false)]
+    merge_dual_stack: bool,
+
+    #[arg(long, default_value = None)]
+    raw: bool,
+
+    #[arg(long, default_value = None)]
+    sctp: bool,
+
+    #[arg(long, default_value = None)]
+    dccp: bool,
+
+    #[arg(long, default_value_t = false)]
+    geo: bool,
+
+    #[arg(long, default_value_t = false)]
+    mdns: bool,
+
+    #[arg(long, default_value = None)]
+    geoip_db: Option<String>,
+
+    #[arg(long, default_value_t = false)]
+    asn: bool,
+
+    #[arg(long, default_value = None)]
+    asn_db: Option<String>,
+
+    #[arg(long, default_value = None)]
+    annotate_ports: bool,
+
+    #[arg(long, default_value = None)]
+    services_file: Option<String>,
+
+    #[arg(long, default_value = None, requires = "annotate_ports")]
+    extended_ports: bool,
+
+    #[arg(long, default_value = None, requires = "annotate_ports", value_parser = parse_port_range)]
+    ephemeral_range: Option<PortRange>,
+
+    #[arg(long = "plugin")]
+    plugins: Vec<String>,
+
+    #[arg(long, default_value = None)]
+    blocklist: Option<String>,
+
+    #[arg(long, default_value_t = false, requires = "blocklist")]
+    only_blocked: bool,
+
+    #[arg(long, default_value = None)]
+    allowlist: Option<String>,
+
+    #[arg(long, default_value_t = false)]
+    own_only: bool,
+
+    #[arg(long, default_value_t = false)]
+    all_users: bool,
+
+    #[arg(long, default_value_t = false)]
+    session: bool,
+
+    #[arg(long, default_value_t = false)]
+    hints: bool,
+
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<columns::Column>,
+
+    #[arg(long, default_value_t = false)]
+    no_index: bool,
+
+    #[arg(long, default_value_t = false)]
+    id: bool,
+
+    #[arg(long, default_value = None)]
+    group_by: Option<columns::Column>,
+
+    #[arg(long, default_value = None, value_parser = columns::parse_sort_key, requires = "group_by")]
+    group_sort: Option<columns::SortKey>,
+
+    #[arg(long, default_value_t = false, conflicts_with = "group_by")]
+    tree: bool,
+
+    #[arg(long, default_value_t = false, conflicts_with_all = ["group_by", "tree"])]
+    by_remote: bool,
+
+    #[arg(long, default_value = None, value_parser = columns::parse_sort_key)]
+    sort: Option<columns::SortKey>,
+
+    #[arg(long, default_value_t = false)]
+    reverse: bool,
+
+    #[arg(long, default_value = None)]
+    limit: Option<usize>,
+
+    #[arg(long, default_value = None, conflicts_with = "limit")]
+    tail: Option<usize>,
+
+    #[arg(long, default_value_t = false)]
+    count: bool,
+
+    #[arg(long, default_value_t = false, conflicts_with = "fail_if_any")]
+    fail_if_none: bool,
+
+    #[arg(long, default_value_t = false)]
+    fail_if_any: bool,
+
+    #[arg(short = 'q', long, default_value_t = false)]
+    quiet: bool,
+
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    #[arg(long, value_enum, default_value_t = string_utils::IpFormat::Bracketed)]
+    ip_format: string_utils::IpFormat,
+
+    #[arg(long, value_enum, default_value_t = pager::PagingMode::Never)]
+    paging: pager::PagingMode,
+
+    #[arg(long, default_value = None)]
+    pager: Option<String>,
+
+    #[arg(long, default_value = None)]
+    pager_min_lines: Option<usize>,
+
+    #[arg(long, default_value_t = false)]
+    ascii: bool,
+
+    #[arg(long, value_enum, default_value_t = table::SummaryMode::Full)]
+    summary: table::SummaryMode,
+
+    #[arg(long = "state-color", value_parser = parse_state_color)]
+    state_colors: Vec<(connections::State, Color)>,
+
+    #[arg(long, default_value_t = false)]
+    color_rows: bool,
+
+    #[arg(long, default_value_t = false)]
+    zebra: bool,
+
+    #[arg(long, default_value = None, value_parser = clap::value_parser!(u16).range(20..))]
+    width: Option<u16>,
+
+    #[arg(long, value_delimiter = ',', value_parser = parse_truncation)]
+    truncate: Vec<(columns::Column, usize)>,
+
+    #[arg(long, default_value_t = false)]
+    no_truncate: bool,
+
+    #[arg(long, default_value = None, conflicts_with_all = ["group_by", "tree", "by_remote"])]
+    format: Option<String>,
+
+    #[arg(long, default_value = None, conflicts_with_all = ["format", "group_by", "tree", "by_remote"])]
+    format_file: Option<String>,
+
+    #[arg(long, default_value = None, conflicts_with_all = ["group_by", "tree", "by_remote"])]
+    format_header: Option<String>,
+
+    #[arg(long, default_value = None, conflicts_with_all = ["group_by", "tree", "by_remote"])]
+    format_footer: Option<String>,
+
+    #[arg(long, default_value = None, conflicts_with_all = ["format", "format_file", "format_header", "format_footer", "group_by", "tree", "by_remote"])]
+    json_envelope: bool,
+
+    #[arg(long, default_value = None)]
+    replay: Option<String>,
+
+    #[arg(long, default_value = None)]
+    no_permission_hint: bool,
+
+    #[arg(long, default_value = None)]
+    timing: bool,
+
+    #[arg(long, default_value = None)]
+    sudo: bool,
+
+    #[arg(long, default_value = None)]
+    profile: Option<String>,
+
+    #[arg(long, default_value = None)]
+    show_config: bool,
+
+    #[arg(long, default_value = None)]
+    explain: bool,
+
+    #[arg(long, default_value = None)]
+    print_cmd: bool,
+
+    #[arg(short = 'v', long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
 }
 
 
-/// Gets all flag values provided by the user in the CLI using the "clap" crate.
+/// The signals which can be chosen by name, e.g. using `--signal KILL`, and their numbers on Linux.
+static SIGNALS: [(&str, u8); 9] = [
+    ("HUP", 1),
+    ("INT", 2),
+    ("QUIT", 3),
+    ("KILL", 9),
+    ("USR1", 10),
+    ("USR2", 12),
+    ("TERM", 15),
+    ("CONT", 18),
+    ("STOP", 19),
+];
+
+/// The environment variables passed on to somo when it's run again with elevated privileges by `--sudo`.
+static ELEVATED_VARIABLES: [&str; 4] = ["ABUSEIPDB_API_KEY", "NO_COLOR", "CLICOLOR_FORCE", "XDG_CONFIG_HOME"];
+
+/// The flags selecting machine-readable output, with which errors are printed as JSON.
+static MACHINE_OUTPUT_FLAGS: [&str; 5] = ["--json-envelope", "--format", "--format-file", "--format-header", "--format-footer"];
+
+/// Represents the subcommands which can be used instead of printing the connections table.
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Prints the first port of a range which isn't used by any socket
+    FreePort {
+        /// The range of ports to search, e.g. 8000-9000
+        #[arg(long, default_value = "8000-9000", value_parser = parse_port_range)]
+        range: PortRange,
+
+        /// Only consider sockets of this protocol
+        #[arg(long, default_value = None, value_parser = connections::PROTOCOLS)]
+        proto: Option<String>,
+    },
+    /// Prints the service names of a port or the ports of a service name, e.g. `somo lookup 5432` or `somo lookup https`
+    Lookup {
+        /// The port or the name of the service
+        query: String,
+
+        /// Only consider services of this protocol
+        #[arg(long, default_value = None, value_parser = connections::PROTOCOLS)]
+        proto: Option<String>,
+    },
+    /// Interactively builds the filters, then prints the connections and the equivalent command
+    Wizard,
+    /// Prints the version with the git commit, build date, enabled features and available backends, e.g. for bug reports
+    Version {
+        /// Print the version information as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool
+    },
+    /// Lists the fields of a connection which can be used in templates and are passed to plugins
+    Fields,
+    /// Lists the vsock sockets between virtual machines and their host, e.g. of guest agents
+    Vsock,
+    /// Shows the connections of the previous output in this terminal again without scanning, e.g. `somo --proto tcp last` to narrow them down
+    Last,
+    /// Lists the listening sockets by how exposed they are (all interfaces, LAN or loopback-only), with their users and services
+    Exposure,
+    /// Reports new listening sockets (or new connections matching the filters) until interrupted, e.g. `somo watch --notify`
+    Watch {
+        /// The time between two scans, e.g. 500ms or 5s
+        #[arg(long, default_value = "2s", value_parser = humantime::parse_duration)]
+        interval: std::time::Duration,
+
+        /// Show a desktop notification for every new socket
+        #[arg(long, default_value_t = false)]
+        notify: bool,
+
+        /// Run a shell command for every new socket, `{}` is replaced by the connection as JSON
+        #[arg(long, default_value = None)]
+        on_new_connection: Option<String>,
+
+        /// Run a shell command for every closed socket, `{}` is replaced by the connection as JSON
+        #[arg(long, default_value = None)]
+        on_closed_connection: Option<String>,
+
+        /// Post every new and closed socket as JSON to this URL
+        #[arg(long, default_value = None)]
+        webhook: Option<String>,
+
+        /// Where the sockets are reported, the system log also receives closed sockets
+        #[arg(long, value_enum, default_value_t = system_log::LogTarget::Stdout)]
+        log_target: system_log::LogTarget,
+    },
+    /// Shows the busiest (matching) TCP connections by their throughput until interrupted, e.g. `somo --program firefox top`
+    Top {
+        /// The time between two samples, e.g. 500ms or 5s
+        #[arg(long, default_value = "2s", value_parser = humantime::parse_duration)]
+        interval: std::time::Duration,
+    },
+    /// Appends samples of the (matching) connections to rotating NDJSON files until interrupted, e.g. `somo record --out /var/log/somo`
+    Record {
+        /// The directory the files are written to
+        #[arg(long)]
+        out: std::path::PathBuf,
+
+        /// The time between two samples, e.g. 30s or 5m
+        #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+        interval: std::time::Duration,
+
+        /// How often a new file is started
+        #[arg(long, value_enum, default_value_t = record::Rotation::Daily)]
+        rotate: record::Rotation,
+
+        /// Delete files older than this, e.g. 7d, by default they are kept
+        #[arg(long, default_value = None, value_parser = humantime::parse_duration)]
+        retention: Option<std::time::Duration>,
+    },
+    /// Prints the completions of somo for a shell, e.g. `somo generate-completions nushell`
+    GenerateCompletions {
+        /// The shell to generate the completions for, or carapace to generate a carapace spec
+        shell: completions::CompletionShell,
+    },
+    /// Edits, shows or validates the config files
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Prints firewall rules which block a remote address, close a local port or block a connection, e.g. `somo suggest-block --ip 1.2.3.4` or `somo suggest-block --index 3`
+    #[command(group(ArgGroup::new("target").required(true).multiple(true).args(["ip", "port", "index", "id"])))]
+    SuggestBlock {
+        /// The remote address to block
+        #[arg(long, default_value = None, value_parser = parse_address)]
+        ip: Option<std::net::IpAddr>,
+
+        /// Block the remote address of this row (#) of the previous output in this terminal, on its local or remote port
+        #[arg(long, default_value = None, conflicts_with_all = ["ip", "id"])]
+        index: Option<usize>,
+
+        /// Block the remote address of the connection of the previous output with this ID, as shown with `--id`
+        #[arg(long, default_value = None, conflicts_with = "ip")]
+        id: Option<String>,
+
+        /// The local port to close (or, together with --ip, to block the address on)
+        #[arg(short = 'p', long, default_value = None)]
+        port: Option<u16>,
+
+        /// The protocol of the port
+        #[arg(long, default_value = "tcp", value_parser = ["tcp", "udp"])]
+        proto: String,
+
+        /// Only print the rules of this firewall
+        #[arg(long, default_value = None)]
+        firewall: Option<firewall::Firewall>,
+    },
+    /// Kills the processes of the matching connections without the interactive prompt, e.g. `somo kill --port 8080 --yes`
+    #[command(group(ArgGroup::new("target").required(true).multiple(true).args(["index", "port", "program", "id"])))]
+    Kill {
+        /// Only kill the process of this row (#) of the previous output in this terminal
+        index: Option<usize>,
+
+        /// Only kill the processes using this local port
+        #[arg(short = 'p', long, default_value = None)]
+        port: Option<String>,
+
+        /// Only kill the processes of this program
+        #[arg(long, default_value = None)]
+        program: Option<String>,
+
+        /// Only kill the process of the connection with this ID, as shown with `--id`
+        #[arg(long, default_value = None)]
+        id: Option<String>,
+
+        /// Only consider sockets of this protocol
+        #[arg(long, default_value = None, value_parser = connections::PROTOCOLS)]
+        proto: Option<String>,
+
+        /// The signal to send, e.g. KILL or 9
+        #[arg(long, default_value = "TERM", value_parser = parse_signal)]
+        signal: String,
+
+        /// Kill without asking for confirmation
+        #[arg(short = 'y', long, default_value_t = false)]
+        yes: bool,
+
+        /// Only print the processes which would be killed
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+
+/// Represents the subcommands of `somo config`.
+#[derive(Subcommand, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigAction {
+    /// Opens the user's config file in $VISUAL or $EDITOR
+    Edit,
+    /// Prints the flags applied by every config file and the templates they define
+    Show,
+    /// Checks the config files for syntax errors, unknown flags, unknown sections and invalid values
+    Validate,
+}
+
+
+/// Represents when the output should be colored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Color the output if stdout is a terminal, respecting `NO_COLOR` and `CLICOLOR_FORCE`
+    Auto,
+    Always,
+    Never,
+}
+
+
+/// Decides if the output should be colored.
+/// In `auto` mode a non-empty `NO_COLOR` disables colors, a `CLICOLOR_FORCE` other than "0" enables them
+/// and otherwise colors are only used if stdout is a terminal (i.e. not piped or redirected).
+/// 
+/// # Arguments
+/// * `color_mode`: The color mode selected by the user.
+/// 
+/// # Returns
+/// `true` if the output should be colored.
+pub fn use_colors(color_mode: ColorMode) -> bool {
+    let env_set = |name: &str, off_value: &str| env::var(name).is_ok_and(|value| value != off_value);
+    match color_mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto if env_set("NO_COLOR", "") => false,
+        ColorMode::Auto if env_set("CLICOLOR_FORCE", "0") => true,
+        ColorMode::Auto => std::io::stdout().is_terminal(),
+    }
+}
+
+
+/// Parses the color of a connection state in the format `<state>=<color>`, e.g. "close_wait=magenta".
+/// 
+/// # Arguments
+/// * `state_color`: The state and color to parse.
+/// 
+/// # Returns
+/// The state and the color or an error message if the state or the color is unknown.
+fn parse_state_color(state_color: &str) -> Result<(connections::State, Color), String> {
+    let (state, color) = state_color.split_once('=').ok_or("expected a state and a color like listen=blue")?;
+    let state: connections::State = state.trim().parse()?;
+    let color: Color = Color::try_from(color.trim()).map_err(|_| format!("unknown color '{}'", color))?;
+    Ok((state, color))
+}
+
+
+/// Parses the max length of the values of a column in the format `<column>=<length>`, e.g. "program=12".
+/// 
+/// # Arguments
+/// * `truncation`: The column and length to parse.
+/// 
+/// # Returns
+/// The column and the max length or an error message if the column or length is invalid.
+fn parse_truncation(truncation: &str) -> Result<(columns::Column, usize), String> {
+    let (column, max_length) = truncation.split_once('=').ok_or("expected a column and a length like program=12")?;
+    let column: columns::Column = columns::Column::from_str(column.trim(), true)?;
+    let max_length: usize = max_length.trim().parse().ok().filter(|max_length| *max_length > 0).ok_or(format!("invalid length '{}'", max_length))?;
+    Ok((column, max_length))
+}
+
+
+/// Gets the theme used to color connections by their state, overriding the default colors with the colors selected by the user.
+/// 
+/// # Arguments
+/// * `state_colors`: The colors of states selected by the user.
+/// * `color_rows`: If `true` the whole row is colored instead of only the state cell.
+/// * `zebra`: If `true` every second row is shaded.
+/// 
+/// # Returns
+/// The theme.
+pub fn get_theme(state_colors: &[(connections::State, Color)], color_rows: bool, zebra: bool) -> table::Theme {
+    let mut theme: table::Theme = table::Theme { color_rows, zebra, ..Default::default() };
+    for (state, color) in state_colors {
+        theme.state_colors.retain(|(themed_state, _)| themed_state != state);
+        theme.state_colors.push((*state, *color));
+    }
+    theme
+}
+
+
+/// Represents an inclusive range of ports.
+#[derive(Clone, Debug)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16
+}
+
+
+/// Parses a signal given by its name (with or without the "SIG" prefix, e.g. "SIGKILL" or "int") or its number (e.g. "9").
+/// 
+/// # Arguments
+/// * `signal`: The signal to parse.
+/// 
+/// # Returns
+/// The name of the signal without the prefix (e.g. "KILL"), or the number if the signal has no name in `SIGNALS`, or an error message if the signal is invalid.
+fn parse_signal(signal: &str) -> Result<String, String> {
+    let signal: String = signal.trim().to_uppercase();
+    let signal: &str = signal.strip_prefix("SIG").unwrap_or(&signal);
+    if let Ok(number) = signal.parse::<u8>() {
+        return match SIGNALS.iter().find(|(_, signal_number)| *signal_number == number) {
+            Some((name, _)) => Ok(name.to_string()),
+            None if (1..=64).contains(&number) => Ok(number.to_string()),
+            None => Err(format!("invalid signal number '{}'", number))
+        };
+    }
+    match SIGNALS.iter().find(|(name, _)| *name == signal) {
+        Some((name, _)) => Ok(name.to_string()),
+        None => Err(format!("unknown signal '{}', expected one of {} or a number", signal, SIGNALS.map(|(name, _)| name).join(", ")))
+    }
+}
+
+
+/// Parses an IP address, IPv6 addresses may be wrapped in brackets like in the table.
+/// 
+/// # Arguments
+/// * `address`: The address to parse.
+/// 
+/// # Returns
+/// The address or an error message if it is invalid.
+fn parse_address(address: &str) -> Result<std::net::IpAddr, String> {
+    string_utils::parse_remote_address(address).ok_or(format!("invalid IP address '{}'", address))
+}
+
+
+/// Parses a range of ports in the format `<start>-<end>`, e.g. "8000-9000".
+/// 
+/// # Arguments
+/// * `range`: The range to parse.
+/// 
+/// # Returns
+/// The parsed range or an error message if the range is invalid.
+fn parse_port_range(range: &str) -> Result<PortRange, String> {
+    let (start, end) = range.split_once('-').ok_or("expected a range like 8000-9000")?;
+    let start: u16 = start.trim().parse().map_err(|_| format!("invalid port '{}'", start))?;
+    let end: u16 = end.trim().parse().map_err(|_| format!("invalid port '{}'", end))?;
+    if start > end {
+        return Err(format!("the start of the range ({}) is greater than its end ({})", start, end));
+    }
+    Ok(PortRange { start, end })
+}
+
+
+/// Prints diagnostic messages of somo (e.g. which files were read) to stderr, `-v` enables infos, `-vv` debug and `-vvv` trace messages.
+///
+/// # Arguments
+/// * `verbosity`: How often `-v` was given.
+///
+/// # Returns
+/// None
+fn init_logging(verbosity: u8) {
+    let level: log::LevelFilter = match verbosity {
+        0 => log::LevelFilter::Off,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    // the wizard parses the flags a second time, the logger of the first time is kept
+    let _ = env_logger::Builder::new()
+        .filter_module("somo", level)
+        .format(|output, record| writeln!(output, "[{}] {}", record.level().as_str().to_lowercase(), record.args()))
+        .try_init();
+}
+
+
+/// Gets the definition of all flags and subcommands of somo, e.g. for generating shell completions.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The clap command.
+pub fn get_command() -> clap::Command {
+    Args::command()
+}
+
+
+/// Parses the flags of the config files followed by the ones provided in the CLI, exiting if they are invalid.
+/// Since the errors of clap don't tell where a flag comes from, errors caused by a config file name it.
+/// With `--show-config` the flags applied by every config file are printed to stderr, e.g. "Via config: --proto tcp".
+///
+/// # Arguments
+/// * `cli_args`: The arguments provided in the CLI, starting with the program name.
+///
+/// # Returns
+/// The parsed flags.
+fn parse_config_args(cli_args: &[String]) -> Args {
+    let sources: Vec<config::ConfigSource> = config::get_checked_config_sources(config::get_profile_name(cli_args).as_deref());
+    let merged_args: Vec<String> = config::merge_config_args(cli_args, &sources);
+    let error: clap::Error = match Args::try_parse_from(&merged_args) {
+        Ok(args) => {
+            init_logging(args.verbose);
+            for source in sources.iter().filter(|source| !source.args.is_empty()) {
+                info!("Applying {} from {}", format_command(&source.args), source.name);
+                if args.show_config {
+                    serr!("Via config: {} ({})", format_command(&source.args), source.name);
+                }
+            }
+            if args.explain {
+                serr!("Explain: {}", explain_args(&args, &sources));
+            }
+            if args.print_cmd {
+                serr!("{}", get_effective_command(&merged_args));
+            }
+            return args;
+        }
+        Err(error) => error,
+    };
+
+    let machine_output: bool = MACHINE_OUTPUT_FLAGS.iter().any(|flag| uses_flag(cli_args, &sources, flag));
+    string_utils::set_json_errors(machine_output);
+
+    // blame the config file whose flags are invalid on their own, or else all of them if only their combination with the CLI is invalid
+    let parses = |args: &[String]| Args::try_parse_from(cli_args.iter().take(1).chain(args)).is_ok();
+    let note: Option<String> = match sources.iter().find(|source| !parses(&source.args)) {
+        Some(source) => Some(format!("the invalid flag comes from the config file '{}', check it using `somo config validate`", source.name)),
+        None if !sources.is_empty() && parses(&cli_args[1..]) => Some(format!(
+            "the flags conflict with the ones of the config files: {}",
+            format_command(&sources.into_iter().flat_map(|source| source.args).collect::<Vec<String>>())
+        )),
+        None => None,
+    };
+    if machine_output && error.use_stderr() {
+        // only the first line of clap's message, without the "error: " prefix and the usage
+        let rendered: String = error.render().to_string();
+        let message: &str = rendered.lines().next().unwrap_or_default().trim_start_matches("error: ");
+        let message: String = match note {
+            Some(note) => format!("{} (note: {})", message, note),
+            None => message.to_string(),
+        };
+        string_utils::print_error("usage", &message);
+        process::exit(2);
+    }
+    match note {
+        Some(note) if error.use_stderr() => {
+            let _ = error.print();
+            serr!("note: {}", note);
+            // the status clap exits with on usage errors
+            process::exit(2);
+        }
+        _ => error.exit(),
+    }
+}
+
+
+/// Checks if a flag is given in the CLI or a config file, e.g. to know if the output is machine-readable before the arguments could be parsed.
+///
+/// # Arguments
+/// * `cli_args`: The arguments of the CLI, starting with the program name.
+/// * `sources`: The config files and profile sections.
+/// * `flag`: The flag, e.g. "--json-envelope".
+///
+/// # Returns
+/// `true` if the flag is given, with or without an attached value like `--format=...`.
+fn uses_flag(cli_args: &[String], sources: &[config::ConfigSource], flag: &str) -> bool {
+    cli_args.iter().skip(1).chain(sources.iter().flat_map(|source| &source.args))
+        .any(|arg| arg == flag || arg.strip_prefix(flag).is_some_and(|rest| rest.starts_with('=')))
+}
+
+
+/// Finds the position at which clap parsed a subcommand in the arguments of the CLI,
+/// so that a flag value with the same name (e.g. in `somo --program wizard wizard`) isn't mistaken for it.
+///
+/// # Arguments
+/// * `cli_args`: The arguments of the CLI, starting with the executable.
+/// * `subcommand`: The name of the subcommand, e.g. "wizard".
+///
+/// # Returns
+/// The position of the subcommand or `None` if the arguments can't be parsed or don't contain it.
+fn get_subcommand_position(cli_args: &[String], subcommand: &str) -> Option<usize> {
+    let matches: ArgMatches = Args::command().try_get_matches_from(cli_args).ok()?;
+    if matches.subcommand_name() != Some(subcommand) {
+        return None;
+    }
+    // the indices of the flags given before the subcommand and their values, which clap counts like the positions of the arguments
+    let value_positions: HashSet<usize> = matches.ids()
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        .filter_map(|id| matches.indices_of(id.as_str()))
+        .flatten()
+        .collect();
+    (1..cli_args.len()).find(|position| cli_args[*position] == subcommand && !value_positions.contains(position))
+}
+
+
+/// Gets all flag values provided by the user in the CLI and the config files using the "clap" crate.
 /// 
 /// # Arguments
 /// None
@@ -66,11 +847,43 @@ struct Args {
 /// # Returns
 /// A struct containing all the flag values.
 pub fn cli() -> FlagValues {
-    let args = Args::parse();
+    // `somo config` and the completions work without applying the config files, since they may be invalid
+    let cli_args: Vec<String> = env::args().collect();
+    // errors which occur before the config files are applied (e.g. of `--profile`) depend on the CLI only
+    string_utils::set_json_errors(MACHINE_OUTPUT_FLAGS.iter().any(|flag| uses_flag(&cli_args, &[], flag)));
+    let mut args = match Args::try_parse_from(&cli_args) {
+        Ok(args) if matches!(args.command, Some(Commands::Config { .. }) | Some(Commands::GenerateCompletions { .. })) => {
+            init_logging(args.verbose);
+            args
+        }
+        _ => parse_config_args(&cli_args),
+    };
+    if args.sudo && connections::get_effective_uid() != 0 {
+        string_utils::set_colors(use_colors(args.color));
+        rerun_elevated();
+    }
+
+    if let Some(Commands::Wizard) = args.command {
+        // the choices are added to the other flags of the CLI (e.g. `somo --json wizard`) in place of the subcommand
+        let mut wizard_args: Vec<String> = cli_args.clone();
+        if let Some(position) = get_subcommand_position(&cli_args, "wizard") {
+            wizard_args.remove(position);
+        }
+        wizard_args.extend(run_wizard());
+        string_utils::pretty_print_info(&format!("Equivalent command: `{}`", format_command(&wizard_args)));
+        args = parse_config_args(&wizard_args);
+    }
+    string_utils::set_json_errors(args.json_envelope || args.format.is_some() || args.format_file.is_some() || args.format_header.is_some() || args.format_footer.is_some());
 
     FlagValues {
-        check: args.check,
+        check_abuse: args.check_abuse,
         kill: args.kill,
+        signal: args.signal,
+        kill_all: args.kill_all,
+        close_socket: args.close_socket,
+        kill_index: args.kill_index,
+        yes: args.yes,
+        dry_run: args.dry_run,
         proto: args.proto,
         ip: args.ip,
         program: args.program,
@@ -78,50 +891,621 @@ pub fn cli() -> FlagValues {
         local_port: args.local_port,
         pid: args.pid,
         open: args.open,
-        exclude_ipv6: args.exclude_ipv6
+        state: args.state,
+        scope: args.scope,
+        exclude_ipv6: args.exclude_ipv6,
+        collapse_v4mapped: args.collapse_v4mapped,
+        merge_dual_stack: args.merge_dual_stack,
+        raw: args.raw,
+        sctp: args.sctp,
+        dccp: args.dccp,
+        geo: args.geo,
+        geoip_db: args.geoip_db,
+        asn: args.asn,
+        mdns: args.mdns,
+        asn_db: args.asn_db,
+        annotate_ports: args.annotate_ports,
+        services_file: args.services_file,
+        extended_ports: args.extended_ports,
+        ephemeral_range: args.ephemeral_range,
+        plugins: args.plugins,
+        blocklist: args.blocklist,
+        only_blocked: args.only_blocked,
+        allowlist: args.allowlist,
+        own_only: args.own_only,
+        all_users: args.all_users,
+        session: args.session,
+        hints: args.hints,
+        columns: args.columns,
+        no_index: args.no_index,
+        id: args.id,
+        group_by: args.group_by,
+        group_sort: args.group_sort,
+        tree: args.tree,
+        by_remote: args.by_remote,
+        sort: args.sort,
+        reverse: args.reverse,
+        limit: args.limit,
+        tail: args.tail,
+        count: args.count,
+        fail_if_none: args.fail_if_none,
+        fail_if_any: args.fail_if_any,
+        quiet: args.quiet,
+        color: args.color,
+        ip_format: args.ip_format,
+        paging: args.paging,
+        pager: args.pager,
+        pager_min_lines: args.pager_min_lines,
+        ascii: args.ascii,
+        summary: args.summary,
+        state_colors: args.state_colors,
+        color_rows: args.color_rows,
+        zebra: args.zebra,
+        width: args.width,
+        truncate: args.truncate,
+        no_truncate: args.no_truncate,
+        format: args.format,
+        format_file: args.format_file,
+        format_header: args.format_header,
+        format_footer: args.format_footer,
+        json_envelope: args.json_envelope,
+        replay: args.replay,
+        no_permission_hint: args.no_permission_hint,
+        timing: args.timing,
+        profile: args.profile,
+        command: args.command
     }
 }
 
 
-/// Kills a process by its PID.
+/// Prompts for a choice using the "inquire" crate and exits if the prompt is canceled (e.g. using Ctrl+C).
+fn prompt_or_exit<T>(answer: Result<T, InquireError>) -> T {
+    answer.unwrap_or_else(|_| process::exit(1))
+}
+
+
+/// Prompts for an optional port, rejecting everything which isn't a valid port number.
+fn prompt_port(message: &str) -> Option<String> {
+    let port: String = prompt_or_exit(Text::new(message)
+        .with_help_message("leave empty to match any port")
+        .with_validator(|input: &str| match input.trim().is_empty() || input.trim().parse::<u16>().is_ok() {
+            true => Ok(Validation::Valid),
+            false => Ok(Validation::Invalid("Not a valid port number.".into())),
+        })
+        .prompt());
+    (!port.trim().is_empty()).then(|| port.trim().to_string())
+}
+
+
+/// Interactively walks through the protocol, state, port and program filters using the "inquire" crate.
 /// 
-/// # Argument
-/// * `pid`: The PID value as a string.
+/// # Arguments
+/// None
 /// 
 /// # Returns
+/// The flags matching the choices, e.g. `["--proto", "tcp", "--state", "listen,established"]`.
+fn run_wizard() -> Vec<String> {
+    let mut wizard_args: Vec<String> = Vec::new();
+
+    let mut protocols: Vec<&str> = vec!["any"];
+    protocols.extend(connections::PROTOCOLS);
+    let proto: &str = prompt_or_exit(Select::new("Which protocol?", protocols).prompt());
+    if proto != "any" {
+        wizard_args.extend(["--proto".to_string(), proto.to_string()]);
+    }
+
+    let states: Vec<connections::State> = prompt_or_exit(MultiSelect::new("Which states?", connections::State::ALL.to_vec())
+        .with_help_message("select using space, select none to match any state")
+        .prompt());
+    if !states.is_empty() {
+        wizard_args.extend(["--state".to_string(), states.iter().map(connections::State::as_str).collect::<Vec<&str>>().join(",")]);
+    }
+
+    if let Some(port) = prompt_port("Which remote port?") {
+        wizard_args.extend(["--port".to_string(), port]);
+    }
+    if let Some(port) = prompt_port("Which local port?") {
+        wizard_args.extend(["--local-port".to_string(), port]);
+    }
+
+    // only offer programs which currently own sockets
+    let mut programs: Vec<String> = connections::get_all_connections(&connections::FilterOptions::default())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|connection| connection.program)
+        .filter(|program| program != "-")
+        .collect();
+    programs.sort();
+    programs.dedup();
+    programs.insert(0, "any".to_string());
+    let program: String = prompt_or_exit(Select::new("Which program (search or select)?", programs).prompt());
+    if program != "any" {
+        wizard_args.extend(["--program".to_string(), program]);
+    }
+
+    wizard_args
+}
+
+
+/// Finds an executable in the directories of the `PATH` environment variable.
+/// 
+/// # Arguments
+/// * `name`: The name of the executable, e.g. "sudo".
+/// 
+/// # Returns
+/// The path of the executable or `None` if it isn't installed.
+fn find_executable(name: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|directory| directory.join(name))
+        .find(|path| path.is_file())
+}
+
+
+/// Re-executes somo with the same arguments (except `--sudo`) using sudo or doas, so that the processes of all users can be resolved and killed.
+/// The user's config file, the AbuseIPDB API key and the color settings are passed on using `--preserve-env` of sudo, since sudo resets
+/// the environment. They are never put on the command line, where other users could read them (e.g. the API key).
+/// doas has no such option, it only keeps them if allowed by `keepenv` or `setenv` in `doas.conf`.
+/// 
+/// # Arguments
 /// None
-pub fn kill_process(pid: &String) {
-    let output = process::Command::new("kill")
-        .arg(pid)
-        .output()
-        .unwrap_or_else(|_| panic!("Failed to kill process with PID {}", pid));
+/// 
+/// # Returns
+/// Never, somo is replaced by the elevated process or exits with an error.
+fn rerun_elevated() -> ! {
+
+    let Some(elevator) = ["sudo", "doas"].into_iter().find_map(find_executable) else {
+        string_utils::pretty_print_error("Couldn't find sudo or doas to run somo with elevated privileges.");
+        process::exit(1);
+    };
+    let Ok(executable) = env::current_exe() else {
+        string_utils::pretty_print_error("Couldn't find the path of the somo executable.");
+        process::exit(1);
+    };
+
+    let mut command: process::Command = process::Command::new(&elevator);
+    // the config directory of the user, even if it's the default one, since the elevated process has another home directory
+    if let Some(config_directory) = config::get_user_config_path().as_deref().and_then(Path::parent).and_then(Path::parent) {
+        command.env("XDG_CONFIG_HOME", config_directory);
+    }
+    if elevator.ends_with("sudo") {
+        command.arg(format!("--preserve-env={}", ELEVATED_VARIABLES.join(",")));
+    }
+
+    let error = command
+        .arg(executable)
+        .args(env::args().skip(1).filter(|arg| arg != "--sudo"))
+        .exec();
+    string_utils::pretty_print_error(&format!("Couldn't run somo with elevated privileges: {}", error));
+    process::exit(1);
+}
+
+
+/// Describes the effective filters, sorting and sources of a run in a human-readable way, used by `--explain`,
+/// e.g. "tcp, ipv4 only, open only, local port=22, sorted by pid asc, from /proc, config profile 'server'".
+///
+/// # Arguments
+/// * `args`: The parsed flags, including the ones of the config files.
+/// * `sources`: The config files and profile sections which were applied.
+///
+/// # Returns
+/// The summary.
+fn explain_args(args: &Args, sources: &[config::ConfigSource]) -> String {
+    let column_name = |column: &columns::Column| column.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default();
+    let mut parts: Vec<String> = Vec::new();
+
+    let mut protocols: Vec<&str> = match &args.proto {
+        Some(proto) => vec![proto],
+        None => vec!["tcp", "udp", "udplite"],
+    };
+    if args.proto.is_none() {
+        protocols.extend([(args.raw, "raw"), (args.sctp, "sctp"), (args.dccp, "dccp")].into_iter().filter(|(enabled, _)| *enabled).map(|(_, proto)| proto));
+    }
+    parts.push(protocols.join("+"));
+    parts.push(match (args.exclude_ipv6, args.collapse_v4mapped) {
+        (true, true) => "ipv4 only (including ipv4-mapped)",
+        (true, false) => "ipv4 only",
+        (false, _) => "ipv4+ipv6",
+    }.to_string());
+    if args.open {
+        parts.push("open only".to_string());
+    }
+    if !args.state.is_empty() {
+        parts.push(format!("state={}", args.state.iter().map(connections::State::as_str).collect::<Vec<&str>>().join(",")));
+    }
+    if let Some(scope) = &args.scope {
+        parts.push(format!("{} remote addresses only", scope.as_str()));
+    }
+    let filters: [(&str, &Option<String>); 5] = [
+        ("remote address", &args.ip), ("remote port", &args.port), ("local port", &args.local_port), ("program", &args.program), ("pid", &args.pid)
+    ];
+    parts.extend(filters.iter().filter_map(|(name, value)| value.as_ref().map(|value| format!("{}={}", name, value))));
+    if args.own_only && !args.all_users {
+        parts.push("own connections only".to_string());
+    }
+    if let Some(allowlist) = &args.allowlist {
+        parts.push(format!("hiding the allowlist '{}'", allowlist));
+    }
+    if let Some(blocklist) = &args.blocklist {
+        parts.push(format!("{} the blocklist '{}'", if args.only_blocked { "only" } else { "marking" }, blocklist));
+    }
+
+    parts.push(match &args.sort {
+        Some(sort) => format!("sorted by {} {}", column_name(&sort.column), if sort.is_descending(args.reverse) { "desc" } else { "asc" }),
+        None => "unsorted".to_string(),
+    });
+    if let Some(group_by) = &args.group_by {
+        parts.push(format!("grouped by {}", column_name(group_by)));
+        if let Some(group_sort) = &args.group_sort {
+            parts.push(format!("sorted by {} {} within groups", column_name(&group_sort.column), if group_sort.is_descending(args.reverse) { "desc" } else { "asc" }));
+        }
+    } else if args.tree {
+        parts.push("as process tree".to_string());
+    } else if args.by_remote {
+        parts.push("per remote host".to_string());
+    }
+
+    parts.push(match (&args.replay, &args.command) {
+        (Some(path), _) => format!("replayed from '{}'", path),
+        (None, Some(Commands::Last)) => "from the previous output".to_string(),
+        (None, _) => "from /proc".to_string(),
+    });
+    let config_files: Vec<&str> = sources.iter().filter(|source| source.profile.is_none() && !source.args.is_empty()).map(|source| source.name.as_str()).collect();
+    if !config_files.is_empty() {
+        parts.push(format!("config {}", config_files.join(" and ")));
+    }
+    if let Some(source) = sources.iter().find(|source| source.profile.is_some()) {
+        let profile: &str = source.profile.as_deref().unwrap_or_default();
+        parts.push(if source.auto_selected { format!("config profile '{}' (matched this host)", profile) } else { format!("config profile '{}'", profile) });
+    }
+    parts.join(", ")
+}
+
+
+/// Formats arguments as a command which can be pasted into a shell, quoting arguments which contain whitespace or other special characters.
+/// 
+/// # Arguments
+/// * `args`: The arguments, starting with the program name.
+/// 
+/// # Returns
+/// The command as a single line.
+fn format_command(args: &[String]) -> String {
+    let is_plain = |arg: &String| !arg.is_empty() && arg.chars().all(|character| character.is_ascii_alphanumeric() || "-_./:=,@+%".contains(character));
+    args.iter()
+        .map(|arg| if is_plain(arg) { arg.to_string() } else { format!("'{}'", arg.replace('\'', "'\\''")) })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+
+/// Gets the command which reproduces the output without any config file, i.e. the flags of the config files followed by the ones of the CLI.
+/// The profile is left out since its flags are included, and so are the variables which change the output (e.g. `NO_COLOR`).
+///
+/// # Arguments
+/// * `merged_args`: The arguments of the config files and the CLI, see `config::merge_config_args`.
+///
+/// # Returns
+/// The command as a single line.
+fn get_effective_command(merged_args: &[String]) -> String {
+    let mut command: Vec<String> = ["NO_COLOR", "CLICOLOR_FORCE"].into_iter()
+        .filter_map(|name| env::var(name).ok().map(|value| format!("{}={}", name, value)))
+        .collect();
+    command.push("somo".to_string());
+
+    let mut args = merged_args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--print-cmd" => {}
+            "--profile" => { args.next(); }
+            _ if arg.starts_with("--profile=") => {}
+            _ => command.push(arg.to_string()),
+        }
+    }
+    format_command(&command)
+}
+
+
+/// Formats a signal parsed by `parse_signal` for messages, e.g. "SIGTERM" or "signal 34".
+fn format_signal(signal: &str) -> String {
+    if signal.parse::<u8>().is_ok() { format!("signal {}", signal) } else { format!("SIG{}", signal) }
+}
 
-    if output.status.success() {
-        //println!("Killed process with PID {}.", pid);
-        string_utils::pretty_print_info(&format!("Killed process with PID {}.", pid));
+
+/// Sends a signal to a process using `kill(2)`, failures are reported with their reason (e.g. a missing permission).
+/// 
+/// # Argument
+/// * `pid`: The PID of the process.
+/// * `signal`: The name (without the "SIG" prefix) or number of the signal, e.g. "TERM", as parsed by `parse_signal`.
+/// 
+/// # Returns
+/// `true` if the signal was sent.
+pub fn kill_process(pid: i32, signal: &str) -> bool {
+    let signal_number: Option<i32> = signal.parse().ok().or(SIGNALS.iter().find(|(name, _)| *name == signal).map(|(_, number)| *number as i32));
+    let Some(signal_number) = signal_number else {
+        string_utils::print_error("kill", &format!("Unknown signal {}.", signal));
+        return false;
+    };
+
+    if unsafe { libc::kill(pid, signal_number) } == 0 {
+        string_utils::pretty_print_info(&format!("Sent {} to process with PID {}.", format_signal(signal), pid));
+        return true;
     }
-    else {
-        println!("Failed to kill process, try running");
-        string_utils::pretty_print_error("Couldn't kill process! Try again using sudo: 'sudo $(where somo)'.");
+    let error: std::io::Error = std::io::Error::last_os_error();
+    let hint: &str = if error.raw_os_error() == Some(libc::EPERM) { " Try again using sudo: 'sudo $(which somo)'." } else { "" };
+    string_utils::print_error("kill", &format!("Couldn't kill the process with PID {}: {}.{}", pid, error, hint));
+    false
+}
+
+
+/// Gets the signal to send, i.e. the one provided by `--signal` or else one chosen interactively, starting at SIGTERM.
+/// 
+/// # Argument
+/// * `signal`: The signal provided by `--signal`.
+/// 
+/// # Returns
+/// The name or number of the signal or `None` if the prompt was cancelled.
+fn choose_signal(signal: &Option<String>) -> Option<String> {
+    if let Some(signal) = signal {
+        return Some(signal.to_string());
+    }
+    let names: Vec<String> = SIGNALS.iter().map(|(name, number)| format!("SIG{} ({})", name, number)).collect();
+    let default: usize = SIGNALS.iter().position(|(name, _)| *name == "TERM").unwrap_or(0);
+    Select::new("Which signal?", names).with_starting_cursor(default).raw_prompt().ok().map(|choice| SIGNALS[choice.index].0.to_string())
+}
+
+
+/// Gets the distinct processes owning connections, in the order they appear.
+/// 
+/// # Argument
+/// * `connections`: The connections.
+/// 
+/// # Returns
+/// The PIDs and programs of the processes, connections whose process is unknown are skipped.
+fn get_processes<'a>(connections: &[&'a connections::Connection]) -> Vec<(i32, &'a str)> {
+    let mut processes: Vec<(i32, &str)> = Vec::new();
+    for connection in connections {
+        if let Some(pid) = connection.pid {
+            if !processes.iter().any(|(known_pid, _)| *known_pid == pid) {
+                processes.push((pid, &connection.program));
+            }
+        }
     }
+    processes
 }
 
 
-/// Starts an interactive selection process in the console for choosing a process to kill using the "inquire" crate.
+/// Sends a signal to every process owning one of the connections.
+/// 
+/// # Argument
+/// * `connections`: The connections whose processes to kill.
+/// * `signal`: The name or number of the signal.
+/// 
+/// # Returns
+/// None, exits with 1 if a process couldn't be killed.
+fn kill_processes(connections: &[&connections::Connection], signal: &str) {
+    let processes: Vec<(i32, &str)> = get_processes(connections);
+    if processes.is_empty() {
+        string_utils::pretty_print_error("The processes of the selected connections are unknown.");
+    }
+    let failures: usize = processes.iter().filter(|(pid, _)| !kill_process(*pid, signal)).count();
+    if failures > 0 {
+        process::exit(1);
+    }
+}
+
+
+/// Describes a connection in the kill and close prompts, so that it can be found by its program or port without looking it up in the table.
+/// 
+/// # Argument
+/// * `index`: The (1-based) row index of the connection in the table.
+/// * `connection`: The connection.
+/// 
+/// # Returns
+/// The label, e.g. "3: nginx (pid 4321) 0.0.0.0:80 LISTEN" or "5: curl (pid 812) 10.0.0.2:51234 -> 93.184.216.34:443 ESTABLISHED".
+fn get_connection_label(index: usize, connection: &connections::Connection) -> String {
+    let mut label: String = format!(
+        "{}: {} (pid {}) {}:{}",
+        index, connection.program, string_utils::format_pid(connection.pid), string_utils::format_address(&connection.local_address), connection.local_port
+    );
+    if !connection.remote_address.is_unspecified() {
+        label.push_str(&format!(" -> {}:{}", string_utils::format_address(&connection.remote_address), connection.remote_port));
+    }
+    format!("{} {}", label, connection.state.to_string().to_uppercase())
+}
+
+
+/// Starts an interactive selection process in the console for choosing the processes to kill using the "inquire" crate.
+/// Multiple connections can be selected, each of their processes is killed once.
+/// If no signal was given, the signal is chosen interactively as well.
 /// 
 /// # Argument
 /// * `connections`: A vector containing all connections which themselves contain a PID value.
+/// * `signal`: The signal to send, provided by `--signal`.
 /// 
 /// # Returns
 /// None
-pub fn interactve_process_kill(connections: &Vec<connections::Connection>) {
-    let selection: Result<u32, InquireError> = Select::new("Which process to kill (search or type index)?", (1..=connections.len() as u32).collect()).prompt();
+pub fn interactve_process_kill(connections: &[connections::Connection], signal: &Option<String>) {
+    let labels: Vec<String> = connections.iter().enumerate().map(|(idx, connection)| get_connection_label(idx + 1, connection)).collect();
+    let selection: Result<Vec<ListOption<String>>, InquireError> = MultiSelect::new("Which processes to kill (search by program, port or index, select using space)?", labels).raw_prompt();
 
     match selection {
-        Ok(choice) => {
-            let pid: &String = &connections[choice as usize - 1].pid;
-            kill_process(pid);
+        Ok(choices) if choices.is_empty() => { },
+        Ok(choices) => {
+            let Some(signal) = choose_signal(signal) else {
+                return;
+            };
+            let selected_connections: Vec<&connections::Connection> = choices.iter().map(|choice| &connections[choice.index]).collect();
+            kill_processes(&selected_connections, &signal);
         },
-        Err(_) => println!("Couldn't find process."),
+        Err(_) => serr!("Couldn't find process."),
     }
-}
\ No newline at end of file
+}
+
+
+/// Starts an interactive selection of TCP connections to close without killing their processes, see `connections::close_socket`.
+/// 
+/// # Argument
+/// * `connections`: The connections to choose from, only TCP connections are offered.
+/// 
+/// # Returns
+/// None
+pub fn interactive_socket_close(connections: &[connections::Connection]) {
+    let tcp_connections: Vec<(usize, &connections::Connection)> = connections.iter().enumerate()
+        .filter(|(_, connection)| connection.proto == "tcp" && connection.state != connections::State::Listen)
+        .collect();
+    if tcp_connections.is_empty() {
+        string_utils::pretty_print_error("There are no TCP connections to close.");
+        return;
+    }
+
+    let labels: Vec<String> = tcp_connections.iter().map(|(idx, connection)| get_connection_label(idx + 1, connection)).collect();
+    let selection: Result<Vec<ListOption<String>>, InquireError> = MultiSelect::new("Which connections to close (search by program, port or index, select using space)?", labels).raw_prompt();
+    let Ok(choices) = selection else {
+        return;
+    };
+    for choice in choices {
+        let (idx, connection) = tcp_connections[choice.index];
+        match connections::close_socket(connection) {
+            Ok(()) => string_utils::pretty_print_info(&format!("Closed connection #{}.", idx + 1)),
+            Err(error) => string_utils::pretty_print_error(&format!("Couldn't close connection #{}: {}", idx + 1, error)),
+        }
+    }
+}
+
+
+/// Kills the processes of the connections matched by `somo kill`, after printing them and confirming it (unless `yes` is set).
+/// Exits with 1 if no process matches or if the confirmation can't be asked because stdin isn't a terminal.
+/// 
+/// # Argument
+/// * `connections`: The matching connections.
+/// * `signal`: The name or number of the signal.
+/// * `yes`: If `true` the processes are killed without confirmation.
+/// * `dry_run`: If `true` the processes are only printed.
+/// 
+/// # Returns
+/// None
+pub fn kill_matching_processes(connections: &[connections::Connection], signal: &str, yes: bool, dry_run: bool) {
+    let connections: Vec<&connections::Connection> = connections.iter().collect();
+    let processes: Vec<(i32, &str)> = get_processes(&connections);
+    if processes.is_empty() {
+        string_utils::pretty_print_error("No process owns a matching connection (or they belong to other users, try using sudo).");
+        process::exit(1);
+    }
+
+    if dry_run {
+        for (pid, program) in &processes {
+            sout!("Would send {} to {} (pid {})", format_signal(signal), program, pid);
+        }
+        return;
+    }
+    let process_list: String = processes.iter().map(|(pid, program)| format!("{} ({})", program, pid)).collect::<Vec<String>>().join(", ");
+    string_utils::pretty_print_info(&format!("Matching processes: {}", process_list));
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            string_utils::pretty_print_error("Can't ask for confirmation, pass `--yes` to kill the processes.");
+            process::exit(1);
+        }
+        if !Confirm::new(&format!("Kill {} process(es)?", processes.len())).with_default(false).prompt().unwrap_or(false) {
+            return;
+        }
+    }
+    kill_processes(&connections, signal);
+}
+
+
+/// Kills the processes of all (matching) connections after confirming the affected PIDs.
+/// 
+/// # Argument
+/// * `connections`: The connections whose processes to kill.
+/// * `signal`: The signal to send, provided by `--signal`.
+/// 
+/// # Returns
+/// None
+pub fn kill_all_processes(connections: &[connections::Connection], signal: &Option<String>) {
+    let connections: Vec<&connections::Connection> = connections.iter().collect();
+    let processes: Vec<(i32, &str)> = get_processes(&connections);
+    if processes.is_empty() {
+        string_utils::pretty_print_error("The processes of the matching connections are unknown.");
+        return;
+    }
+
+    let process_list: String = processes.iter().map(|(pid, program)| format!("{} ({})", program, pid)).collect::<Vec<String>>().join(", ");
+    let question: String = format!("Kill {} process(es): {}?", processes.len(), process_list);
+    if !Confirm::new(&question).with_default(false).prompt().unwrap_or(false) {
+        return;
+    }
+    if let Some(signal) = choose_signal(signal) {
+        kill_processes(&connections, &signal);
+    }
+}
+
+/// Checks the config files for syntax errors, unknown flags and sections, as well as for invalid values by parsing the flags of every config file.
+/// Prints the problems with their file and line and exits with status 1 if there are any.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// None
+pub fn validate_config() {
+    let flags: Vec<String> = Args::command().get_arguments().filter_map(|arg| arg.get_long()).map(String::from).collect();
+    let problems: Vec<config::ConfigProblem> = config::validate_config(&flags);
+    for problem in &problems {
+        match problem.line {
+            Some(line) => serr!("{}:{}: {}", problem.path, line, problem.message),
+            None => serr!("{}: {}", problem.path, problem.message),
+        }
+    }
+
+    // the values are only checked once the files can be parsed and all flags are known
+    let mut invalid_values: usize = 0;
+    if problems.is_empty() {
+        for source in config::get_config_sources(None) {
+            if let Err(error) = Args::try_parse_from(std::iter::once("somo".to_string()).chain(source.args)) {
+                let message: String = error.to_string();
+                serr!("{}: {}", source.name, message.lines().next().unwrap_or_default().trim_start_matches("error: "));
+                invalid_values += 1;
+            }
+        }
+    }
+
+    match problems.len() + invalid_values {
+        0 => string_utils::pretty_print_info("The config files are valid."),
+        count => {
+            string_utils::pretty_print_error(&format!("Found {} problem(s) in the config files.", count));
+            process::exit(1);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("KILL"), Ok("KILL".to_string()));
+        assert_eq!(parse_signal("SIGKILL"), Ok("KILL".to_string()));
+        assert_eq!(parse_signal("sigint"), Ok("INT".to_string()));
+        assert_eq!(parse_signal(" term "), Ok("TERM".to_string()));
+        // numbers are named if possible, other valid numbers (e.g. real-time signals) are kept
+        assert_eq!(parse_signal("9"), Ok("KILL".to_string()));
+        assert_eq!(parse_signal("SIG15"), Ok("TERM".to_string()));
+        assert_eq!(parse_signal("34"), Ok("34".to_string()));
+
+        assert_eq!(parse_signal("0"), Err("invalid signal number '0'".to_string()));
+        assert_eq!(parse_signal("65"), Err("invalid signal number '65'".to_string()));
+        assert!(parse_signal("SIGFOO").unwrap_err().starts_with("unknown signal 'FOO'"));
+        assert!(parse_signal("-9").unwrap_err().starts_with("unknown signal '-9'"));
+    }
+
+    #[test]
+    fn test_get_subcommand_position() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        assert_eq!(get_subcommand_position(&args(&["somo", "wizard"]), "wizard"), Some(1));
+        assert_eq!(get_subcommand_position(&args(&["somo", "--ascii", "wizard"]), "wizard"), Some(2));
+        // flag values with the name of the subcommand aren't the subcommand
+        assert_eq!(get_subcommand_position(&args(&["somo", "--program", "wizard", "wizard"]), "wizard"), Some(3));
+        assert_eq!(get_subcommand_position(&args(&["somo", "--program", "wizard"]), "wizard"), None);
+    }
+}
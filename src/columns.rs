@@ -0,0 +1,461 @@
+use clap::ValueEnum;
+use std::cmp::Ordering;
+
+use crate::address_checkers;
+use crate::connections;
+use crate::string_utils;
+
+/// Represents a field of a connection which can be rendered as a table column or used to sort and group connections.
+/// The value names are the names used to select columns in the CLI, e.g. `--columns proto,local_port,state` or `--sort remote_address`,
+/// so every new field is automatically available to all of these flags (and their completions). It's also the registry of the fields
+/// available in templates and JSON (see `field`), so every field of a connection needs a column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum Column {
+    Index,
+    Id,
+    Proto,
+    Family,
+    LocalAddress,
+    LocalPort,
+    LocalService,
+    RemoteAddress,
+    RemotePort,
+    RemoteService,
+    RemoteName,
+    Scope,
+    AddressType,
+    Country,
+    City,
+    Asn,
+    Org,
+    ThreatScore,
+    Blocked,
+    Program,
+    Pid,
+    AssociatedPids,
+    ProgramPid,
+    Ppid,
+    Pprogram,
+    State,
+    Uid,
+    User,
+    Tty,
+    Session,
+    Uptime,
+    Tunnel,
+    Container,
+    AcceptQueue,
+    Backlog,
+    Drops,
+    ReuseportGroup,
+    DualStack,
+    TxRate,
+    RxRate,
+}
+
+/// Represents the order of a sort key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// Represents a column to sort by, optionally with an order, e.g. `--sort remote_port:desc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: Column,
+    /// The order given with the column, if not set `--reverse` decides.
+    pub order: Option<SortOrder>
+}
+
+impl SortKey {
+    /// Checks if the key sorts in descending order, using `--reverse` as a fallback if the key has no order.
+    pub fn is_descending(&self, reverse: bool) -> bool {
+        match self.order {
+            Some(order) => order == SortOrder::Descending,
+            None => reverse,
+        }
+    }
+
+    /// Compares two connections by the column of the key in its order.
+    ///
+    /// # Arguments
+    /// * `connection_a`: The first connection.
+    /// * `connection_b`: The second connection.
+    /// * `reverse`: If `true` the connections are compared in descending order, unless the key has an order.
+    ///
+    /// # Returns
+    /// The ordering of the connections.
+    pub fn compare(&self, connection_a: &connections::Connection, connection_b: &connections::Connection, reverse: bool) -> Ordering {
+        let ordering: Ordering = compare_connections(&self.column, connection_a, connection_b);
+        if self.is_descending(reverse) { ordering.reverse() } else { ordering }
+    }
+}
+
+/// The columns rendered if no columns are selected.
+pub static DEFAULT_COLUMNS: [Column; 7] = [
+    Column::Index, Column::Proto, Column::LocalPort, Column::RemoteAddress, Column::RemotePort, Column::ProgramPid, Column::State
+];
+
+
+impl Column {
+    /// Gets the human-readable header of the column.
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Index => "#",
+            Column::Id => "id",
+            Column::Proto => "proto",
+            Column::Family => "family",
+            Column::LocalAddress => "local address",
+            Column::LocalPort => "local port",
+            Column::LocalService => "local service",
+            Column::RemoteAddress => "remote address",
+            Column::Scope => "scope",
+            Column::AddressType => "address type",
+            Column::Country => "country",
+            Column::City => "city",
+            Column::Asn => "asn",
+            Column::Org => "organization",
+            Column::ThreatScore => "threat score",
+            Column::Blocked => "blocked",
+            Column::RemotePort => "remote port",
+            Column::RemoteService => "remote service",
+            Column::RemoteName => "remote name",
+            Column::Program => "program",
+            Column::Pid => "pid",
+            Column::AssociatedPids => "pids",
+            Column::ProgramPid => "program/pid",
+            Column::Ppid => "ppid",
+            Column::Pprogram => "parent program",
+            Column::State => "state",
+            Column::Uid => "uid",
+            Column::User => "user",
+            Column::Tty => "tty",
+            Column::Session => "session",
+            Column::Uptime => "uptime",
+            Column::Tunnel => "tunnel",
+            Column::Container => "container",
+            Column::AcceptQueue => "accept queue",
+            Column::Backlog => "backlog",
+            Column::Drops => "drops",
+            Column::ReuseportGroup => "reuseport group",
+            Column::DualStack => "dual stack",
+            Column::TxRate => "tx/s",
+            Column::RxRate => "rx/s",
+        }
+    }
+
+    /// Gets the plain (not formatted) value of the column for a connection.
+    ///
+    /// # Arguments
+    /// * `connection`: The connection.
+    /// * `index`: The (1-based) position of the connection in the output.
+    ///
+    /// # Returns
+    /// The value as a string.
+    pub fn value(&self, connection: &connections::Connection, index: usize) -> String {
+        match self {
+            Column::Index => index.to_string(),
+            Column::Id => connections::get_connection_id(connection),
+            Column::Proto => connection.proto.to_string(),
+            Column::Family => connection.family.to_string(),
+            Column::LocalAddress => string_utils::format_address(&connection.local_address),
+            Column::LocalPort => connection.local_port.to_string(),
+            Column::LocalService => connection.local_service.as_deref().unwrap_or("-").to_string(),
+            Column::RemoteAddress => string_utils::format_address(&connection.remote_address),
+            Column::RemotePort => connection.remote_port.to_string(),
+            Column::RemoteService => connection.remote_service.as_deref().unwrap_or("-").to_string(),
+            Column::RemoteName => connection.remote_name.as_deref().unwrap_or("-").to_string(),
+            Column::Scope => connection.address_scope.as_str().to_string(),
+            Column::AddressType => match connection.address_type {
+                address_checkers::IPType::Localhost => "localhost".to_string(),
+                address_checkers::IPType::Unspecified => "unspecified".to_string(),
+                address_checkers::IPType::Extern => "extern".to_string(),
+            },
+            Column::Country => connection.country.as_deref().unwrap_or("-").to_string(),
+            Column::City => connection.city.as_deref().unwrap_or("-").to_string(),
+            Column::Asn => connection.asn.map_or("-".to_string(), |asn| format!("AS{}", asn)),
+            Column::Org => connection.org.as_deref().unwrap_or("-").to_string(),
+            Column::ThreatScore => connection.threat_score.map_or("-".to_string(), |threat_score| threat_score.to_string()),
+            Column::Blocked => connection.blocked.to_string(),
+            Column::Program => connection.program.to_string(),
+            Column::Pid => string_utils::format_pid(connection.pid),
+            Column::AssociatedPids if connection.associated_pids.is_empty() => "-".to_string(),
+            Column::AssociatedPids => connection.associated_pids.iter().map(i32::to_string).collect::<Vec<String>>().join(","),
+            Column::ProgramPid => format!("{}/{}", connection.program, string_utils::format_pid(connection.pid)),
+            Column::Ppid => string_utils::format_pid(connection.ppid),
+            Column::Pprogram => connection.pprogram.to_string(),
+            Column::State => connection.state.to_string(),
+            Column::Uid => connection.uid.to_string(),
+            Column::User => connection.user.to_string(),
+            Column::Tty => connection.tty.to_string(),
+            Column::Session => match connection.login_session {
+                Some(login_session) => format!("{} (session {})", connection.tty, login_session),
+                None => connection.tty.to_string()
+            },
+            Column::Uptime => string_utils::format_uptime(connection.proc_uptime),
+            Column::Tunnel => connection.tunnel.as_deref().unwrap_or("-").to_string(),
+            Column::Container => connection.container.as_deref().unwrap_or("-").to_string(),
+            Column::Backlog => connection.backlog.map_or("-".to_string(), |backlog| backlog.to_string()),
+            Column::ReuseportGroup => connection.reuseport_group.map_or("-".to_string(), |group| group.to_string()),
+            Column::DualStack => connection.dual_stack.to_string(),
+            Column::Drops => connection.drops.map_or("-".to_string(), |drops| drops.to_string()),
+            Column::TxRate => string_utils::format_byte_rate(connection.tx_rate),
+            Column::RxRate => string_utils::format_byte_rate(connection.rx_rate),
+            Column::AcceptQueue => match (connection.accept_queue, connection.backlog) {
+                (Some(accept_queue), Some(backlog)) => format!("{}/{}", accept_queue, backlog),
+                _ => "-".to_string()
+            },
+        }
+    }
+
+    /// Gets the field of a connection the column shows and its description, i.e. the name of the field in templates and JSON (listed by `somo fields`).
+    ///
+    /// # Returns
+    /// The name and description of the field, `None` if the column combines several fields (e.g. `program_pid`).
+    pub fn field(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Column::Index => Some(("index", "the (1-based) position of the connection in the output (only in templates)")),
+            Column::Id => Some(("id", "a stable ID derived from the protocol, addresses and ports, e.g. 3fa2b1c0 (only in templates)")),
+            Column::Proto => Some(("proto", "the protocol, e.g. tcp")),
+            Column::Family => Some(("family", "the address family of the socket, ipv4 or ipv6 (also for IPv4-mapped addresses collapsed with --collapse-v4mapped)")),
+            Column::LocalAddress => Some(("local_address", "the local IP address")),
+            Column::LocalPort => Some(("local_port", "the local port")),
+            Column::LocalService => Some(("local_service", "the name of the service using the local port, e.g. postgresql (with --annotate-ports)")),
+            Column::RemoteAddress => Some(("remote_address", "the remote IP address")),
+            Column::RemotePort => Some(("remote_port", "the remote port")),
+            Column::RemoteService => Some(("remote_service", "the name of the service using the remote port, e.g. https (with --annotate-ports)")),
+            Column::RemoteName => Some(("remote_name", "the name of a LAN peer, e.g. printer.local (with --mdns)")),
+            Column::Scope => Some(("address_scope", "the scope of the remote address: loopback, unspecified, link-local, private, cgnat, multicast or public")),
+            Column::AddressType => Some(("address_type", "the type of the remote address: localhost, unspecified or extern")),
+            Column::Country => Some(("country", "the country of the remote address (with --geo)")),
+            Column::City => Some(("city", "the city of the remote address (with --geo)")),
+            Column::Asn => Some(("asn", "the autonomous system number of the remote address (with --asn)")),
+            Column::Org => Some(("org", "the organization of the remote address (with --asn)")),
+            Column::ThreatScore => Some(("threat_score", "the AbuseIPDB threat score (with --check-abuse)")),
+            Column::Blocked => Some(("blocked", "whether the remote address is on the blocklist (with --blocklist)")),
+            Column::Program => Some(("program", "the name of the program owning the socket, \"-\" if unknown")),
+            Column::Pid => Some(("pid", "the PID of the program, \"-\" if unknown")),
+            Column::AssociatedPids => Some(("associated_pids", "the PIDs of all processes holding the socket, more than one if it's shared (e.g. by preforked workers)")),
+            Column::ProgramPid => None,
+            Column::Ppid => Some(("ppid", "the PID of the parent process, \"-\" if unknown")),
+            Column::Pprogram => Some(("pprogram", "the name of the parent process, e.g. systemd or bash, \"-\" if unknown")),
+            Column::State => Some(("state", "the connection state, e.g. established or listen")),
+            Column::Uid => Some(("uid", "the UID of the socket owner")),
+            Column::User => Some(("user", "the name of the socket owner")),
+            Column::Tty => Some(("tty", "the controlling terminal of the program")),
+            Column::Session => Some(("login_session", "the login session of the program (see loginctl)")),
+            Column::Uptime => Some(("proc_uptime", "the number of seconds since the program was started")),
+            Column::Tunnel => Some(("tunnel", "the ssh port forwarding spec, e.g. -L 8080:db:5432")),
+            Column::Container => Some(("container", "the container and target port a docker-proxy socket forwards to, e.g. webapp:8080")),
+            Column::AcceptQueue => Some(("accept_queue", "the amount of connections waiting to be accepted by a TCP listener")),
+            Column::Backlog => Some(("backlog", "the maximum length of the accept queue of a TCP listener, new connections are dropped once it's full")),
+            Column::Drops => Some(("drops", "the amount of packets a UDP (or raw) socket dropped, e.g. because its receive buffer was full")),
+            Column::ReuseportGroup => Some(("reuseport_group", "the number of the group of TCP listeners sharing the address and port with SO_REUSEPORT, e.g. a worker pool")),
+            Column::DualStack => Some(("dual_stack", "whether the connection stands for an IPv4 and an IPv6 listener (with --merge-dual-stack)")),
+            Column::TxRate => Some(("tx_rate", "the bytes per second a TCP connection sent between two samples (in somo top)")),
+            Column::RxRate => Some(("rx_rate", "the bytes per second a TCP connection received between two samples (in somo top)")),
+        }
+    }
+}
+
+
+/// Gets the columns to render based on the columns selected by the user.
+///
+/// # Arguments
+/// * `selected_columns`: The columns selected by the user, in the order they should be rendered. If empty, `DEFAULT_COLUMNS` are used.
+/// * `show_session`: If `true` the session column is added if it isn't selected already.
+/// * `show_index`: If `false` the index column is removed.
+/// * `show_id`: If `true` the ID column replaces the index column (or is added as the first column), unless it's selected already.
+///
+/// # Returns
+/// The columns to render.
+pub fn get_columns(selected_columns: &[Column], show_session: bool, show_index: bool, show_id: bool) -> Vec<Column> {
+    let mut columns: Vec<Column> = if selected_columns.is_empty() { DEFAULT_COLUMNS.to_vec() } else { selected_columns.to_vec() };
+    if show_session && !columns.contains(&Column::Session) {
+        columns.push(Column::Session);
+    }
+    if show_id && !columns.contains(&Column::Id) {
+        match columns.iter().position(|column| *column == Column::Index) {
+            Some(position) => columns[position] = Column::Id,
+            None => columns.insert(0, Column::Id),
+        }
+    }
+    if !show_index {
+        columns.retain(|column| *column != Column::Index);
+    }
+    columns
+}
+
+
+/// Parses a sort key of the CLI, a column name optionally followed by ":asc" or ":desc".
+///
+/// # Arguments
+/// * `value`: The sort key, e.g. "remote_port:desc".
+///
+/// # Returns
+/// The sort key or an error if the column or the order is unknown.
+pub fn parse_sort_key(value: &str) -> Result<SortKey, String> {
+    let (name, order) = match value.split_once(':') {
+        Some((name, order)) => (name, Some(order)),
+        None => (value, None),
+    };
+    let column: Column = Column::from_str(name, true).map_err(|_| {
+        let names: Vec<String> = Column::value_variants().iter().filter_map(|column| column.to_possible_value()).map(|value| value.get_name().to_string()).collect();
+        format!("unknown column '{}', expected one of {}", name, names.join(", "))
+    })?;
+    let order: Option<SortOrder> = match order.map(str::to_ascii_lowercase).as_deref() {
+        None => None,
+        Some("asc") => Some(SortOrder::Ascending),
+        Some("desc") => Some(SortOrder::Descending),
+        Some(order) => return Err(format!("unknown order '{}', expected asc or desc", order)),
+    };
+    Ok(SortKey { column, order })
+}
+
+
+/// Gets the rank of a state in the lifecycle of a connection, so that states sort in protocol order
+/// (listen < synsent < synrecv < established < ... < timewait < close) rather than alphabetically.
+fn get_state_rank(state: &connections::State) -> u8 {
+    match state {
+        connections::State::Listen => 0,
+        connections::State::SynSent => 1,
+        connections::State::SynRecv => 2,
+        connections::State::NewSynRecv => 3,
+        connections::State::Established => 4,
+        connections::State::FinWait1 => 5,
+        connections::State::FinWait2 => 6,
+        connections::State::CloseWait => 7,
+        connections::State::Closing => 8,
+        connections::State::LastAck => 9,
+        connections::State::TimeWait => 10,
+        connections::State::Close => 11,
+    }
+}
+
+
+/// Compares two optional values, e.g. PIDs numerically or service names. Missing values (displayed as "-") come after all values.
+fn compare_optional<T: Ord>(value_a: &Option<T>, value_b: &Option<T>) -> Ordering {
+    match (value_a, value_b) {
+        (Some(value_a), Some(value_b)) => value_a.cmp(value_b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+
+/// Compares two connections by the typed value of a column rather than its rendered text,
+/// i.e. ports and PIDs numerically, addresses by address and states in protocol order.
+///
+/// # Arguments
+/// * `column`: The column to compare by.
+/// * `connection_a`: The first connection.
+/// * `connection_b`: The second connection.
+///
+/// # Returns
+/// The ordering of the connections.
+fn compare_connections(column: &Column, connection_a: &connections::Connection, connection_b: &connections::Connection) -> Ordering {
+    match column {
+        Column::LocalAddress => connection_a.local_address.cmp(&connection_b.local_address),
+        Column::LocalPort => connection_a.local_port.cmp(&connection_b.local_port),
+        Column::RemoteAddress => connection_a.remote_address.cmp(&connection_b.remote_address),
+        Column::RemotePort => connection_a.remote_port.cmp(&connection_b.remote_port),
+        Column::LocalService => compare_optional(&connection_a.local_service, &connection_b.local_service),
+        Column::RemoteService => compare_optional(&connection_a.remote_service, &connection_b.remote_service),
+        Column::RemoteName => compare_optional(&connection_a.remote_name, &connection_b.remote_name),
+        Column::Country => compare_optional(&connection_a.country, &connection_b.country),
+        Column::City => compare_optional(&connection_a.city, &connection_b.city),
+        Column::Asn => compare_optional(&connection_a.asn, &connection_b.asn),
+        Column::Org => compare_optional(&connection_a.org, &connection_b.org),
+        Column::Scope => connection_a.address_scope.cmp(&connection_b.address_scope),
+        Column::Pid => compare_optional(&connection_a.pid, &connection_b.pid),
+        Column::AssociatedPids => connection_a.associated_pids.cmp(&connection_b.associated_pids),
+        Column::Ppid => compare_optional(&connection_a.ppid, &connection_b.ppid),
+        Column::ProgramPid => connection_a.program.cmp(&connection_b.program).then(compare_optional(&connection_a.pid, &connection_b.pid)),
+        Column::State => get_state_rank(&connection_a.state).cmp(&get_state_rank(&connection_b.state)),
+        Column::Session => compare_optional(&connection_a.login_session, &connection_b.login_session).then(connection_a.tty.cmp(&connection_b.tty)),
+        Column::Uptime => compare_optional(&connection_a.proc_uptime, &connection_b.proc_uptime),
+        Column::ThreatScore => compare_optional(&connection_a.threat_score, &connection_b.threat_score),
+        Column::Uid => connection_a.uid.cmp(&connection_b.uid),
+        Column::Backlog => compare_optional(&connection_a.backlog, &connection_b.backlog),
+        Column::ReuseportGroup => compare_optional(&connection_a.reuseport_group, &connection_b.reuseport_group),
+        Column::Drops => compare_optional(&connection_a.drops, &connection_b.drops),
+        Column::TxRate => compare_optional(&connection_a.tx_rate, &connection_b.tx_rate),
+        Column::RxRate => compare_optional(&connection_a.rx_rate, &connection_b.rx_rate),
+        Column::AcceptQueue => compare_optional(&connection_a.accept_queue, &connection_b.accept_queue).then(compare_optional(&connection_a.backlog, &connection_b.backlog)),
+        // the index is the position after sorting, so it keeps the current order
+        Column::Index => Ordering::Equal,
+        _ => column.value(connection_a, 0).cmp(&column.value(connection_b, 0)),
+    }
+}
+
+
+/// Sorts connections by the value of a column. The sort is stable, so connections with equal values keep their order.
+///
+/// # Arguments
+/// * `connections`: The connections to sort.
+/// * `sort_by`: The column to sort by and its order.
+/// * `reverse`: If `true` the connections are sorted in descending order, unless the sort key has an order.
+///
+/// # Returns
+/// None
+pub fn sort_connections(connections: &mut [connections::Connection], sort_by: &SortKey, reverse: bool) {
+    connections.sort_by(|connection_a, connection_b| sort_by.compare(connection_a, connection_b, reverse));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connections::State;
+    use crate::fixtures::ConnectionFixture;
+
+    #[test]
+    fn test_parse_sort_key() {
+        assert_eq!(parse_sort_key("remote_port"), Ok(SortKey { column: Column::RemotePort, order: None }));
+        assert_eq!(parse_sort_key("remote_port:asc"), Ok(SortKey { column: Column::RemotePort, order: Some(SortOrder::Ascending) }));
+        assert_eq!(parse_sort_key("remote_port:desc"), Ok(SortKey { column: Column::RemotePort, order: Some(SortOrder::Descending) }));
+        assert_eq!(parse_sort_key("Remote_Port:DESC"), Ok(SortKey { column: Column::RemotePort, order: Some(SortOrder::Descending) }));
+
+        assert!(parse_sort_key("remote").unwrap_err().starts_with("unknown column 'remote'"));
+        assert_eq!(parse_sort_key("pid:up"), Err("unknown order 'up', expected asc or desc".to_string()));
+    }
+
+    #[test]
+    fn test_sort_connections() {
+        let mut connections: Vec<connections::Connection> = vec![
+            ConnectionFixture::new().state(State::TimeWait).build(),
+            ConnectionFixture::new().remote("10.0.0.7", 80).build(),
+            ConnectionFixture::new().state(State::Listen).remote("10.0.0.7", 8080).build()
+        ];
+        let by_state: SortKey = parse_sort_key("state").unwrap();
+        sort_connections(&mut connections, &by_state, false);
+        let states: Vec<connections::State> = connections.iter().map(|connection| connection.state).collect();
+        assert_eq!(states, [connections::State::Listen, connections::State::Established, connections::State::TimeWait]);
+
+        // ports are compared numerically and the order of the key takes precedence over `--reverse`
+        let by_port: SortKey = parse_sort_key("remote_port:asc").unwrap();
+        sort_connections(&mut connections, &by_port, true);
+        let ports: Vec<u16> = connections.iter().map(|connection| connection.remote_port).collect();
+        assert_eq!(ports, [80, 443, 8080]);
+        sort_connections(&mut connections, &parse_sort_key("remote_port").unwrap(), true);
+        let ports: Vec<u16> = connections.iter().map(|connection| connection.remote_port).collect();
+        assert_eq!(ports, [8080, 443, 80]);
+    }
+
+    #[test]
+    fn test_columns_cover_fields() {
+        // every field of a connection in templates and JSON has a column, and every column field exists
+        let fields: Vec<&str> = Column::value_variants().iter().filter_map(Column::field).map(|(name, _)| name).collect();
+        let json: serde_json::Value = serde_json::to_value(ConnectionFixture::new().build()).unwrap();
+        let keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        for key in &keys {
+            assert!(fields.contains(key), "the field {} has no column", key);
+        }
+        for field in fields.iter().filter(|field| !["index", "id"].contains(field)) {
+            assert!(keys.contains(field), "the column field {} isn't a field of a connection", field);
+        }
+    }
+}
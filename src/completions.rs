@@ -0,0 +1,145 @@
+use clap::{Arg, ArgAction, Command, ValueEnum};
+use clap_complete::Shell;
+use std::io;
+
+/// Represents the shells (and completion frameworks) for which `somo generate-completions` generates completions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+    Powershell,
+    Nushell,
+    /// A spec for carapace, which provides the completions for many shells
+    Carapace,
+}
+
+
+/// Quotes a string for YAML. A JSON string is also a valid double-quoted YAML string.
+fn quote_yaml(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+
+/// Gets the name of a flag in a carapace spec, e.g. "-p, --port=". `=` marks flags taking a value and `*` repeatable flags.
+///
+/// # Arguments
+/// * `arg`: The flag.
+///
+/// # Returns
+/// The name or `None` if the argument is positional.
+fn get_carapace_flag(arg: &Arg) -> Option<String> {
+    let long: Option<String> = arg.get_long().map(|long| format!("--{}", long));
+    let short: Option<String> = arg.get_short().map(|short| format!("-{}", short));
+    let mut flag: String = match (short, long) {
+        (Some(short), Some(long)) => format!("{}, {}", short, long),
+        (Some(flag), None) | (None, Some(flag)) => flag,
+        (None, None) => return None,
+    };
+    if matches!(arg.get_action(), ArgAction::Append | ArgAction::Count) {
+        flag.push('*');
+    }
+    if arg.get_action().takes_values() {
+        flag.push('=');
+    }
+    Some(flag)
+}
+
+
+/// Appends the carapace spec of a command and its subcommands to a YAML document.
+///
+/// # Arguments
+/// * `spec`: The YAML document.
+/// * `command`: The command.
+/// * `indent`: The indentation of the command's keys.
+///
+/// # Returns
+/// None
+fn push_carapace_command(spec: &mut String, command: &Command, indent: &str) {
+    let description: String = command.get_about().map(|about| about.to_string()).unwrap_or_default();
+    spec.push_str(&format!("name: {}\n{}description: {}\n", command.get_name(), indent, quote_yaml(&description)));
+
+    let args: Vec<&Arg> = command.get_arguments().filter(|arg| !arg.is_hide_set()).collect();
+    let flags: Vec<(String, &Arg)> = args.iter().filter_map(|arg| Some((get_carapace_flag(arg)?, *arg))).collect();
+    if !flags.is_empty() {
+        spec.push_str(&format!("{}flags:\n", indent));
+        for (flag, arg) in &flags {
+            let help: String = arg.get_help().map(|help| help.to_string()).unwrap_or_default();
+            spec.push_str(&format!("{}  {}: {}\n", indent, flag, quote_yaml(&help)));
+        }
+    }
+
+    // the possible values of flags and positional arguments, e.g. of --proto
+    let get_values = |arg: &Arg| arg.get_possible_values().iter().map(|value| quote_yaml(value.get_name())).collect::<Vec<String>>();
+    let flag_values: Vec<(&str, Vec<String>)> = args.iter()
+        .filter_map(|arg| Some((arg.get_long()?, get_values(arg))))
+        .filter(|(_, values)| !values.is_empty())
+        .collect();
+    let positional_values: Vec<Vec<String>> = args.iter().filter(|arg| arg.is_positional()).map(|arg| get_values(arg)).collect();
+    if !flag_values.is_empty() || positional_values.iter().any(|values| !values.is_empty()) {
+        spec.push_str(&format!("{}completion:\n", indent));
+        if !flag_values.is_empty() {
+            spec.push_str(&format!("{}  flag:\n", indent));
+            for (flag, values) in flag_values {
+                spec.push_str(&format!("{}    {}: [{}]\n", indent, flag, values.join(", ")));
+            }
+        }
+        if positional_values.iter().any(|values| !values.is_empty()) {
+            spec.push_str(&format!("{}  positional:\n", indent));
+            for values in positional_values {
+                spec.push_str(&format!("{}    - [{}]\n", indent, values.join(", ")));
+            }
+        }
+    }
+
+    let subcommands: Vec<&Command> = command.get_subcommands().filter(|subcommand| !subcommand.is_hide_set()).collect();
+    if !subcommands.is_empty() {
+        spec.push_str(&format!("{}commands:\n", indent));
+        for subcommand in subcommands {
+            spec.push_str(&format!("{}  - ", indent));
+            push_carapace_command(spec, subcommand, &format!("{}    ", indent));
+        }
+    }
+}
+
+
+/// Generates a carapace spec (https://carapace-sh.github.io/carapace-spec/) of a command, including its flags, their values and its subcommands.
+///
+/// # Arguments
+/// * `command`: The command.
+///
+/// # Returns
+/// The spec as YAML.
+fn get_carapace_spec(command: &Command) -> String {
+    let mut spec: String = String::new();
+    push_carapace_command(&mut spec, command, "");
+    spec
+}
+
+
+/// Prints the completions of a command for a shell, or a carapace spec.
+///
+/// # Arguments
+/// * `shell`: The shell.
+/// * `command`: The command, i.e. somo with all its flags and subcommands.
+///
+/// # Returns
+/// None
+pub fn print_completions(shell: CompletionShell, mut command: Command) {
+    let name: String = command.get_name().to_string();
+    let shell: Shell = match shell {
+        CompletionShell::Bash => Shell::Bash,
+        CompletionShell::Zsh => Shell::Zsh,
+        CompletionShell::Fish => Shell::Fish,
+        CompletionShell::Elvish => Shell::Elvish,
+        CompletionShell::Powershell => Shell::PowerShell,
+        CompletionShell::Nushell => return clap_complete::generate(clap_complete_nushell::Nushell, &mut command, name, &mut io::stdout()),
+        CompletionShell::Carapace => {
+            command.build();
+            sout!("{}", get_carapace_spec(&command).trim_end());
+            return;
+        }
+    };
+    clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+}
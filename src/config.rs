@@ -0,0 +1,802 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::string_utils;
+
+/// Path of the system-wide config file, e.g. used by admins of shared servers to set defaults for all users.
+static SYSTEM_CONFIG_PATH: &str = "/etc/somo/config";
+
+/// The keys of a profile which select it automatically instead of being flags, e.g. `match-hostname = "web-*"`.
+static PROFILE_RULES: [&str; 2] = ["match-hostname", "match-env"];
+
+
+/// Gets the path of the user's config file, i.e. `$XDG_CONFIG_HOME/somo/config` or `~/.config/somo/config`.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The path of the config file or `None` if neither `XDG_CONFIG_HOME` nor `HOME` is set.
+pub fn get_user_config_path() -> Option<PathBuf> {
+    let config_directory = match env::var("XDG_CONFIG_HOME") {
+        Ok(directory) => PathBuf::from(directory),
+        Err(_) => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+    Some(config_directory.join("somo").join("config"))
+}
+
+
+/// Gets the path of the user's services database, whose entries override the ones of `/etc/services`, next to the config file.
+pub fn get_services_override_path() -> Option<PathBuf> {
+    get_user_config_path().map(|path| path.with_file_name("services"))
+}
+
+
+/// Reads the flags from a config file. Every line contains one flag optionally followed by its value, e.g. `--proto tcp`.
+/// Empty lines and comments starting with `#` are ignored, as well as everything after the first section header like `[templates]`.
+///
+/// # Arguments
+/// * `path`: The path of the config file.
+///
+/// # Returns
+/// The flags and values of the config file as separate arguments, empty if the file doesn't exist.
+fn read_config_file(path: &PathBuf) -> Vec<String> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+
+    content.lines()
+        .map(|line| line.trim())
+        .take_while(|line| !line.starts_with('['))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .flat_map(|line| line.split_whitespace().map(String::from).collect::<Vec<String>>())
+        .collect()
+}
+
+
+/// Removes the quotes around a value of a config section and resolves the escape sequences `\"`, `\\`, `\n` and `\t` inside of them.
+///
+/// # Arguments
+/// * `value`: The value, e.g. `"{{pid}}\t{{program}}"`.
+///
+/// # Returns
+/// The unquoted value, or the value itself if it isn't quoted.
+fn unquote_value(value: &str) -> String {
+    let Some(quoted) = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')) else {
+        return value.to_string();
+    };
+
+    let mut unquoted: String = String::new();
+    let mut characters = quoted.chars();
+    while let Some(character) = characters.next() {
+        match (character, character == '\\') {
+            (_, true) => match characters.next() {
+                Some('n') => unquoted.push('\n'),
+                Some('t') => unquoted.push('\t'),
+                Some(escaped) => unquoted.push(escaped),
+                None => unquoted.push('\\'),
+            },
+            (character, false) => unquoted.push(character),
+        }
+    }
+    unquoted
+}
+
+
+/// Reads the entries of a section of a config file. The section starts with a header like `[templates]`
+/// and contains one `name = "value"` entry per line.
+///
+/// # Arguments
+/// * `path`: The path of the config file.
+/// * `section`: The name of the section, e.g. "templates".
+///
+/// # Returns
+/// The names and values of the entries, empty if the file or the section doesn't exist.
+fn read_config_section(path: &PathBuf, section: &str) -> Vec<(String, String)> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let header: String = format!("[{}]", section);
+
+    content.lines()
+        .map(|line| line.trim())
+        .skip_while(|line| *line != header)
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), unquote_value(value.trim())))
+        .collect()
+}
+
+
+/// Gets the path of the TOML config file which complements a flag-per-line config file, e.g. `~/.config/somo/config.toml`.
+///
+/// # Arguments
+/// * `path`: The path of the flag-per-line config file.
+///
+/// # Returns
+/// The path of the TOML config file in the same directory.
+fn get_toml_config_path(path: &Path) -> PathBuf {
+    path.with_extension("toml")
+}
+
+
+/// Reads and parses a TOML config file. A file which can't be parsed is reported as a warning and ignored.
+///
+/// # Arguments
+/// * `path`: The path of the TOML config file.
+///
+/// # Returns
+/// The tables of the config file or `None` if the file doesn't exist or is invalid.
+fn read_toml_config(path: &Path) -> Option<toml::Table> {
+    let content = fs::read_to_string(path).ok()?;
+    match content.parse::<toml::Table>() {
+        Ok(config) => Some(config),
+        Err(error) => {
+            string_utils::pretty_print_warning(&format!("Couldn't parse config file '{}': {}", path.display(), error.message().trim().replace('\n', " ")));
+            None
+        }
+    }
+}
+
+
+/// Converts a value of a TOML config file to the arguments of a flag, e.g. `proto = "tcp"` to `--proto tcp`.
+/// `true` sets a flag without a value, `false` omits it and every element of an array is passed as a separate value.
+///
+/// # Arguments
+/// * `flag`: The name of the flag without the leading dashes, e.g. "exclude-ipv6".
+/// * `value`: The value of the flag.
+///
+/// # Returns
+/// The flag and its value as separate arguments.
+fn get_flag_args(flag: &str, value: &toml::Value) -> Vec<String> {
+    let flag: String = format!("--{}", flag.replace('_', "-"));
+    match value {
+        toml::Value::Boolean(true) => vec![flag],
+        toml::Value::Boolean(false) => Vec::new(),
+        toml::Value::String(value) => vec![flag, value.to_string()],
+        toml::Value::Array(values) => values.iter().flat_map(|value| get_flag_args(&flag[2..], value)).collect(),
+        toml::Value::Table(_) => {
+            string_utils::pretty_print_warning(&format!("Ignoring the table '{}' in the config file, flags can't be tables.", &flag[2..]));
+            Vec::new()
+        }
+        value => vec![flag, value.to_string()],
+    }
+}
+
+
+/// Converts a table of flags of a TOML config file, i.e. `[defaults]` or a profile, to arguments.
+///
+/// # Arguments
+/// * `table`: The table mapping flag names to their values.
+///
+/// # Returns
+/// The flags and values as separate arguments.
+fn get_table_args(table: &toml::Table) -> Vec<String> {
+    table.iter().flat_map(|(flag, value)| get_flag_args(flag, value)).collect()
+}
+
+
+/// Converts the `[theme]` table of a TOML config file to arguments. Every state is mapped to a color
+/// (e.g. `listen = "blue"` to `--state-color listen=blue`), except for `color-rows` and `zebra` which are passed as flags.
+///
+/// # Arguments
+/// * `table`: The theme table.
+///
+/// # Returns
+/// The flags and values as separate arguments.
+fn get_theme_args(table: &toml::Table) -> Vec<String> {
+    table.iter().flat_map(|(name, value)| match (name.replace('_', "-").as_str(), value) {
+        ("color-rows" | "zebra", value) => get_flag_args(name, value),
+        (state, color) => vec!["--state-color".to_string(), format!("{}={}", state, color.as_str().unwrap_or_default())],
+    }).collect()
+}
+
+
+/// Converts the `[pager]` table of a TOML config file to arguments, i.e. `command` to `--pager`, `paging` to `--paging` and `min-lines` to `--pager-min-lines`.
+///
+/// # Arguments
+/// * `table`: The pager table.
+///
+/// # Returns
+/// The flags and values as separate arguments.
+fn get_pager_args(table: &toml::Table) -> Vec<String> {
+    table.iter().flat_map(|(name, value)| match name.replace('_', "-").as_str() {
+        "command" => get_flag_args("pager", value),
+        "min-lines" => get_flag_args("pager-min-lines", value),
+        _ => get_flag_args(name, value),
+    }).collect()
+}
+
+
+/// Converts the table of a profile to arguments, leaving out the rules which select the profile automatically.
+fn get_profile_args(table: &toml::Table) -> Vec<String> {
+    table.iter()
+        .filter(|(flag, _)| !PROFILE_RULES.contains(&flag.replace('_', "-").as_str()))
+        .flat_map(|(flag, value)| get_flag_args(flag, value))
+        .collect()
+}
+
+
+/// Checks if a value matches a pattern in which `*` matches any text and `?` any single character, e.g. "web-*".
+///
+/// # Arguments
+/// * `pattern`: The pattern.
+/// * `value`: The value to match.
+///
+/// # Returns
+/// `true` if the whole value matches the pattern.
+fn matches_pattern(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut pattern_idx, mut value_idx) = (0, 0);
+    // the position of the last `*` and of the value when it was reached, to retry with `*` matching one more character
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while value_idx < value.len() {
+        match pattern.get(pattern_idx) {
+            Some('*') => {
+                backtrack = Some((pattern_idx, value_idx));
+                pattern_idx += 1;
+            }
+            Some(character) if *character == '?' || *character == value[value_idx] => {
+                pattern_idx += 1;
+                value_idx += 1;
+            }
+            _ => match backtrack {
+                Some((star_idx, star_value_idx)) => {
+                    pattern_idx = star_idx + 1;
+                    value_idx = star_value_idx + 1;
+                    backtrack = Some((star_idx, star_value_idx + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_idx..].iter().all(|character| *character == '*')
+}
+
+
+/// Checks if a rule of a profile matches, i.e. a pattern for `match-hostname` or a variable for `match-env`,
+/// either as "NAME=pattern" or just "NAME" which matches if the variable is set and not empty.
+fn matches_rule(rule: &str, pattern: &str, hostname: Option<&str>) -> bool {
+    match rule {
+        "match-hostname" => hostname.is_some_and(|hostname| matches_pattern(pattern, hostname)),
+        _ => match pattern.split_once('=') {
+            Some((name, pattern)) => env::var(name).is_ok_and(|value| matches_pattern(pattern, &value)),
+            None => env::var(pattern).is_ok_and(|value| !value.is_empty()),
+        },
+    }
+}
+
+
+/// Checks if a profile is selected automatically, i.e. if it has rules and all of them match.
+/// A rule can be a list of patterns, of which any has to match.
+///
+/// # Arguments
+/// * `table`: The table of the profile.
+/// * `hostname`: The hostname of the machine, if it's known.
+///
+/// # Returns
+/// `true` if the profile has rules and all of them match.
+fn is_profile_matching(table: &toml::Table, hostname: Option<&str>) -> bool {
+    let rules: Vec<(String, &toml::Value)> = table.iter()
+        .map(|(key, value)| (key.replace('_', "-"), value))
+        .filter(|(key, _)| PROFILE_RULES.contains(&key.as_str()))
+        .collect();
+    !rules.is_empty() && rules.iter().all(|(rule, value)| match value {
+        toml::Value::String(pattern) => matches_rule(rule, pattern, hostname),
+        toml::Value::Array(patterns) => patterns.iter().filter_map(toml::Value::as_str).any(|pattern| matches_rule(rule, pattern, hostname)),
+        _ => false,
+    })
+}
+
+
+/// Gets the profile which is selected automatically if none is selected with `--profile`.
+/// Profiles of the user's config take precedence over the system-wide config, within a config the first matching profile is used.
+///
+/// # Arguments
+/// * `configs`: The TOML config files in the order in which they are applied.
+///
+/// # Returns
+/// The name of the first matching profile or `None` if no profile matches.
+fn get_matching_profile(configs: &[(PathBuf, toml::Table)]) -> Option<String> {
+    let hostname: Option<String> = string_utils::get_hostname();
+    configs.iter().rev()
+        .filter_map(|(_, config)| get_table(config, &["profile"]))
+        .find_map(|profiles| profiles.iter().find(|(_, profile)| profile.as_table().is_some_and(|table| is_profile_matching(table, hostname.as_deref()))))
+        .map(|(name, _)| name.to_string())
+}
+
+
+/// Gets a table of a TOML config file by its path of keys, e.g. `["profile", "server"]` for `[profile.server]`.
+fn get_table<'a>(config: &'a toml::Table, keys: &[&str]) -> Option<&'a toml::Table> {
+    keys.iter().try_fold(config, |table, key| table.get(*key).and_then(toml::Value::as_table))
+}
+
+
+/// Gets the name of the profile selected with `--profile` in the CLI arguments.
+///
+/// # Arguments
+/// * `cli_args`: The arguments provided in the CLI.
+///
+/// # Returns
+/// The name of the profile or `None` if none was selected.
+pub fn get_profile_name(cli_args: &[String]) -> Option<String> {
+    let mut selected: Option<String> = None;
+    let mut args = cli_args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            selected = args.next().cloned();
+        } else if let Some(name) = arg.strip_prefix("--profile=") {
+            selected = Some(name.to_string());
+        }
+    }
+    selected
+}
+
+
+/// Gets the output templates defined in the `[templates]` section of the system-wide and the user's config files.
+/// Templates of the user's config override system-wide templates with the same name, and templates of a TOML
+/// config file override the ones of the flag-per-line config file in the same directory.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The names and the handlebars templates.
+pub fn get_templates() -> Vec<(String, String)> {
+    let mut templates: Vec<(String, String)> = Vec::new();
+    for path in [Some(PathBuf::from(SYSTEM_CONFIG_PATH)), get_user_config_path()].into_iter().flatten() {
+        let toml_templates: Vec<(String, String)> = read_toml_config(&get_toml_config_path(&path)).as_ref()
+            .and_then(|config| get_table(config, &["templates"]))
+            .map(|table| table.iter().filter_map(|(name, template)| Some((name.to_string(), template.as_str()?.to_string()))).collect())
+            .unwrap_or_default();
+
+        for (name, template) in read_config_section(&path, "templates").into_iter().chain(toml_templates) {
+            templates.retain(|(existing_name, _)| *existing_name != name);
+            templates.push((name, template));
+        }
+    }
+    templates
+}
+
+
+/// Represents the flags which a config file, or a profile of it, applies.
+#[derive(Debug)]
+pub struct ConfigSource {
+    pub name: String,
+    pub args: Vec<String>,
+    /// The name of the profile if the source is a profile section.
+    pub profile: Option<String>,
+    /// If `true` the profile wasn't selected with `--profile` but by its `match-hostname` or `match-env` rules.
+    pub auto_selected: bool
+}
+
+
+/// Gets the flags of the system-wide and the user's config files in the order in which they are applied.
+/// Of each config, the flag-per-line file is applied first and then the `[defaults]`, `[theme]` and `[pager]` of the TOML file.
+/// The sections of the selected profile are applied after all of them. If no profile is selected, the first one whose rules match is used.
+///
+/// # Arguments
+/// * `profile`: The name of the profile selected with `--profile`, if any.
+///
+/// # Returns
+/// The config files and profile sections which exist, including the ones without flags.
+pub fn get_config_sources(profile: Option<&str>) -> Vec<ConfigSource> {
+    let mut sources: Vec<ConfigSource> = Vec::new();
+    let mut configs: Vec<(PathBuf, toml::Table)> = Vec::new();
+    for path in [Some(PathBuf::from(SYSTEM_CONFIG_PATH)), get_user_config_path()].into_iter().flatten() {
+        if path.exists() {
+            sources.push(ConfigSource { name: path.display().to_string(), args: read_config_file(&path), profile: None, auto_selected: false });
+        }
+
+        let toml_path: PathBuf = get_toml_config_path(&path);
+        let Some(config) = read_toml_config(&toml_path) else {
+            continue;
+        };
+        let mut args: Vec<String> = get_table(&config, &["defaults"]).map(get_table_args).unwrap_or_default();
+        args.extend(get_table(&config, &["theme"]).map(get_theme_args).unwrap_or_default());
+        args.extend(get_table(&config, &["pager"]).map(get_pager_args).unwrap_or_default());
+        sources.push(ConfigSource { name: toml_path.display().to_string(), args, profile: None, auto_selected: false });
+        configs.push((toml_path, config));
+    }
+
+    let auto_selected: bool = profile.is_none();
+    let Some(profile) = profile.map(String::from).or_else(|| get_matching_profile(&configs)) else {
+        return sources;
+    };
+    for (toml_path, config) in &configs {
+        if let Some(table) = get_table(config, &["profile", &profile]) {
+            sources.push(ConfigSource {
+                name: format!("{} [profile.{}]", toml_path.display(), profile),
+                args: get_profile_args(table),
+                profile: Some(profile.clone()),
+                auto_selected
+            });
+        }
+    }
+    sources
+}
+
+
+/// Gets the flags of the config files, exiting if the selected profile isn't defined in any of them.
+///
+/// # Arguments
+/// * `profile`: The name of the profile selected with `--profile`, if any.
+///
+/// # Returns
+/// The config files and profile sections which exist.
+pub fn get_checked_config_sources(profile: Option<&str>) -> Vec<ConfigSource> {
+    let sources: Vec<ConfigSource> = get_config_sources(profile);
+    if let (Some(profile), false) = (profile, sources.iter().any(|source| source.profile.is_some())) {
+        string_utils::print_error("config", &format!("The profile '{}' isn't defined in a config file.", profile));
+        process::exit(1);
+    }
+    sources
+}
+
+
+/// Inserts the flags of the system-wide and the user's config files in front of the flags provided in the CLI.
+/// Since later flags override earlier ones, the CLI overrides the user's config which overrides the system-wide config.
+///
+/// # Arguments
+/// * `cli_args`: The arguments provided in the CLI, starting with the program name.
+/// * `sources`: The config files and profile sections in the order in which they are applied, see `get_checked_config_sources`.
+///
+/// # Returns
+/// The program name followed by the flags of the config files and the CLI.
+pub fn merge_config_args(cli_args: &[String], sources: &[ConfigSource]) -> Vec<String> {
+    let mut args: Vec<String> = cli_args.iter().take(1).cloned().collect();
+    for source in sources {
+        args.extend(source.args.iter().cloned());
+    }
+    args.extend(cli_args.iter().skip(1).cloned());
+
+    args
+}
+
+
+/// Groups arguments into one flag and its values per line, like in the flag-per-line config file.
+///
+/// # Arguments
+/// * `args`: The flags and values as separate arguments.
+///
+/// # Returns
+/// The lines, e.g. "--proto tcp".
+fn get_flag_lines(args: &[String]) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for arg in args {
+        match lines.last_mut() {
+            Some(line) if !arg.starts_with('-') => {
+                line.push(' ');
+                line.push_str(arg);
+            }
+            _ => lines.push(arg.to_string()),
+        }
+    }
+    lines
+}
+
+
+/// Quotes a value for a config section and escapes it, the reverse of `unquote_value`.
+fn quote_value(value: &str) -> String {
+    let escaped: String = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+
+/// Prints the effective configuration, i.e. the flags applied by every config file in order and the merged templates.
+/// The output is a valid flag-per-line config file itself.
+///
+/// # Arguments
+/// * `profile`: The name of the profile selected with `--profile`, if any.
+///
+/// # Returns
+/// None
+pub fn print_config(profile: Option<&str>) {
+    let sources: Vec<ConfigSource> = get_checked_config_sources(profile);
+    if sources.is_empty() {
+        string_utils::pretty_print_info("There are no config files.");
+    }
+    for source in sources {
+        sout!("# {}", source.name);
+        for line in get_flag_lines(&source.args) {
+            sout!("{}", line);
+        }
+        sout!("");
+    }
+
+    let templates: Vec<(String, String)> = get_templates();
+    if !templates.is_empty() {
+        sout!("[templates]");
+        for (name, template) in templates {
+            sout!("{} = {}", name, quote_value(&template));
+        }
+    }
+}
+
+
+/// Gets the path of the config file which `somo config edit` opens, i.e. the user's TOML config unless only a flag-per-line config exists.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The path of the config file or `None` if neither `XDG_CONFIG_HOME` nor `HOME` is set.
+fn get_editable_config_path() -> Option<PathBuf> {
+    let path: PathBuf = get_user_config_path()?;
+    let toml_path: PathBuf = get_toml_config_path(&path);
+    if path.exists() && !toml_path.exists() {
+        return Some(path);
+    }
+    Some(toml_path)
+}
+
+
+/// Opens the user's config file in `$VISUAL` or `$EDITOR` (or else `vi`), creating its directory if needed.
+/// Exits if the editor can't be started or fails.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// None
+pub fn edit_config() {
+    let Some(path) = get_editable_config_path() else {
+        string_utils::pretty_print_error("Couldn't find the config directory, neither XDG_CONFIG_HOME nor HOME is set.");
+        process::exit(1);
+    };
+    if let Some(Err(error)) = path.parent().map(fs::create_dir_all) {
+        string_utils::pretty_print_error(&format!("Couldn't create the config directory: {}", error));
+        process::exit(1);
+    }
+
+    // the editor may contain arguments, e.g. "code --wait"
+    let editor: String = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| "vi".to_string());
+    let mut editor_args = editor.split_whitespace();
+    let status = process::Command::new(editor_args.next().unwrap_or("vi"))
+        .args(editor_args)
+        .arg(&path)
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            string_utils::pretty_print_error(&format!("The editor '{}' failed ({}).", editor, status));
+            process::exit(1);
+        }
+        Err(error) => {
+            string_utils::pretty_print_error(&format!("Couldn't start the editor '{}': {}", editor, error));
+            process::exit(1);
+        }
+    }
+}
+
+
+/// Represents a problem found in a config file by `somo config validate`.
+#[derive(Debug, PartialEq)]
+pub struct ConfigProblem {
+    pub path: String,
+    pub line: Option<usize>,
+    pub message: String
+}
+
+
+/// Gets the line number of an offset into the content of a file.
+fn get_line_number(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+
+/// Checks a flag-per-line config file for lines which aren't known flags and sections other than `[templates]`.
+///
+/// # Arguments
+/// * `content`: The content of the config file.
+/// * `flags`: The long names of all flags, e.g. "proto".
+///
+/// # Returns
+/// The line numbers and messages of the problems.
+fn validate_config_file(content: &str, flags: &[String]) -> Vec<(usize, String)> {
+    let mut problems: Vec<(usize, String)> = Vec::new();
+    let mut section: Option<&str> = None;
+    for (idx, line) in content.lines().enumerate().map(|(idx, line)| (idx + 1, line.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            section = Some(line);
+            if line != "[templates]" {
+                problems.push((idx, format!("unknown section {}, only [templates] is supported", line)));
+            }
+            continue;
+        }
+        match section {
+            Some("[templates]") if !line.contains('=') => problems.push((idx, "expected a template like `name = \"{{pid}}\"`".to_string())),
+            Some(_) => {}
+            None => {
+                let flag: &str = line.split_whitespace().next().unwrap_or_default();
+                let name: &str = flag.strip_prefix("--").unwrap_or_default().split('=').next().unwrap_or_default();
+                if !flag.starts_with("--") {
+                    problems.push((idx, format!("expected a flag like `--proto tcp`, found '{}'", flag)));
+                } else if !flags.iter().any(|known_flag| known_flag == name) {
+                    problems.push((idx, format!("unknown flag '{}'", flag)));
+                }
+            }
+        }
+    }
+    problems
+}
+
+
+/// Checks a TOML config file for syntax errors, unknown sections, unknown flags and templates which aren't strings.
+///
+/// # Arguments
+/// * `content`: The content of the config file.
+/// * `flags`: The long names of all flags, e.g. "proto".
+///
+/// # Returns
+/// The line numbers (if known) and messages of the problems.
+fn validate_toml_config(content: &str, flags: &[String]) -> Vec<(Option<usize>, String)> {
+    let document = match toml_edit::ImDocument::parse(content) {
+        Ok(document) => document,
+        Err(error) => {
+            let line: Option<usize> = error.span().map(|span| get_line_number(content, span.start));
+            return vec![(line, error.message().trim().replace('\n', " "))];
+        }
+    };
+    let line_of = |key: &toml_edit::Key| key.span().map(|span| get_line_number(content, span.start));
+
+    let mut problems: Vec<(Option<usize>, String)> = Vec::new();
+    let check_flags = |table: &dyn toml_edit::TableLike, section: &str, problems: &mut Vec<(Option<usize>, String)>| {
+        for (name, _) in table.iter() {
+            let flag: String = name.replace('_', "-");
+            let is_rule: bool = section.starts_with("profile.") && PROFILE_RULES.contains(&flag.as_str());
+            if !is_rule && !flags.contains(&flag) {
+                let key: Option<&toml_edit::Key> = table.get_key_value(name).map(|(key, _)| key);
+                problems.push((key.and_then(line_of), format!("unknown flag '{}' in [{}]", name, section)));
+            }
+        }
+    };
+
+    let root = document.as_table();
+    for (name, item) in root.iter() {
+        let line: Option<usize> = root.key(name).and_then(line_of);
+        let Some(table) = item.as_table_like() else {
+            problems.push((line, format!("'{}' should be a section like [{}]", name, name)));
+            continue;
+        };
+        match name {
+            "defaults" => check_flags(table, name, &mut problems),
+            "theme" => {
+                for (state, value) in table.iter().filter(|(state, value)| !value.is_str() && !["color-rows", "zebra"].contains(&state.replace('_', "-").as_str())) {
+                    let key: Option<&toml_edit::Key> = table.get_key_value(state).map(|(key, _)| key);
+                    problems.push((key.and_then(line_of), format!("the color of '{}' should be a string, not {}", state, value.type_name())));
+                }
+            }
+            "pager" => {
+                for (option, _) in table.iter().filter(|(option, _)| !["command", "paging", "min-lines"].contains(&option.replace('_', "-").as_str())) {
+                    let key: Option<&toml_edit::Key> = table.get_key_value(option).map(|(key, _)| key);
+                    problems.push((key.and_then(line_of), format!("unknown option '{}' in [pager], expected command, paging or min-lines", option)));
+                }
+            }
+            "templates" => {
+                for (template, value) in table.iter().filter(|(_, value)| !value.is_str()) {
+                    let key: Option<&toml_edit::Key> = table.get_key_value(template).map(|(key, _)| key);
+                    problems.push((key.and_then(line_of), format!("the template '{}' should be a string, not {}", template, value.type_name())));
+                }
+            }
+            "profile" => {
+                for (profile, item) in table.iter() {
+                    match item.as_table_like() {
+                        Some(profile_table) => {
+                            check_flags(profile_table, &format!("profile.{}", profile), &mut problems);
+                            for (rule, value) in profile_table.iter().filter(|(rule, _)| PROFILE_RULES.contains(&rule.replace('_', "-").as_str())) {
+                                let is_valid: bool = value.as_str().is_some() || value.as_array().is_some_and(|patterns| patterns.iter().all(|pattern| pattern.is_str()));
+                                if !is_valid {
+                                    let key: Option<&toml_edit::Key> = profile_table.get_key_value(rule).map(|(key, _)| key);
+                                    problems.push((key.and_then(line_of), format!("the rule '{}' of [profile.{}] should be a string or a list of strings", rule, profile)));
+                                }
+                            }
+                        }
+                        None => {
+                            let key: Option<&toml_edit::Key> = table.get_key_value(profile).map(|(key, _)| key);
+                            problems.push((key.and_then(line_of), format!("the profile '{}' should be a section like [profile.{}]", profile, profile)));
+                        }
+                    }
+                }
+            }
+            _ => problems.push((line, format!("unknown section [{}], expected [defaults], [theme], [pager], [templates] or [profile.<name>]", name))),
+        }
+    }
+    problems
+}
+
+
+/// Checks the system-wide and the user's config files for syntax errors, unknown flags and unknown sections.
+/// The values of the flags aren't checked here.
+///
+/// # Arguments
+/// * `flags`: The long names of all flags, e.g. "proto".
+///
+/// # Returns
+/// The problems of all config files which exist.
+pub fn validate_config(flags: &[String]) -> Vec<ConfigProblem> {
+    let mut problems: Vec<ConfigProblem> = Vec::new();
+    for path in [Some(PathBuf::from(SYSTEM_CONFIG_PATH)), get_user_config_path()].into_iter().flatten() {
+        if let Ok(content) = fs::read_to_string(&path) {
+            problems.extend(validate_config_file(&content, flags).into_iter().map(|(line, message)| {
+                ConfigProblem { path: path.display().to_string(), line: Some(line), message }
+            }));
+        }
+        let toml_path: PathBuf = get_toml_config_path(&path);
+        if let Ok(content) = fs::read_to_string(&toml_path) {
+            problems.extend(validate_toml_config(&content, flags).into_iter().map(|(line, message)| {
+                ConfigProblem { path: toml_path.display().to_string(), line, message }
+            }));
+        }
+    }
+    problems
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_toml_args() {
+        let config: toml::Table = concat!(
+            "[defaults]\n",
+            "proto = \"tcp\"\n",
+            "exclude_ipv6 = true\n",
+            "open = false\n",
+            "columns = [\"proto\", \"state\"]\n",
+            "[theme]\n",
+            "listen = \"blue\"\n",
+            "color-rows = true\n",
+            "[profile.server]\n",
+            "local-port = 443\n",
+        ).parse().unwrap();
+
+        assert_eq!(
+            get_table_args(get_table(&config, &["defaults"]).unwrap()),
+            ["--proto", "tcp", "--exclude-ipv6", "--columns", "proto", "--columns", "state"]
+        );
+        assert_eq!(get_theme_args(get_table(&config, &["theme"]).unwrap()), ["--state-color", "listen=blue", "--color-rows"]);
+        assert_eq!(get_table_args(get_table(&config, &["profile", "server"]).unwrap()), ["--local-port", "443"]);
+        assert_eq!(get_profile_name(&["somo".to_string(), "--profile=server".to_string()]), Some("server".to_string()));
+    }
+
+    #[test]
+    fn test_profile_rules() {
+        assert!(matches_pattern("web-*", "web-01"));
+        assert!(matches_pattern("*-0?", "db-01"));
+        assert!(!matches_pattern("web-*", "db-01"));
+
+        let config: toml::Table = concat!(
+            "[profile.db]\n",
+            "match-hostname = [\"db-*\", \"pg-*\"]\n",
+            "[profile.web]\n",
+            "match-hostname = \"web-*\"\n",
+            "open = true\n",
+        ).parse().unwrap();
+        let profiles: &toml::Table = get_table(&config, &["profile"]).unwrap();
+
+        assert!(is_profile_matching(profiles["db"].as_table().unwrap(), Some("pg-2")));
+        assert!(!is_profile_matching(profiles["web"].as_table().unwrap(), Some("pg-2")));
+        assert!(!is_profile_matching(profiles["web"].as_table().unwrap(), None));
+        assert_eq!(get_profile_args(profiles["web"].as_table().unwrap()), ["--open"]);
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let flags: Vec<String> = vec!["proto".to_string(), "exclude-ipv6".to_string()];
+        assert_eq!(
+            validate_config_file("--proto tcp\n--prot udp\n\n[templates]\nbrief = \"{{pid}}\"\n[other]\n", &flags),
+            [(2, "unknown flag '--prot'".to_string()), (6, "unknown section [other], only [templates] is supported".to_string())]
+        );
+
+        let problems = validate_toml_config("[defaults]\nexclude_ipv6 = true\n\n[profile.server]\nprot = \"tcp\"\n", &flags);
+        assert_eq!(problems, [(Some(5), "unknown flag 'prot' in [profile.server]".to_string())]);
+        assert_eq!(validate_toml_config("[defaults\n", &flags)[0].0, Some(1));
+    }
+}
@@ -1,12 +1,86 @@
 use procfs::process::Stat;
-use procfs::process::FDTarget;
-use std::collections::HashMap;
+use procfs::process::{FDTarget, Process};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, trace};
+use serde::{Deserialize, Serialize};
 
 use crate::string_utils;
 use crate::address_checkers;
+use crate::netlink;
 
-/// Contains options for filtering a `Conntection`.
+/// Represents an error while collecting the connections, e.g. from `/proc` or from a fixture file.
 #[derive(Debug)]
+pub enum Error {
+    Proc(procfs::ProcError),
+    Io(std::io::Error),
+    Fixture(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Proc(error) => write!(f, "couldn't read from /proc: {}", error),
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Fixture(error) => write!(f, "invalid connections: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Proc(error) => Some(error),
+            Error::Io(error) => Some(error),
+            Error::Fixture(error) => Some(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Fixture(error)
+    }
+}
+
+impl From<procfs::ProcError> for Error {
+    fn from(error: procfs::ProcError) -> Self {
+        Error::Proc(error)
+    }
+}
+
+/// The result of reading the sockets or processes from `/proc`.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The time spent in each phase of collecting the connections, see `get_all_connections_timed`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Timings {
+    pub process_scan: Duration,
+    pub socket_read: Duration,
+    pub filtering: Duration
+}
+
+/// Contains options for filtering a `Conntection`.
+/// Filters which aren't set match every connection, they can be set directly or using the builder methods:
+/// ```
+/// let filter_options = somo::FilterOptions::new().proto("tcp").local_port("8080");
+/// assert_eq!(filter_options.by_proto.as_deref(), Some("tcp"));
+/// ```
+#[derive(Debug, Default)]
 pub struct FilterOptions {
     pub by_proto: Option<String>,
     pub by_program: Option<String>,
@@ -15,47 +89,625 @@ pub struct FilterOptions {
     pub by_remote_port: Option<String>,
     pub by_local_port: Option<String>,
     pub by_open: bool,
-    pub exclude_ipv6: bool
+    pub by_states: Vec<State>,
+    pub by_uid: Option<u32>,
+    pub by_scope: Option<address_checkers::AddressScope>,
+    pub exclude_ipv6: bool,
+    pub collapse_v4mapped: bool,
+    pub skip_processes: bool,
+    pub narrow_processes: bool,
+    pub optional_protocols: Vec<String>
+}
+
+impl FilterOptions {
+    /// Creates filter options which match every connection.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only matches connections of a protocol, one of `PROTOCOLS`.
+    pub fn proto(mut self, proto: &str) -> Self {
+        self.by_proto = Some(proto.to_string());
+        self
+    }
+
+    /// Only matches connections of a program, e.g. "sshd".
+    pub fn program(mut self, program: &str) -> Self {
+        self.by_program = Some(program.to_string());
+        self
+    }
+
+    /// Only matches connections of a process, also sockets it shares with other processes.
+    pub fn pid(mut self, pid: &str) -> Self {
+        self.by_pid = Some(pid.to_string());
+        self
+    }
+
+    /// Only matches connections to a remote address, e.g. "192.168.0.2".
+    pub fn remote_address(mut self, remote_address: &str) -> Self {
+        self.by_remote_address = Some(remote_address.to_string());
+        self
+    }
+
+    /// Only matches connections to a remote port.
+    pub fn remote_port(mut self, remote_port: &str) -> Self {
+        self.by_remote_port = Some(remote_port.to_string());
+        self
+    }
+
+    /// Only matches connections using a local port.
+    pub fn local_port(mut self, local_port: &str) -> Self {
+        self.by_local_port = Some(local_port.to_string());
+        self
+    }
+
+    /// If `true`, only matches open connections, i.e. connections which aren't closed.
+    pub fn open(mut self, open: bool) -> Self {
+        self.by_open = open;
+        self
+    }
+
+    /// Only matches connections in one of the selected states, can be used multiple times.
+    pub fn state(mut self, state: State) -> Self {
+        self.by_states.push(state);
+        self
+    }
+
+    /// Only matches connections owned by a user.
+    pub fn uid(mut self, uid: u32) -> Self {
+        self.by_uid = Some(uid);
+        self
+    }
+
+    /// Only matches connections whose remote address has a scope, e.g. `AddressScope::Public` for peers on the internet.
+    pub fn scope(mut self, scope: address_checkers::AddressScope) -> Self {
+        self.by_scope = Some(scope);
+        self
+    }
+
+    /// If `true`, IPv6 sockets aren't read.
+    pub fn exclude_ipv6(mut self, exclude_ipv6: bool) -> Self {
+        self.exclude_ipv6 = exclude_ipv6;
+        self
+    }
+
+    /// If `true`, IPv4-mapped IPv6 addresses (e.g. "::ffff:65.9.95.5" of dual-stack sockets) become IPv4 addresses,
+    /// so that their sockets are treated as IPv4 sockets, e.g. by `exclude_ipv6`.
+    pub fn collapse_v4mapped(mut self, collapse_v4mapped: bool) -> Self {
+        self.collapse_v4mapped = collapse_v4mapped;
+        self
+    }
+
+    /// If `true`, the processes owning the sockets aren't resolved (their program and PID are unknown), which makes collecting the connections much faster.
+    pub fn skip_processes(mut self, skip_processes: bool) -> Self {
+        self.skip_processes = skip_processes;
+        self
+    }
+
+    /// If `true`, only the processes which can match `pid` and `program` are inspected, which makes collecting their connections faster.
+    /// Sockets shared with other processes then only have the matching processes in `associated_pids`, so it's meant for when the owners aren't shown.
+    pub fn narrow_processes(mut self, narrow_processes: bool) -> Self {
+        self.narrow_processes = narrow_processes;
+        self
+    }
+
+    /// Also reads the sockets of an optional protocol (one of `OPTIONAL_PROTOCOLS`), which are only read if enabled or selected by `proto`.
+    pub fn optional_protocol(mut self, proto: &str) -> Self {
+        self.optional_protocols.push(proto.to_string());
+        self
+    }
+
+    /// Checks if the sockets of a protocol are read, i.e. if it is selected by `proto` or, if no protocol is selected, if it isn't optional or enabled.
+    /// 
+    /// # Arguments
+    /// * `proto`: The protocol, one of `PROTOCOLS`.
+    /// 
+    /// # Returns
+    /// `true` if the sockets of the protocol are read.
+    pub fn reads_protocol(&self, proto: &str) -> bool {
+        match &self.by_proto {
+            Some(filter_proto) => filter_proto == proto,
+            None => !OPTIONAL_PROTOCOLS.contains(&proto) || self.optional_protocols.iter().any(|optional_proto| optional_proto == proto)
+        }
+    }
+}
+
+/// Represents the state of a socket as found in the socket tables of `/proc/net`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Established,
+    SynSent,
+    SynRecv,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Close,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+    NewSynRecv,
+}
+
+impl State {
+    /// All states, in the order of their numbers in the kernel.
+    pub const ALL: [State; 12] = [
+        State::Established, State::SynSent, State::SynRecv, State::FinWait1, State::FinWait2, State::TimeWait,
+        State::Close, State::CloseWait, State::LastAck, State::Listen, State::Closing, State::NewSynRecv,
+    ];
+
+    /// Gets the name of the state as displayed in the table, e.g. "timewait".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            State::Established => "established",
+            State::SynSent => "synsent",
+            State::SynRecv => "synrecv",
+            State::FinWait1 => "finwait1",
+            State::FinWait2 => "finwait2",
+            State::TimeWait => "timewait",
+            State::Close => "close",
+            State::CloseWait => "closewait",
+            State::LastAck => "lastack",
+            State::Listen => "listen",
+            State::Closing => "closing",
+            State::NewSynRecv => "newsynrecv",
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for State {
+    type Err = String;
+
+    /// Parses the name of a state case-insensitively, with or without underscores, e.g. "time_wait" or "TIMEWAIT".
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        let name: String = name.replace(['_', '-'], "").to_ascii_lowercase();
+        State::ALL.into_iter().find(|state| state.as_str() == name).ok_or(format!("unknown state '{}'", name))
+    }
+}
+
+impl From<procfs::net::TcpState> for State {
+    fn from(state: procfs::net::TcpState) -> Self {
+        use procfs::net::TcpState;
+        match state {
+            TcpState::Established => State::Established,
+            TcpState::SynSent => State::SynSent,
+            TcpState::SynRecv => State::SynRecv,
+            TcpState::FinWait1 => State::FinWait1,
+            TcpState::FinWait2 => State::FinWait2,
+            TcpState::TimeWait => State::TimeWait,
+            TcpState::Close => State::Close,
+            TcpState::CloseWait => State::CloseWait,
+            TcpState::LastAck => State::LastAck,
+            TcpState::Listen => State::Listen,
+            TcpState::Closing => State::Closing,
+            TcpState::NewSynRecv => State::NewSynRecv,
+        }
+    }
+}
+
+impl From<procfs::net::UdpState> for State {
+    fn from(state: procfs::net::UdpState) -> Self {
+        match state {
+            procfs::net::UdpState::Established => State::Established,
+            procfs::net::UdpState::Close => State::Close,
+        }
+    }
+}
+
+/// Represents the address family of a socket, i.e. whether it was found in the IPv4 or the IPv6 socket table.
+/// Sockets of IPv4-mapped addresses are IPv6 sockets, even if their addresses are shown as IPv4 addresses (see `collapse_v4mapped`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Family {
+    Ipv4,
+    Ipv6,
+}
+
+impl Family {
+    /// Gets the family of a socket by its address.
+    pub fn of(address: &IpAddr) -> Self {
+        if address.is_ipv4() { Family::Ipv4 } else { Family::Ipv6 }
+    }
+
+    /// Gets the name of the family as displayed in the table, e.g. "ipv6".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Family::Ipv4 => "ipv4",
+            Family::Ipv6 => "ipv6",
+        }
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// (De)serializes an IP address in the text format of the table, i.e. IPv6 addresses wrapped in brackets.
+mod address_text {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::net::IpAddr;
+
+    use crate::string_utils;
+
+    pub fn serialize<S: Serializer>(address: &IpAddr, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&string_utils::format_address(address))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<IpAddr, D::Error> {
+        let text: String = String::deserialize(deserializer)?;
+        string_utils::parse_remote_address(&text).ok_or_else(|| D::Error::custom(format!("invalid address '{}'", text)))
+    }
+}
+
+/// (De)serializes a port as a string, e.g. "443".
+mod port_text {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(port: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&port.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+        let text: String = String::deserialize(deserializer)?;
+        text.parse().map_err(|_| D::Error::custom(format!("invalid port '{}'", text)))
+    }
+}
+
+/// The program of connections (e.g. older snapshots) without a parent program.
+fn unknown_program() -> String {
+    "-".to_string()
+}
+
+/// (De)serializes an optional PID as a string, "-" if it is unknown.
+mod pid_text {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(pid: &Option<i32>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&pid.map_or("-".to_string(), |pid| pid.to_string()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i32>, D::Error> {
+        let text: String = String::deserialize(deserializer)?;
+        match text.as_str() {
+            "-" => Ok(None),
+            pid => pid.parse().map(Some).map_err(|_| D::Error::custom(format!("invalid PID '{}'", text))),
+        }
+    }
 }
 
 /// Represents a processed socket connection with all its attributes.
-#[derive(Debug)]
+/// It is (de)serialized in the same text format as displayed in the table, e.g. `"local_port": "22"` and `"pid": "-"` if the PID is unknown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Connection {
     pub proto: String,
-    pub local_port: String,
-    pub remote_address: String,
-    pub remote_port: String,
+    pub family: Family,
+    #[serde(with = "address_text")]
+    pub local_address: IpAddr,
+    #[serde(with = "port_text")]
+    pub local_port: u16,
+    #[serde(with = "address_text")]
+    pub remote_address: IpAddr,
+    #[serde(with = "port_text")]
+    pub remote_port: u16,
     pub program: String,
-    pub pid: String,
-    pub state: String,
+    #[serde(with = "pid_text")]
+    pub pid: Option<i32>,
+    #[serde(default, with = "pid_text")]
+    pub ppid: Option<i32>,
+    /// The name of the parent process, e.g. "systemd" or "bash", "-" if unknown.
+    #[serde(default = "unknown_program")]
+    pub pprogram: String,
+    pub state: State,
+    pub uid: u32,
+    pub user: String,
+    pub tty: String,
+    pub login_session: Option<u32>,
+    /// The number of seconds since the owning process was started, `None` if the process is unknown.
+    #[serde(default)]
+    pub proc_uptime: Option<u64>,
+    /// The PIDs of all processes holding the socket, ordered by PID, more than one if it's shared (e.g. by preforked workers).
+    #[serde(default)]
+    pub associated_pids: Vec<i32>,
+    /// The amount of connections of a TCP listener which wait to be accepted, `None` if it isn't a TCP listener or it's unknown.
+    #[serde(default)]
+    pub accept_queue: Option<u32>,
+    /// The maximum length of the accept queue of a TCP listener (the backlog passed to `listen`), new connections are dropped once it's full.
+    #[serde(default)]
+    pub backlog: Option<u32>,
+    /// The amount of packets a UDP (or raw) socket dropped, e.g. because its receive buffer was full, `None` for other protocols.
+    #[serde(default)]
+    pub drops: Option<u64>,
+    pub tunnel: Option<String>,
+    pub container: Option<String>,
     pub address_type: address_checkers::IPType,
-    pub abuse_score: Option<i64>
+    /// The scope of the remote address, e.g. "private" for a peer in the LAN or "public" for one on the internet.
+    pub address_scope: address_checkers::AddressScope,
+    pub threat_score: Option<i64>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub asn: Option<u32>,
+    pub org: Option<String>,
+    /// The name of a LAN peer resolved using mDNS or LLMNR, e.g. "printer.local".
+    #[serde(default)]
+    pub remote_name: Option<String>,
+    pub local_service: Option<String>,
+    pub remote_service: Option<String>,
+    #[serde(skip)]
+    pub extra_fields: Vec<(String, String)>,
+    pub blocked: bool,
+    /// The group of TCP listeners bound to the same address and port with `SO_REUSEPORT` (e.g. a pool of workers), `None` if the listener is alone.
+    #[serde(default)]
+    pub reuseport_group: Option<u32>,
+    /// If `true` the connection represents an IPv4 and an IPv6 listener of the same program and port, see `merge_dual_stack_listeners`.
+    #[serde(default)]
+    pub dual_stack: bool,
+    /// The bytes per second a TCP connection sent (and got acknowledged) between two samples of `somo top`, `None` if it wasn't sampled.
+    #[serde(default)]
+    pub tx_rate: Option<u64>,
+    /// The bytes per second a TCP connection received between two samples of `somo top`, `None` if it wasn't sampled.
+    #[serde(default)]
+    pub rx_rate: Option<u64>
 }
 
 
-/// Gets all running processes on the system using the "procfs" crate.
+/// Gets the running processes on the system using the "procfs" crate.
 /// This code is taken from the "procfs" crate documentation.
+/// Only the processes which can match the `pid` and `program` filter options are inspected if `narrow_processes` is set, none if `skip_processes` is set.
+/// Processes which can't be inspected (e.g. due to missing permissions) are skipped and summarized in a warning.
 /// 
 /// # Arguments
-/// None
+/// * `filter_options`: The filter options provided by the user.
+/// 
+/// # Returns
+/// A map of the current processes (by the inodes of their sockets) or an error if the processes can't be listed.
+/// Sockets shared by several processes (e.g. the preforked workers of a server) have all of them, ordered by PID.
+pub(crate) fn get_processes(filter_options: &FilterOptions) -> Result<HashMap<u64, Vec<Arc<Stat>>>> {
+    if filter_options.skip_processes {
+        info!("Skipping the process scan, since the owners of the sockets aren't needed");
+        return Ok(HashMap::new());
+    }
+    let all_procs: Vec<procfs::ProcResult<Process>> = match &filter_options.by_pid {
+        // only sockets without a known owner can match "-"
+        Some(pid) if filter_options.narrow_processes => pid.parse().map_or(Vec::new(), |pid| vec![Process::new(pid)]),
+        _ => procfs::process::all_processes()?.collect(),
+    };
+    let program: Option<&str> = filter_options.by_program.as_deref().filter(|_| filter_options.narrow_processes);
+    debug!("Inspecting the file descriptors of {} process(es)", all_procs.len());
+
+    // inspect the file descriptors of the processes in parallel, since this is the slowest part of a scan
+    let threads: usize = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size: usize = all_procs.len().div_ceil(threads).max(1);
+    let results: Vec<InspectedProcesses> = thread::scope(|scope| {
+        let handles: Vec<_> = all_procs.chunks(chunk_size).map(|chunk| scope.spawn(move || inspect_processes(chunk, program))).collect();
+        handles.into_iter().filter_map(|handle| handle.join().ok()).collect()
+    });
+
+    let mut map: HashMap<u64, Vec<Arc<Stat>>> = HashMap::new();
+    let mut failures: BTreeMap<&str, usize> = BTreeMap::new();
+    for (sockets, chunk_failures) in results {
+        for (inode, stat) in sockets {
+            map.entry(inode).or_default().push(stat);
+        }
+        for (reason, count) in chunk_failures {
+            *failures.entry(reason).or_default() += count;
+        }
+    }
+    // the lowest PID is usually the process which opened the socket and then forked the others, so it is considered the owner
+    for owners in map.values_mut() {
+        owners.sort_by_key(|stat| stat.pid);
+        owners.dedup_by_key(|stat| stat.pid);
+    }
+
+    info!("Found {} socket(s) owned by processes", map.len());
+    if !failures.is_empty() {
+        let amount: usize = failures.values().sum();
+        let reasons: Vec<String> = failures.iter().map(|(reason, count)| format!("{} ({})", reason, count)).collect();
+        string_utils::pretty_print_warning(&format!("Couldn't inspect {} process(es): {}.", amount, reasons.join(", ")));
+    }
+    Ok(map)
+}
+
+
+/// The inodes of the sockets of processes together with the (shared) stat of the process owning them,
+/// and the amount of processes which couldn't be inspected by the reason.
+type InspectedProcesses = (Vec<(u64, Arc<Stat>)>, BTreeMap<&'static str, usize>);
+
+
+/// Finds the sockets of processes by reading their file descriptors.
+/// 
+/// # Arguments
+/// * `processes`: The processes to inspect.
+/// * `program`: If set, the file descriptors of other programs aren't read.
 /// 
 /// # Returns
-/// A map of all current processes.
-fn get_processes() -> HashMap<u64, Stat> {
-    let all_procs = procfs::process::all_processes().unwrap();
+/// The sockets and the processes which couldn't be inspected.
+fn inspect_processes(processes: &[procfs::ProcResult<Process>], program: Option<&str>) -> InspectedProcesses {
+    let mut sockets: Vec<(u64, Arc<Stat>)> = Vec::new();
+    let mut failures: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut add_failure = |error: &procfs::ProcError| {
+        // processes which exited while being inspected aren't a failure
+        if !matches!(error, procfs::ProcError::NotFound(_)) {
+            *failures.entry(describe_proc_error(error)).or_default() += 1;
+        }
+    };
 
-    let mut map: HashMap<u64, Stat> = HashMap::new();
-    for p in all_procs {
-        let process = p.unwrap();
-        if let (Ok(stat), Ok(fds)) = (process.stat(), process.fd()) {
-            for fd in fds {
-                if let FDTarget::Socket(inode) = fd.unwrap().target {
-                    map.insert(inode, stat.clone());
+    for p in processes {
+        let process: &Process = match p {
+            Ok(process) => process,
+            Err(error) => {
+                add_failure(error);
+                continue;
+            }
+        };
+        let stat: Stat = match process.stat() {
+            Ok(stat) if program.is_some_and(|program| program != stat.comm) => continue,
+            Ok(stat) => stat,
+            Err(error) => {
+                trace!("Couldn't read the stat of process {}: {}", process.pid, describe_proc_error(&error));
+                add_failure(&error);
+                continue;
+            }
+        };
+        match process.fd() {
+            Ok(fds) => {
+                let stat: Arc<Stat> = Arc::new(stat);
+                // file descriptors which are closed while being read are skipped
+                for fd in fds.flatten() {
+                    if let FDTarget::Socket(inode) = fd.target {
+                        sockets.push((inode, Arc::clone(&stat)));
+                    }
                 }
             }
+            Err(error) => {
+                trace!("Couldn't read the file descriptors of {} (pid {}): {}", stat.comm, stat.pid, describe_proc_error(&error));
+                add_failure(&error);
+            }
         }
     }
-    map
+    (sockets, failures)
+}
+
+
+/// Describes why reading from `/proc` failed, e.g. to summarize the processes which couldn't be inspected.
+/// 
+/// # Arguments
+/// * `error`: The error.
+/// 
+/// # Returns
+/// A short description like "permission denied".
+fn describe_proc_error(error: &procfs::ProcError) -> &'static str {
+    match error {
+        procfs::ProcError::PermissionDenied(_) => "permission denied",
+        procfs::ProcError::NotFound(_) => "not found",
+        procfs::ProcError::Incomplete(_) => "incomplete data",
+        _ => "unexpected error",
+    }
+}
+
+
+/// Gets the name of a terminal device (as found in `/dev`) given its major and minor device number.
+/// 
+/// # Arguments
+/// * `tty_nr`: The major and minor device number of the terminal, as stored in `/proc/<pid>/stat`.
+/// 
+/// # Example
+/// ```ignore
+/// assert_eq!(get_tty_name((136, 3)), Some("pts/3".to_string()));
+/// assert_eq!(get_tty_name((0, 0)), None);
+/// ```
+/// 
+/// # Returns
+/// The name of the terminal or `None` if the process has no controlling terminal.
+fn get_tty_name(tty_nr: (i32, i32)) -> Option<String> {
+    match tty_nr {
+        (0, _) => None,
+        (major @ 136..=143, minor) => Some(format!("pts/{}", (major - 136) * 256 + minor)),
+        (4, minor @ 0..=63) => Some(format!("tty{}", minor)),
+        (4, minor) => Some(format!("ttyS{}", minor - 64)),
+        (major, minor) => Some(format!("{}:{}", major, minor)),
+    }
+}
+
+
+/// Gets the name and the parent PID of a process, e.g. to resolve the ancestors of a process owning a connection.
+/// 
+/// # Arguments
+/// * `pid`: The PID of the process.
+/// 
+/// # Returns
+/// The name and the parent PID of the process or `None` if the process doesn't exist (anymore).
+pub fn get_process_parent(pid: i32) -> Option<(String, i32)> {
+    let stat: Stat = procfs::process::Process::new(pid).and_then(|process| process.stat()).ok()?;
+    Some((stat.comm, stat.ppid))
+}
+
+
+/// Gets the login session of a process, i.e. the audit session ID which systemd-logind uses as its session ID (see `loginctl list-sessions`).
+/// 
+/// # Arguments
+/// * `pid`: The PID of the process.
+/// 
+/// # Returns
+/// The session ID or `None` if the process doesn't belong to a login session (e.g. daemons started by the init system).
+fn get_login_session(pid: i32) -> Option<u32> {
+    static UNSET_SESSION_ID: u32 = u32::MAX;
+
+    let session_id: u32 = fs::read_to_string(format!("/proc/{}/sessionid", pid)).ok()?.trim().parse().ok()?;
+    (session_id != UNSET_SESSION_ID).then_some(session_id)
+}
+
+
+/// Gets how long a process has been running, using its start time (in clock ticks after boot) from `/proc/<pid>/stat`.
+///
+/// # Arguments
+/// * `stat`: The status of the process.
+/// * `system_uptime`: The seconds since boot, from `/proc/uptime`.
+///
+/// # Returns
+/// The seconds since the process was started.
+fn get_process_uptime(stat: &Stat, system_uptime: f64) -> u64 {
+    let started_after_boot: f64 = stat.starttime as f64 / procfs::ticks_per_second() as f64;
+    (system_uptime - started_after_boot).max(0.0) as u64
+}
+
+
+/// Gets the UID whose connections should exclusively be shown when connections are restricted to the invoking user.
+/// Root is never restricted.
+/// 
+/// # Arguments
+/// * `restrict`: If `true` connections should be restricted to the invoking user.
+/// 
+/// # Returns
+/// The effective UID of the invoking user or `None` if connections shouldn't be restricted.
+pub fn get_restricted_uid(restrict: bool) -> Option<u32> {
+    if !restrict {
+        return None;
+    }
+    let uid: u32 = get_effective_uid();
+    (uid != 0).then_some(uid)
+}
+
+
+/// Gets the effective UID of the running process.
+/// If the UID can't be determined, it falls back to the unprivileged "nobody" user to not reveal other users' connections.
+/// 
+/// # Arguments
+/// None
+/// 
+/// # Returns
+/// The effective UID.
+pub fn get_effective_uid() -> u32 {
+    procfs::process::Process::myself().and_then(|process| process.status()).map_or(65534, |status| status.euid)
+}
+
+
+/// Gets the names of all users from `/etc/passwd`.
+/// 
+/// # Arguments
+/// None
+/// 
+/// # Returns
+/// A map of UIDs to user names, empty if `/etc/passwd` can't be read.
+fn get_user_names() -> HashMap<u32, String> {
+    let passwd: String = fs::read_to_string("/etc/passwd").unwrap_or_default();
+
+    passwd.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name: &str = fields.next()?;
+            let uid: u32 = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
 }
 
 
@@ -67,148 +719,498 @@ fn get_processes() -> HashMap<u64, Stat> {
 /// 
 /// # Returns
 /// `true` if the connection should be filtered out, `false` if not.
-fn filter_out_connection(connection_details: &Connection, filter_options: &FilterOptions) -> bool {
+pub(crate) fn filter_out_connection(connection_details: &Connection, filter_options: &FilterOptions) -> bool {
     match &filter_options.by_remote_port {
-        Some(filter_remote_port) if &connection_details.remote_port != filter_remote_port => return true,
+        Some(filter_remote_port) if filter_remote_port.parse() != Ok(connection_details.remote_port) => return true,
         _ => { }
     }
     match &filter_options.by_local_port {
-        Some(filter_local_port) if &connection_details.local_port != filter_local_port => return true,
+        Some(filter_local_port) if filter_local_port.parse() != Ok(connection_details.local_port) => return true,
         _ => { }
     }
     match &filter_options.by_remote_address {
-        Some(filter_remote_address) if &connection_details.remote_address != filter_remote_address => return true,
+        Some(filter_remote_address) if string_utils::parse_remote_address(filter_remote_address).map(|address| string_utils::normalize_address(&address)) != Some(string_utils::normalize_address(&connection_details.remote_address)) => return true,
         _ => { }
     }
     match &filter_options.by_program {
         Some(filter_program) if &connection_details.program != filter_program => return true,
         _ => { }
     }
+    // a shared socket matches every process holding it
     match &filter_options.by_pid {
-        Some(filter_pid) if &connection_details.pid != filter_pid => return true,
+        Some(filter_pid) if *filter_pid != string_utils::format_pid(connection_details.pid) && !connection_details.associated_pids.iter().any(|pid| pid.to_string() == *filter_pid) => return true,
         _ => { }
     }
-    if filter_options.by_open && connection_details.state == "close" {
+    if filter_options.by_open && connection_details.state == State::Close {
         return true;
     }
+    if !filter_options.by_states.is_empty() && !filter_options.by_states.contains(&connection_details.state) {
+        return true;
+    }
+    match filter_options.by_uid {
+        Some(filter_uid) if connection_details.uid != filter_uid => return true,
+        _ => { }
+    }
+    match filter_options.by_scope {
+        Some(filter_scope) if connection_details.address_scope != filter_scope => return true,
+        _ => { }
+    }
 
     false
 }
 
 
-/// Gets all currently open TCP connections using the "procfs" crate and processes them.
+/// All protocols which can be selected using the `proto` filter option.
+pub static PROTOCOLS: [&str; 6] = ["tcp", "udp", "udplite", "raw", "sctp", "dccp"];
+
+/// Protocols whose sockets are only read if they are selected or enabled (e.g. using `--raw`), since they are rarely of interest.
+pub static OPTIONAL_PROTOCOLS: [&str; 3] = ["raw", "sctp", "dccp"];
+
+/// Represents an entry of one of the socket tables in `/proc/net`, independent of its protocol.
+struct SocketEntry {
+    family: Family,
+    local_address: SocketAddr,
+    remote_address: SocketAddr,
+    state: State,
+    uid: u32,
+    inode: u64,
+    drops: Option<u64>
+}
+
+
+/// Handles the result of reading a socket table using the "procfs" crate, so that a missing or unreadable table doesn't abort the whole scan.
 /// 
 /// # Arguments
-/// * `all_processes`: A map of all running processes on the system.
-/// * `filter_options`: The filter options provided by the user.
-/// * `check_malicious`: If `true` the remote address is checked for abusiveness using the AbuseIPDB.com API.
+/// * `path`: The path of the socket table, used in the warning.
+/// * `table`: The result of reading the table.
 /// 
 /// # Returns
-/// All processed and filtered TCP connections as a `Connection` struct in a vector.
-async fn get_tcp_connections(all_processes: &HashMap<u64, Stat>, filter_options: &FilterOptions, check_malicious: bool) -> Vec<Connection> {
-    let mut tcp = procfs::net::tcp().unwrap();
-    if !filter_options.exclude_ipv6 {
-        tcp.extend(procfs::net::tcp6().unwrap());
+/// The entries of the table, empty if the table doesn't exist (e.g. `/proc/net/tcp6` if IPv6 is disabled) or can't be read, which is reported as a warning.
+fn read_socket_table<T>(path: &str, table: procfs::ProcResult<Vec<T>>) -> Vec<T> {
+    match table {
+        Ok(entries) => {
+            debug!("Read {} socket(s) from {}", entries.len(), path);
+            entries
+        }
+        Err(procfs::ProcError::NotFound(_)) => {
+            debug!("Skipped {}, it doesn't exist", path);
+            Vec::new()
+        }
+        Err(error) => {
+            string_utils::pretty_print_warning(&format!("Couldn't read {}: {}", path, describe_proc_error(&error)));
+            Vec::new()
+        }
     }
+}
 
-    let mut all_tcp_connections: Vec<Connection> = Vec::new();
-    for entry in tcp {
 
-        // process the remote-address and remote-port by spliting them at ":"
-        let (_, local_port) = string_utils::get_address_parts(&format!("{}", entry.local_address));
-        let (remote_address, remote_port) = string_utils::get_address_parts(&format!("{}", entry.remote_address));
-        let state = format!("{:?}", entry.state).to_ascii_lowercase();
-        
-        // check if there is no program/pid information
-        let program: String;
-        let pid: String;
-        if let Some(stat) = all_processes.get(&entry.inode) {
-            program = stat.comm.to_string();
-            pid = stat.pid.to_string();
-        } else {
-            program = "-".to_string();
-            pid = "-".to_string();
-        }
+/// Reads a UDP-formatted socket table (e.g. `/proc/net/udplite`) which isn't covered by the "procfs" crate.
+/// 
+/// # Arguments
+/// * `path`: The path of the socket table.
+/// 
+/// # Returns
+/// The entries of the table or `None` if the table doesn't exist, e.g. because the protocol isn't supported by the kernel.
+fn read_udp_formatted_table(path: &str) -> Option<Vec<procfs::net::UdpNetEntry>> {
+    let Ok(file) = File::open(path) else {
+        debug!("Skipped {}, it doesn't exist", path);
+        return None;
+    };
+    let entries: Vec<procfs::net::UdpNetEntry> = procfs::net::read_udp_table(BufReader::new(file)).ok()?;
+    debug!("Read {} socket(s) from {}", entries.len(), path);
+    Some(entries)
+}
 
-        let address_type: address_checkers::IPType = address_checkers::check_address_type(&remote_address);
 
-        let mut connection: Connection = Connection {
-            proto: "tcp".to_string(),
-            local_port,
-            remote_address: remote_address.to_string(),
-            remote_port,
-            program,
-            pid,
-            state,
-            address_type,
-            abuse_score: None
+/// Adds the amount of dropped packets to the sockets of UDP-formatted socket tables, i.e. the last column of the tables,
+/// which the "procfs" crate doesn't read. Packets are dropped e.g. if the receive buffer of a socket is full.
+/// 
+/// # Arguments
+/// * `entries`: The sockets read from the tables.
+/// * `paths`: The paths of the socket tables, tables which don't exist are skipped.
+/// 
+/// # Returns
+/// None
+fn add_drops(entries: &mut [SocketEntry], paths: &[&str]) {
+    let mut drops: HashMap<u64, u64> = HashMap::new();
+    for table in paths.iter().filter_map(|path| fs::read_to_string(path).ok()) {
+        // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode ref pointer drops
+        drops.extend(table.lines().skip(1).filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            Some((fields.get(9)?.parse::<u64>().ok()?, fields.get(12)?.parse::<u64>().ok()?))
+        }));
+    }
+    for entry in entries.iter_mut() {
+        entry.drops = drops.get(&entry.inode).copied();
+    }
+}
+
+
+/// Converts the state of an SCTP association (`enum sctp_state` of the kernel) to the closest TCP state.
+/// 
+/// # Arguments
+/// * `state`: The number of the state.
+/// 
+/// # Returns
+/// The state or `None` if the number is unknown.
+fn get_sctp_association_state(state: u8) -> Option<State> {
+    match state {
+        0 => Some(State::Close),
+        1 | 2 => Some(State::SynSent),
+        3 => Some(State::Established),
+        4 | 5 => Some(State::FinWait1),
+        6 => Some(State::CloseWait),
+        7 => Some(State::LastAck),
+        _ => None
+    }
+}
+
+
+/// Parses the addresses an SCTP socket is bound or connected to, as listed in `/proc/net/sctp`.
+/// 
+/// # Arguments
+/// * `fields`: The fields following the ports of an entry, the primary address is marked with a "*".
+/// 
+/// # Returns
+/// The primary (or else the first) address of the leading addresses, `None` if there are none.
+fn parse_sctp_addresses<'a>(fields: impl Iterator<Item = &'a str>) -> Option<IpAddr> {
+    let addresses: Vec<(bool, IpAddr)> = fields.map_while(|field| {
+        let (primary, field) = match field.strip_prefix('*') {
+            Some(field) => (true, field),
+            None => (false, field)
         };
+        field.parse().ok().map(|address| (primary, address))
+    }).collect();
+    addresses.iter().find(|(primary, _)| *primary).or(addresses.first()).map(|(_, address)| *address)
+}
 
-        // check if connection should be filtered out
-        let filter_connection: bool = filter_out_connection(&connection, filter_options);
-        if filter_connection {
-            continue;
+
+/// Parses the SCTP associations (i.e. connections) of `/proc/net/sctp/assocs`.
+/// 
+/// # Arguments
+/// * `table`: The content of the table.
+/// 
+/// # Returns
+/// The associations as a `SocketEntry` struct in a vector, malformed lines are skipped.
+fn parse_sctp_associations(table: &str) -> Vec<SocketEntry> {
+    table.lines().skip(1).filter_map(|line| {
+        // ASSOC SOCK STY SST ST HBKT ASSOC-ID TX_QUEUE RX_QUEUE UID INODE LPORT RPORT LADDRS <-> RADDRS ...
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let separator: usize = fields.iter().position(|field| *field == "<->")?;
+        let local_address: IpAddr = parse_sctp_addresses(fields.get(13..separator)?.iter().copied())?;
+        let remote_address: IpAddr = parse_sctp_addresses(fields.get(separator + 1..)?.iter().copied())?;
+        Some(SocketEntry {
+            family: Family::of(&local_address),
+            local_address: SocketAddr::new(local_address, fields.get(11)?.parse().ok()?),
+            remote_address: SocketAddr::new(remote_address, fields.get(12)?.parse().ok()?),
+            state: get_sctp_association_state(fields.get(4)?.parse().ok()?)?,
+            uid: fields.get(9)?.parse().ok()?,
+            inode: fields.get(10)?.parse().ok()?,
+            drops: None
+        })
+    }).collect()
+}
+
+
+/// Parses the SCTP endpoints (i.e. bound sockets) of `/proc/net/sctp/eps`.
+/// 
+/// # Arguments
+/// * `table`: The content of the table.
+/// 
+/// # Returns
+/// The endpoints as a `SocketEntry` struct in a vector, malformed lines are skipped.
+fn parse_sctp_endpoints(table: &str) -> Vec<SocketEntry> {
+    table.lines().skip(1).filter_map(|line| {
+        // ENDPT SOCK STY SST HBKT LPORT UID INODE LADDRS
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let local_address: IpAddr = parse_sctp_addresses(fields.get(8..)?.iter().copied())?;
+        let remote_address: IpAddr = match local_address {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        // the socket states of SCTP are the ones of TCP
+        Some(SocketEntry {
+            family: Family::of(&local_address),
+            local_address: SocketAddr::new(local_address, fields.get(5)?.parse().ok()?),
+            remote_address: SocketAddr::new(remote_address, 0),
+            state: State::from(procfs::net::TcpState::from_u8(fields.get(3)?.parse().ok()?)?),
+            uid: fields.get(6)?.parse().ok()?,
+            inode: fields.get(7)?.parse().ok()?,
+            drops: None
+        })
+    }).collect()
+}
+
+
+/// Replaces IPv4-mapped IPv6 addresses of sockets by IPv4 addresses, e.g. "::ffff:65.9.95.5" by "65.9.95.5".
+/// 
+/// # Arguments
+/// * `entries`: The sockets.
+/// * `exclude_ipv6`: If `true` the sockets which are still IPv6 sockets afterwards are removed.
+/// 
+/// # Returns
+/// None
+fn collapse_v4mapped_entries(entries: &mut Vec<SocketEntry>, exclude_ipv6: bool) {
+    for entry in entries.iter_mut() {
+        entry.local_address.set_ip(entry.local_address.ip().to_canonical());
+        entry.remote_address.set_ip(entry.remote_address.ip().to_canonical());
+    }
+    if exclude_ipv6 {
+        entries.retain(|entry| entry.local_address.is_ipv4());
+    }
+}
+
+
+/// Replaces the IPv4-mapped IPv6 addresses of a connection by IPv4 addresses, e.g. "::ffff:65.9.95.5" by "65.9.95.5".
+/// 
+/// # Arguments
+/// * `connection`: The connection.
+/// 
+/// # Returns
+/// None
+pub fn collapse_v4mapped_addresses(connection: &mut Connection) {
+    connection.local_address = connection.local_address.to_canonical();
+    connection.remote_address = connection.remote_address.to_canonical();
+}
+
+
+/// Gets all sockets of a protocol using the "procfs" crate.
+/// 
+/// # Arguments
+/// * `proto`: The protocol, one of `PROTOCOLS`.
+/// * `exclude_ipv6`: If `true` only the IPv4 socket table is read.
+/// 
+/// # Returns
+/// All sockets of the protocol as a `SocketEntry` struct in a vector.
+fn get_socket_entries(proto: &str, exclude_ipv6: bool) -> Vec<SocketEntry> {
+    let tcp_entry = |entry: procfs::net::TcpNetEntry| SocketEntry {
+        family: Family::of(&entry.local_address.ip()),
+        local_address: entry.local_address,
+        remote_address: entry.remote_address,
+        state: State::from(entry.state),
+        uid: entry.uid,
+        inode: entry.inode,
+        drops: None
+    };
+    let udp_entry = |entry: procfs::net::UdpNetEntry| SocketEntry {
+        family: Family::of(&entry.local_address.ip()),
+        local_address: entry.local_address,
+        remote_address: entry.remote_address,
+        state: State::from(entry.state),
+        uid: entry.uid,
+        inode: entry.inode,
+        drops: None
+    };
+
+    let mut entries: Vec<SocketEntry> = Vec::new();
+    match proto {
+        "tcp" => {
+            entries.extend(read_socket_table("/proc/net/tcp", procfs::net::tcp()).into_iter().map(tcp_entry));
+            if !exclude_ipv6 {
+                entries.extend(read_socket_table("/proc/net/tcp6", procfs::net::tcp6()).into_iter().map(tcp_entry));
+            }
         }
-        
-        // if malicious-check is activated, get an abuse score from AbuseIPDB.com
-        if check_malicious {
-            connection.abuse_score = address_checkers::check_address_for_abuse(&remote_address, false).await.unwrap_or(Some(-1i64));
+        "udp" => {
+            entries.extend(read_socket_table("/proc/net/udp", procfs::net::udp()).into_iter().map(udp_entry));
+            if !exclude_ipv6 {
+                entries.extend(read_socket_table("/proc/net/udp6", procfs::net::udp6()).into_iter().map(udp_entry));
+            }
+            add_drops(&mut entries, &["/proc/net/udp", "/proc/net/udp6"]);
         }
-
-        all_tcp_connections.push(connection);
+        "udplite" => {
+            entries.extend(read_udp_formatted_table("/proc/net/udplite").unwrap_or_default().into_iter().map(udp_entry));
+            if !exclude_ipv6 {
+                entries.extend(read_udp_formatted_table("/proc/net/udplite6").unwrap_or_default().into_iter().map(udp_entry));
+            }
+            add_drops(&mut entries, &["/proc/net/udplite", "/proc/net/udplite6"]);
+        }
+        // the local port of a raw socket is the number of its IP protocol, e.g. 1 for ICMP
+        "raw" => {
+            entries.extend(read_udp_formatted_table("/proc/net/raw").unwrap_or_default().into_iter().map(udp_entry));
+            if !exclude_ipv6 {
+                entries.extend(read_udp_formatted_table("/proc/net/raw6").unwrap_or_default().into_iter().map(udp_entry));
+            }
+            add_drops(&mut entries, &["/proc/net/raw", "/proc/net/raw6"]);
+        }
+        // the SCTP tables only exist if the sctp kernel module is loaded
+        "sctp" => {
+            for (path, parse_table) in [("/proc/net/sctp/eps", parse_sctp_endpoints as fn(&str) -> Vec<SocketEntry>), ("/proc/net/sctp/assocs", parse_sctp_associations)] {
+                match fs::read_to_string(path) {
+                    Ok(table) => {
+                        let table_entries: Vec<SocketEntry> = parse_table(&table);
+                        debug!("Read {} socket(s) from {}", table_entries.len(), path);
+                        entries.extend(table_entries);
+                    }
+                    Err(_) => debug!("Skipped {}, the sctp kernel module isn't loaded", path),
+                }
+            }
+            if exclude_ipv6 {
+                entries.retain(|entry| entry.local_address.is_ipv4());
+            }
+        }
+        // DCCP sockets are only listed by sock_diag, which reports their states using the numbering of the TCP states
+        "dccp" => {
+            match netlink::get_dccp_sockets() {
+                Ok(sockets) => {
+                    debug!("Read {} socket(s) from sock_diag", sockets.len());
+                    entries.extend(sockets.into_iter().filter_map(|socket| Some(SocketEntry {
+                        family: Family::of(&socket.local_address.ip()),
+                        local_address: socket.local_address,
+                        remote_address: socket.remote_address,
+                        state: State::from(procfs::net::TcpState::from_u8(socket.state)?),
+                        uid: socket.uid,
+                        inode: socket.inode,
+                        drops: None
+                    })));
+                }
+                // the kernel doesn't know the protocol if the dccp_diag kernel module isn't loaded (or DCCP isn't supported at all)
+                Err(error) if error.raw_os_error() == Some(libc::ENOENT) => debug!("Skipped DCCP, the dccp_diag kernel module isn't loaded"),
+                Err(error) => string_utils::pretty_print_warning(&format!("Couldn't get the DCCP sockets from sock_diag: {}", error)),
+            }
+            if exclude_ipv6 {
+                entries.retain(|entry| entry.local_address.is_ipv4());
+            }
+        }
+        _ => { }
     }
+    entries
+}
+
+
+/// Gets the accept queue lengths and backlogs of the TCP listeners, which only the `sock_diag` interface reports.
+/// If it can't be queried the listeners don't have them, which isn't considered an error.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The accept queue lengths and backlogs by the inodes of the listeners.
+fn get_listen_queues() -> HashMap<u64, (u32, u32)> {
+    netlink::get_tcp_listen_queues().unwrap_or_else(|error| {
+        debug!("Couldn't get the accept queues of the listeners from sock_diag: {}", error);
+        HashMap::new()
+    })
+}
+
 
-    all_tcp_connections
+/// Finds the TCP listeners which are bound to the same address and port, since the kernel only allows this with `SO_REUSEPORT`
+/// (e.g. for load-balanced worker pools). UDP sockets aren't grouped, since they can also share a port using `SO_REUSEADDR`.
+/// The groups are found before the connections are filtered and numbered from 1 by their address and port,
+/// so that a group keeps its number regardless of the filters, as long as the listeners before it exist.
+/// 
+/// # Arguments
+/// * `entries`: The socket entries of the TCP table.
+/// 
+/// # Returns
+/// The numbers of the groups by their local addresses, only containing addresses shared by several listeners.
+fn get_reuseport_groups(entries: &[SocketEntry]) -> HashMap<SocketAddr, u32> {
+    let mut listeners: BTreeMap<SocketAddr, usize> = BTreeMap::new();
+    for entry in entries.iter().filter(|entry| entry.state == State::Listen) {
+        *listeners.entry(entry.local_address).or_default() += 1;
+    }
+    listeners.into_iter()
+        .filter(|(_, amount)| *amount > 1)
+        .zip(1..)
+        .map(|((local_address, _), group)| (local_address, group))
+        .collect()
 }
 
 
-/// Gets all currently open UDP connections using the "procfs" crate and processes them.
-/// ###### TODO: combine with the `get_tcp_connections` function if possible.
+/// Processes the socket entries of a protocol into connections.
 /// 
 /// # Arguments
+/// * `proto`: The protocol, one of `PROTOCOLS`.
+/// * `entries`: The socket entries of the protocol.
 /// * `all_processes`: A map of all running processes on the system.
+/// * `user_names`: A map of UIDs to user names.
+/// * `listen_queues`: The accept queue lengths and backlogs of the listeners by their inodes.
+/// * `reuseport_groups`: The `SO_REUSEPORT` groups of the listeners by their local addresses.
 /// * `filter_options`: The filter options provided by the user.
-/// * `check_malicious`: If `true` the remote address is checked for abusiveness using the AbuseIPDB.com API.
 /// 
 /// # Returns
-/// All processed and filtered UDP connections as a `Connection` struct in a vector.
-async fn get_udp_connections(all_processes: &HashMap<u64, Stat>, filter_options: &FilterOptions, check_malicious: bool) -> Vec<Connection> {
-    let mut udp = procfs::net::udp().unwrap();
-    if !filter_options.exclude_ipv6 {
-        udp.extend(procfs::net::udp6().unwrap());
-    }
-
-    let mut all_udp_connections: Vec<Connection> = Vec::new();
-    for entry in udp {
+/// All processed and filtered connections of the protocol as a `Connection` struct in a vector.
+fn get_connections(
+    proto: &str, entries: Vec<SocketEntry>, all_processes: &HashMap<u64, Vec<Arc<Stat>>>, user_names: &HashMap<u32, String>,
+    listen_queues: &HashMap<u64, (u32, u32)>, reuseport_groups: &HashMap<SocketAddr, u32>, filter_options: &FilterOptions
+) -> Vec<Connection> {
+    let total: usize = entries.len();
+    let mut all_proto_connections: Vec<Connection> = Vec::new();
+    let system_uptime: Option<f64> = procfs::Uptime::new().ok().map(|uptime| uptime.uptime);
+    // many sockets share a parent (e.g. the workers of a server), so each parent is only looked up once
+    let mut parent_programs: HashMap<i32, String> = HashMap::new();
+    for entry in entries {
 
-        // process the remote-address and remote-port by spliting them at ":"
-        let (_, local_port) = string_utils::get_address_parts(&format!("{}", entry.local_address));
-        let (remote_address, remote_port) = string_utils::get_address_parts(&format!("{}", entry.remote_address));
-        let state = format!("{:?}", entry.state).to_ascii_lowercase();
-        
         // check if there is no program/pid information
         let program: String;
-        let pid: String;
-        if let Some(stat) = all_processes.get(&entry.inode) {
+        let pid: Option<i32>;
+        let ppid: Option<i32>;
+        let pprogram: String;
+        let tty: String;
+        let login_session: Option<u32>;
+        let proc_uptime: Option<u64>;
+        let owners: &[Arc<Stat>] = all_processes.get(&entry.inode).map_or(&[], Vec::as_slice);
+        if let Some(stat) = owners.first() {
             program = stat.comm.to_string();
-            pid = stat.pid.to_string();
+            pid = Some(stat.pid);
+            ppid = Some(stat.ppid);
+            pprogram = parent_programs.entry(stat.ppid)
+                .or_insert_with(|| get_process_parent(stat.ppid).map_or("-".to_string(), |(name, _)| name))
+                .to_string();
+            tty = get_tty_name(stat.tty_nr()).unwrap_or("-".to_string());
+            login_session = get_login_session(stat.pid);
+            proc_uptime = system_uptime.map(|system_uptime| get_process_uptime(stat, system_uptime));
         } else {
             program = "-".to_string();
-            pid = "-".to_string();
+            pid = None;
+            ppid = None;
+            pprogram = "-".to_string();
+            tty = "-".to_string();
+            login_session = None;
+            proc_uptime = None;
         }
 
-        let address_type: address_checkers::IPType = address_checkers::check_address_type(&remote_address);
+        let address_type: address_checkers::IPType = address_checkers::check_address_type(&entry.remote_address.ip());
+        let listen_queue: Option<&(u32, u32)> = listen_queues.get(&entry.inode);
 
-        let mut connection: Connection = Connection {
-            proto: "udp".to_string(),
-            local_port,
-            remote_address: remote_address.to_string(),
-            remote_port,
+        let connection: Connection = Connection {
+            proto: proto.to_string(),
+            family: entry.family,
+            local_address: entry.local_address.ip(),
+            local_port: entry.local_address.port(),
+            remote_address: entry.remote_address.ip(),
+            remote_port: entry.remote_address.port(),
             program,
             pid,
-            state,
+            ppid,
+            pprogram,
+            state: entry.state,
+            uid: entry.uid,
+            user: user_names.get(&entry.uid).map_or(entry.uid.to_string(), String::to_string),
+            tty,
+            login_session,
+            proc_uptime,
+            associated_pids: owners.iter().map(|stat| stat.pid).collect(),
+            accept_queue: listen_queue.map(|(accept_queue, _)| *accept_queue),
+            backlog: listen_queue.map(|(_, backlog)| *backlog),
+            drops: entry.drops,
+            tunnel: None,
+            container: None,
             address_type,
-            abuse_score: None
+            address_scope: address_checkers::get_address_scope(&entry.remote_address.ip()),
+            threat_score: None,
+            country: None,
+            city: None,
+            asn: None,
+            org: None,
+            remote_name: None,
+            local_service: None,
+            remote_service: None,
+            extra_fields: Vec::new(),
+            blocked: false,
+            reuseport_group: reuseport_groups.get(&entry.local_address).copied().filter(|_| entry.state == State::Listen),
+            dual_stack: false,
+            tx_rate: None,
+            rx_rate: None
         };
 
         // check if connection should be filtered out
@@ -216,41 +1218,320 @@ async fn get_udp_connections(all_processes: &HashMap<u64, Stat>, filter_options:
         if filter_connection {
             continue;
         }
-        
-        // if malicious-check is activated, get an abuse score from AbuseIPDB.com
-        if check_malicious {
-            connection.abuse_score = address_checkers::check_address_for_abuse(&remote_address, false).await.unwrap_or(Some(-1i64));
-        }
 
-        all_udp_connections.push(connection);
+        all_proto_connections.push(connection);
     }
 
-    all_udp_connections
+    let unknown_owners: usize = all_proto_connections.iter().filter(|connection| connection.pid.is_none()).count();
+    debug!("{} of {} {} socket(s) match the filters, the owners of {} of them are unknown", all_proto_connections.len(), total, proto, unknown_owners);
+    all_proto_connections
 }
 
- 
 
-/// Gets both TCP and UDP connections and combines them based on the `proto` filter option.
+/// Gets the connections of all protocols and combines them based on the `proto` filter option.
 /// 
 /// # Arguments
 /// * `filter_options`: The filter options provided by the user.
-/// * `check_malicious`: If `true` the remote address is checked for abusiveness using the AbuseIPDB.com API.
 /// 
 /// # Returns
-/// All processed and filtered TCP/UDP connections as a `Connection` struct in a vector.
-pub async fn get_all_connections(filter_options: &FilterOptions, check_malicious: bool) -> Vec<Connection> {
-    let all_processes: HashMap<u64, Stat> = get_processes();
+/// All processed and filtered TCP/UDP/UDP-Lite connections as a `Connection` struct in a vector or an error if the processes in `/proc` can't be listed.
+/// Socket tables which can't be read (e.g. `/proc/net/tcp6` if IPv6 is disabled) are skipped.
+pub fn get_all_connections(filter_options: &FilterOptions) -> Result<Vec<Connection>> {
+    get_all_connections_timed(filter_options, &mut Timings::default())
+}
 
-    match &filter_options.by_proto {
-        Some(filter_proto) if filter_proto == "tcp" => return get_tcp_connections(&all_processes, filter_options, check_malicious).await,
-        Some(filter_proto) if filter_proto == "udp" => return get_udp_connections(&all_processes, filter_options, check_malicious).await,
-        _ => { }
+
+/// Like `get_all_connections`, but also measures how long each phase took.
+/// 
+/// # Arguments
+/// * `filter_options`: The filter options provided by the user.
+/// * `timings`: The durations of the phases are added to it.
+/// 
+/// # Returns
+/// All processed and filtered connections or an error if the processes in `/proc` can't be listed.
+pub fn get_all_connections_timed(filter_options: &FilterOptions, timings: &mut Timings) -> Result<Vec<Connection>> {
+    let start: Instant = Instant::now();
+    let all_processes: HashMap<u64, Vec<Arc<Stat>>> = get_processes(filter_options)?;
+    let user_names: HashMap<u32, String> = get_user_names();
+    timings.process_scan += start.elapsed();
+
+    let mut all_connections: Vec<Connection> = Vec::new();
+    for proto in PROTOCOLS {
+        if !filter_options.reads_protocol(proto) {
+            continue;
+        }
+        let start: Instant = Instant::now();
+        // the IPv6 tables are needed to find the IPv4-mapped sockets, even if IPv6 sockets are excluded
+        let mut entries: Vec<SocketEntry> = get_socket_entries(proto, filter_options.exclude_ipv6 && !filter_options.collapse_v4mapped);
+        if filter_options.collapse_v4mapped {
+            collapse_v4mapped_entries(&mut entries, filter_options.exclude_ipv6);
+        }
+        let listen_queues: HashMap<u64, (u32, u32)> = if proto == "tcp" && entries.iter().any(|entry| entry.state == State::Listen) {
+            get_listen_queues()
+        } else {
+            HashMap::new()
+        };
+        let reuseport_groups: HashMap<SocketAddr, u32> = if proto == "tcp" { get_reuseport_groups(&entries) } else { HashMap::new() };
+        timings.socket_read += start.elapsed();
+
+        let start: Instant = Instant::now();
+        all_connections.extend(get_connections(proto, entries, &all_processes, &user_names, &listen_queues, &reuseport_groups, filter_options));
+        timings.filtering += start.elapsed();
     }
 
-    let mut all_connections = get_tcp_connections(&all_processes, filter_options, check_malicious).await;
-    let all_udp_connections = get_udp_connections(&all_processes, filter_options, check_malicious).await;
-    all_connections.extend(all_udp_connections);
+    Ok(all_connections)
+}
+
+
+/// The bytes every TCP connection has sent (and got acknowledged) and received so far, by its local and remote address, see `get_tcp_byte_counters`.
+#[cfg(feature = "throughput")]
+pub type ByteCounters = HashMap<(SocketAddr, SocketAddr), (u64, u64)>;
+
+
+/// Gets the amount of bytes every TCP connection has sent and received so far from the `sock_diag` interface of the kernel.
+/// Two samples of them give the throughput of the connections, see `annotate_throughput`.
+/// 
+/// # Arguments
+/// None
+/// 
+/// # Returns
+/// The byte counters of the connections or an error if the `sock_diag` interface can't be queried.
+#[cfg(feature = "throughput")]
+pub fn get_tcp_byte_counters() -> Result<ByteCounters> {
+    netlink::get_tcp_byte_counters().map_err(Error::Io)
+}
 
-    all_connections
+
+/// Sets the sent and received bytes per second of the TCP connections, based on two samples of their byte counters.
+/// Connections which don't appear in both samples (e.g. because they were opened in between) keep no throughput.
+/// 
+/// # Arguments
+/// * `connections`: The connections to annotate.
+/// * `previous_counters`: The byte counters of the earlier sample.
+/// * `current_counters`: The byte counters of the later sample.
+/// * `elapsed`: The time between the two samples.
+/// 
+/// # Returns
+/// None
+#[cfg(feature = "throughput")]
+pub fn annotate_throughput(connections: &mut [Connection], previous_counters: &ByteCounters, current_counters: &ByteCounters, elapsed: Duration) {
+    let seconds: f64 = elapsed.as_secs_f64().max(f64::EPSILON);
+    for connection in connections.iter_mut().filter(|connection| connection.proto == "tcp") {
+        let key: (SocketAddr, SocketAddr) = (
+            SocketAddr::new(connection.local_address.to_canonical(), connection.local_port),
+            SocketAddr::new(connection.remote_address.to_canonical(), connection.remote_port)
+        );
+        if let (Some((previous_sent, previous_received)), Some((sent, received))) = (previous_counters.get(&key), current_counters.get(&key)) {
+            connection.tx_rate = Some((sent.saturating_sub(*previous_sent) as f64 / seconds).round() as u64);
+            connection.rx_rate = Some((received.saturating_sub(*previous_received) as f64 / seconds).round() as u64);
+        }
+    }
+}
+
+
+/// Checks if a socket accepts traffic from other sockets, i.e. if it's a TCP (or SCTP or DCCP) listener or an unconnected UDP socket.
+/// 
+/// # Arguments
+/// * `connection`: The connection of the socket.
+/// 
+/// # Returns
+/// `true` if the socket is listening.
+pub fn is_listening(connection: &Connection) -> bool {
+    match connection.proto.as_str() {
+        "udp" | "udplite" => connection.state == State::Close && connection.remote_address.is_unspecified(),
+        _ => connection.state == State::Listen,
+    }
 }
 
+
+/// Merges the IPv4 and IPv6 listeners of the same program on the same port and protocol into one connection, marked as `dual_stack`.
+/// Only listeners bound to the same kind of address are merged, i.e. `0.0.0.0` with `[::]` and `127.0.0.1` with `[::1]`.
+/// The IPv4 listener is kept in place of both.
+/// 
+/// # Arguments
+/// * `connections`: The connections.
+/// 
+/// # Returns
+/// The connections with the IPv6 listeners removed whose IPv4 counterparts are marked.
+pub fn merge_dual_stack_listeners(connections: Vec<Connection>) -> Vec<Connection> {
+    let bind_kind = |address: &IpAddr| if address.is_unspecified() { Some(true) } else if address.is_loopback() { Some(false) } else { None };
+    let listener_key = |connection: &Connection| -> Option<(String, Option<i32>, u16, bool)> {
+        if !is_listening(connection) {
+            return None;
+        }
+        Some((connection.proto.clone(), connection.pid, connection.local_port, bind_kind(&connection.local_address)?))
+    };
+
+    let ipv4_listeners: HashSet<(String, Option<i32>, u16, bool)> = connections.iter()
+        .filter(|connection| connection.local_address.is_ipv4())
+        .filter_map(listener_key)
+        .collect();
+    let mut merged_listeners: HashSet<(String, Option<i32>, u16, bool)> = HashSet::new();
+    let mut connections: Vec<Connection> = connections.into_iter()
+        .filter(|connection| {
+            let Some(key) = listener_key(connection).filter(|key| connection.local_address.is_ipv6() && ipv4_listeners.contains(key)) else {
+                return true;
+            };
+            merged_listeners.insert(key);
+            false
+        })
+        .collect();
+    for connection in connections.iter_mut().filter(|connection| connection.local_address.is_ipv4()) {
+        connection.dual_stack = listener_key(connection).is_some_and(|key| merged_listeners.contains(&key));
+    }
+    connections
+}
+
+
+/// Gets a short ID of a connection derived from its protocol, addresses and ports, which stays the same across runs (and somo versions).
+/// It is the FNV-1a hash of the 5-tuple, so that diffs of the output and follow-up commands like `somo kill --id` can reference a connection.
+/// 
+/// # Arguments
+/// * `connection`: The connection.
+/// 
+/// # Example
+/// ```
+/// use somo::connections::get_connection_id;
+/// use somo::FixtureSource;
+/// 
+/// let fixture = FixtureSource::from_json(r#"[{
+///     "proto": "tcp", "family": "ipv4", "local_address": "0.0.0.0", "local_port": "22", "remote_address": "0.0.0.0", "remote_port": "0",
+///     "program": "sshd", "pid": "812", "ppid": "1", "pprogram": "systemd", "state": "listen", "uid": 0, "user": "root", "tty": "-",
+///     "address_type": "unspecified", "blocked": false
+/// }]"#)?;
+/// let connections = somo::ConnectionSource::get_connections(&fixture, &somo::FilterOptions::new())?;
+/// assert_eq!(get_connection_id(&connections[0]).len(), 8);
+/// # Ok::<(), somo::Error>(())
+/// ```
+/// 
+/// # Returns
+/// The ID as 8 hexadecimal digits.
+pub fn get_connection_id(connection: &Connection) -> String {
+    let five_tuple: String = format!(
+        "{} {} {} {} {}",
+        connection.proto, connection.local_address, connection.local_port, connection.remote_address, connection.remote_port
+    );
+    let hash: u64 = five_tuple.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{:08x}", (hash >> 32) as u32 ^ hash as u32)
+}
+
+
+/// Finds the first port of a range which isn't used as the local port of any socket.
+/// 
+/// # Arguments
+/// * `start`: The first port of the range.
+/// * `end`: The last port of the range (inclusive).
+/// * `proto`: If set, only sockets of this protocol are considered.
+/// 
+/// # Returns
+/// The first unused port, `None` if every port of the range is used or an error if the sockets can't be read.
+pub fn get_free_port(start: u16, end: u16, proto: Option<String>) -> Result<Option<u16>> {
+    let filter_options: FilterOptions = FilterOptions { by_proto: proto, skip_processes: true, ..Default::default() };
+    let used_ports: Vec<u16> = get_all_connections(&filter_options)?.into_iter().map(|connection| connection.local_port).collect();
+
+    Ok((start..=end).find(|port| !used_ports.contains(port)))
+}
+
+
+/// Closes a TCP connection without killing its process (like `ss -K`), the process gets an error on its next use of the socket.
+/// This requires root (`CAP_NET_ADMIN`) and a kernel built with `CONFIG_INET_DIAG_DESTROY`.
+/// 
+/// # Arguments
+/// * `connection`: The connection to close.
+/// 
+/// # Returns
+/// An error if the connection isn't a TCP connection or can't be closed.
+pub fn close_socket(connection: &Connection) -> Result<()> {
+    if connection.proto != "tcp" {
+        return Err(Error::Io(std::io::Error::new(std::io::ErrorKind::Unsupported, "only TCP sockets can be closed")));
+    }
+    // the kernel identifies IPv6 sockets by their IPv6 addresses, even if they were collapsed to IPv4 addresses
+    let socket_address = |address: IpAddr| match (connection.family, address) {
+        (Family::Ipv6, IpAddr::V4(address)) => IpAddr::V6(address.to_ipv6_mapped()),
+        _ => address,
+    };
+    let local_address: SocketAddr = SocketAddr::new(socket_address(connection.local_address), connection.local_port);
+    let remote_address: SocketAddr = SocketAddr::new(socket_address(connection.remote_address), connection.remote_port);
+    Ok(netlink::destroy_tcp_socket(&local_address, &remote_address)?)
+}
+
+
+/// Checks if the `sock_diag` netlink interface, used to close sockets and list vsock sockets, can be opened.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// `true` if a `sock_diag` socket can be created.
+pub fn is_sock_diag_available() -> bool {
+    netlink::SockDiag::open().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::ConnectionFixture;
+
+    #[test]
+    fn test_parse_sctp_tables() {
+        let endpoints = parse_sctp_endpoints(concat!(
+            " ENDPT     SOCK   STY SST HBKT LPORT   UID INODE LADDRS\n",
+            "       0        0 2   10  40   36412      0 52371 0.0.0.0 \n",
+        ));
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].local_address, "0.0.0.0:36412".parse().unwrap());
+        assert_eq!(endpoints[0].state, State::Listen);
+        assert_eq!(endpoints[0].inode, 52371);
+
+        let associations = parse_sctp_associations(concat!(
+            " ASSOC     SOCK   STY SST ST  HBKT ASSOC-ID TX_QUEUE RX_QUEUE UID INODE LPORT RPORT LADDRS <-> RADDRS HBINT INS OUTS MAXRT T1X T2X RTXC wmema wmemq sndbuf rcvbuf\n",
+            "       0        0 2   1   3   0       2        0        0    1000 52400 36412 38412  10.0.0.2 <-> 10.0.1.7 *10.0.0.7 \t 30000    10    10   10    0    0        0        1        0   212992   212992\n",
+            "malformed\n",
+        ));
+        assert_eq!(associations.len(), 1);
+        assert_eq!(associations[0].local_address, "10.0.0.2:36412".parse().unwrap());
+        assert_eq!(associations[0].remote_address, "10.0.0.7:38412".parse().unwrap());
+        assert_eq!(associations[0].state, State::Established);
+        assert_eq!(associations[0].uid, 1000);
+    }
+
+    fn listener(local_address: &str, pid: i32, program: &str) -> Connection {
+        ConnectionFixture::listener(local_address, 8080).program(program, pid).build()
+    }
+
+    #[test]
+    fn test_merge_dual_stack_listeners() {
+        // the IPv6 listener of the same program is merged into the IPv4 one, also for loopback addresses
+        let merged: Vec<Connection> = merge_dual_stack_listeners(vec![listener("0.0.0.0", 812, "nginx"), listener("[::]", 812, "nginx")]);
+        assert_eq!(merged.len(), 1);
+        assert!(merged[0].local_address.is_ipv4() && merged[0].dual_stack);
+        let merged: Vec<Connection> = merge_dual_stack_listeners(vec![listener("[::1]", 812, "nginx"), listener("127.0.0.1", 812, "nginx")]);
+        assert_eq!(merged.len(), 1);
+        assert!(merged[0].local_address.is_ipv4() && merged[0].dual_stack);
+
+        // listeners of different programs or bound to different kinds of addresses stay separate
+        let merged: Vec<Connection> = merge_dual_stack_listeners(vec![listener("0.0.0.0", 812, "nginx"), listener("[::]", 900, "caddy")]);
+        assert_eq!(merged.len(), 2);
+        assert!(merged.iter().all(|connection| !connection.dual_stack));
+        let merged: Vec<Connection> = merge_dual_stack_listeners(vec![listener("0.0.0.0", 812, "nginx"), listener("[::1]", 812, "nginx")]);
+        assert_eq!(merged.len(), 2);
+
+        // an IPv4-mapped listener is only reachable using IPv4, so it isn't the IPv6 half of a dual-stack pair
+        let merged: Vec<Connection> = merge_dual_stack_listeners(vec![listener("0.0.0.0", 812, "nginx"), listener("[::ffff:0.0.0.0]", 812, "nginx")]);
+        assert_eq!(merged.len(), 2);
+        assert!(merged.iter().all(|connection| !connection.dual_stack));
+    }
+
+    #[cfg(feature = "throughput")]
+    #[test]
+    fn test_annotate_throughput() {
+        let mut connections: Vec<Connection> = vec![listener("[::ffff:10.0.0.2]", 812, "nginx"), listener("0.0.0.0", 900, "caddy")];
+        let key: (SocketAddr, SocketAddr) = ("10.0.0.2:8080".parse().unwrap(), "[::]:0".parse().unwrap());
+        let previous_counters: ByteCounters = HashMap::from([(key, (1000, 0))]);
+        let current_counters: ByteCounters = HashMap::from([(key, (5000, 2048))]);
+        annotate_throughput(&mut connections, &previous_counters, &current_counters, Duration::from_secs(2));
+        // IPv4-mapped addresses match the IPv4 addresses reported by sock_diag, connections missing in a sample have no throughput
+        assert_eq!((connections[0].tx_rate, connections[0].rx_rate), (Some(2000), Some(1024)));
+        assert_eq!((connections[1].tx_rate, connections[1].rx_rate), (None, None));
+    }
+}
@@ -0,0 +1,118 @@
+use log::debug;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use crate::connections;
+
+/// The socket of the Docker API used if `$DOCKER_HOST` isn't set to a Unix socket.
+static DEFAULT_DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// The name of the process which listens on the published ports of containers.
+static DOCKER_PROXY: &str = "docker-proxy";
+
+/// Represents a port mapping of a container as returned by the Docker API.
+#[derive(Debug, Deserialize)]
+struct Port {
+    #[serde(rename = "PrivatePort")]
+    private_port: u16,
+    #[serde(rename = "PublicPort")]
+    public_port: Option<u16>,
+    #[serde(rename = "Type")]
+    proto: String
+}
+
+/// Represents a running container as returned by the Docker API.
+#[derive(Debug, Deserialize)]
+struct Container {
+    #[serde(rename = "Names", default)]
+    names: Vec<String>,
+    #[serde(rename = "Ports", default)]
+    ports: Vec<Port>
+}
+
+
+/// Gets the path of the Docker API socket, respecting `$DOCKER_HOST` if it points to a Unix socket.
+pub fn get_docker_socket() -> Option<String> {
+    match env::var("DOCKER_HOST") {
+        Ok(host) if !host.is_empty() => host.strip_prefix("unix://").map(String::from),
+        _ => Some(DEFAULT_DOCKER_SOCKET.to_string()),
+    }
+}
+
+
+/// Gets the running containers from the Docker API. HTTP/1.0 is used, so the response ends with the connection and isn't chunked.
+///
+/// # Arguments
+/// * `socket`: The path of the Docker API socket.
+///
+/// # Returns
+/// The containers or an error if the API isn't reachable, e.g. if the user isn't allowed to use it.
+fn get_containers(socket: &str) -> io::Result<Vec<Container>> {
+    let mut stream: UnixStream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    stream.write_all(b"GET /containers/json HTTP/1.0\r\nHost: docker\r\n\r\n")?;
+    let mut response: String = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let (head, body) = response.split_once("\r\n\r\n").ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP response"))?;
+    let status: &str = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(io::Error::other(format!("unexpected response '{}'", status)));
+    }
+    serde_json::from_str(body).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+
+/// Maps the published ports of the containers to their names and target ports.
+///
+/// # Arguments
+/// * `containers`: The running containers.
+///
+/// # Returns
+/// The container name and target port, e.g. "webapp:8080", by the published port and its protocol.
+fn get_published_ports(containers: &[Container]) -> HashMap<(u16, String), String> {
+    let mut published_ports: HashMap<(u16, String), String> = HashMap::new();
+    for container in containers {
+        let name: &str = container.names.first().map(|name| name.trim_start_matches('/')).unwrap_or("-");
+        for port in &container.ports {
+            if let Some(public_port) = port.public_port {
+                published_ports.insert((public_port, port.proto.clone()), format!("{}:{}", name, port.private_port));
+            }
+        }
+    }
+    published_ports
+}
+
+
+/// Marks the sockets of `docker-proxy` with the container and target port of the published port they forward to, e.g. "webapp:8080".
+/// The Docker API is only queried if there are such sockets, if it isn't reachable the sockets stay unmarked.
+///
+/// # Arguments
+/// * `connections`: The connections to annotate.
+///
+/// # Returns
+/// None
+pub fn annotate_containers(connections: &mut [connections::Connection]) {
+    if !connections.iter().any(|connection| connection.program == DOCKER_PROXY) {
+        return;
+    }
+    let Some(socket) = get_docker_socket() else {
+        debug!("Skipped resolving the docker-proxy ports, $DOCKER_HOST isn't a Unix socket");
+        return;
+    };
+    let published_ports: HashMap<(u16, String), String> = match get_containers(&socket) {
+        Ok(containers) => get_published_ports(&containers),
+        Err(error) => {
+            debug!("Couldn't query the Docker API at {}: {}", socket, error);
+            return;
+        }
+    };
+
+    for connection in connections.iter_mut().filter(|connection| connection.program == DOCKER_PROXY) {
+        connection.container = published_ports.get(&(connection.local_port, connection.proto.clone())).cloned();
+    }
+}
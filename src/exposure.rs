@@ -0,0 +1,66 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::connections;
+use crate::string_utils;
+
+/// Represents from where a listening socket can be reached, ordered from the least to the most exposed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BindScope {
+    /// Bound to a loopback address, only reachable from this host
+    Loopback,
+    /// Bound to the address of a specific interface, reachable from the networks of that interface
+    Lan,
+    /// Bound to `0.0.0.0` or `[::]`, reachable on every interface
+    AllInterfaces,
+}
+
+impl fmt::Display for BindScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BindScope::Loopback => "loopback-only",
+            BindScope::Lan => "LAN",
+            BindScope::AllInterfaces => "all interfaces",
+        })
+    }
+}
+
+
+/// Gets the scope a socket is bound to by its local address, IPv4-mapped addresses are treated like IPv4 addresses.
+///
+/// # Arguments
+/// * `local_address`: The local address of the socket.
+///
+/// # Returns
+/// The scope.
+pub fn get_bind_scope(local_address: &IpAddr) -> BindScope {
+    let address: IpAddr = string_utils::normalize_address(local_address);
+    if address.is_unspecified() {
+        BindScope::AllInterfaces
+    } else if address.is_loopback() {
+        BindScope::Loopback
+    } else {
+        BindScope::Lan
+    }
+}
+
+
+/// Gets the listening sockets with their bind scopes, the most exposed first and then by port.
+///
+/// # Arguments
+/// * `all_connections`: The connections to pick the listening sockets from.
+///
+/// # Returns
+/// The scopes and listening sockets.
+pub fn get_exposed_sockets(all_connections: &[connections::Connection]) -> Vec<(BindScope, &connections::Connection)> {
+    let mut exposed_sockets: Vec<(BindScope, &connections::Connection)> = all_connections.iter()
+        .filter(|connection| connections::is_listening(connection))
+        .map(|connection| (get_bind_scope(&connection.local_address), connection))
+        .collect();
+    exposed_sockets.sort_by(|(scope_a, connection_a), (scope_b, connection_b)| {
+        scope_b.cmp(scope_a)
+            .then(connection_a.local_port.cmp(&connection_b.local_port))
+            .then(connection_a.proto.cmp(&connection_b.proto))
+    });
+    exposed_sockets
+}
@@ -0,0 +1,169 @@
+use clap::ValueEnum;
+use std::net::IpAddr;
+
+use crate::connections::{self, Connection};
+
+/// Represents a firewall for which `somo suggest-block` generates rules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Firewall {
+    Nftables,
+    Iptables,
+    Ufw,
+}
+
+/// Represents what to block: traffic from and to a remote address, to a local port or both combined,
+/// or the traffic with a service of a remote address, i.e. a remote port.
+#[derive(Debug)]
+pub struct BlockTarget {
+    pub address: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub remote_port: Option<u16>,
+    pub proto: String
+}
+
+impl BlockTarget {
+    /// Gets the target blocking a connection, e.g. a row of the previous output. Its remote address is blocked on the local port
+    /// if the connection is incoming (i.e. a client connected to a local service) and on its remote port otherwise.
+    /// Listeners have no remote address, so their local port is closed instead.
+    ///
+    /// # Arguments
+    /// * `connection`: The connection to block.
+    /// * `incoming`: If `true` the connection was accepted by a local listener.
+    ///
+    /// # Returns
+    /// The target, which blocks the whole remote address if the protocol isn't TCP or UDP.
+    pub fn from_connection(connection: &Connection, incoming: bool) -> BlockTarget {
+        let proto: String = connection.proto.to_string();
+        if connections::is_listening(connection) || connection.remote_address.is_unspecified() {
+            return BlockTarget { address: None, port: Some(connection.local_port), remote_port: None, proto };
+        }
+        let address: Option<IpAddr> = Some(connection.remote_address.to_canonical());
+        if proto != "tcp" && proto != "udp" {
+            return BlockTarget { address, port: None, remote_port: None, proto };
+        }
+        if incoming {
+            BlockTarget { address, port: Some(connection.local_port), remote_port: None, proto }
+        } else {
+            BlockTarget { address, port: None, remote_port: Some(connection.remote_port), proto }
+        }
+    }
+}
+
+
+/// Generates the nftables rules for a target, assuming the common `inet filter` table with `input` and `output` chains.
+fn get_nftables_rules(target: &BlockTarget) -> Vec<String> {
+    let family = |address: &IpAddr| if address.is_ipv4() { "ip" } else { "ip6" };
+    if let (Some(address), Some(remote_port)) = (&target.address, target.remote_port) {
+        return vec![
+            format!("nft add rule inet filter output {} daddr {} {} dport {} drop", family(address), address, target.proto, remote_port),
+            format!("nft add rule inet filter input {} saddr {} {} sport {} drop", family(address), address, target.proto, remote_port),
+        ];
+    }
+    match (&target.address, target.port) {
+        (Some(address), None) => vec![
+            format!("nft add rule inet filter input {} saddr {} drop", family(address), address),
+            format!("nft add rule inet filter output {} daddr {} drop", family(address), address),
+        ],
+        (Some(address), Some(port)) => vec![
+            format!("nft add rule inet filter input {} saddr {} {} dport {} drop", family(address), address, target.proto, port),
+        ],
+        (None, Some(port)) => vec![
+            format!("nft add rule inet filter input {} dport {} drop", target.proto, port),
+        ],
+        (None, None) => Vec::new(),
+    }
+}
+
+
+/// Generates the iptables (or ip6tables for IPv6 addresses) rules for a target, inserted at the top of the chains.
+fn get_iptables_rules(target: &BlockTarget) -> Vec<String> {
+    let command = |address: &IpAddr| if address.is_ipv4() { "iptables" } else { "ip6tables" };
+    if let (Some(address), Some(remote_port)) = (&target.address, target.remote_port) {
+        return vec![
+            format!("{} -I OUTPUT -d {} -p {} --dport {} -j DROP", command(address), address, target.proto, remote_port),
+            format!("{} -I INPUT -s {} -p {} --sport {} -j DROP", command(address), address, target.proto, remote_port),
+        ];
+    }
+    match (&target.address, target.port) {
+        (Some(address), None) => vec![
+            format!("{} -I INPUT -s {} -j DROP", command(address), address),
+            format!("{} -I OUTPUT -d {} -j DROP", command(address), address),
+        ],
+        (Some(address), Some(port)) => vec![
+            format!("{} -I INPUT -s {} -p {} --dport {} -j DROP", command(address), address, target.proto, port),
+        ],
+        (None, Some(port)) => vec![
+            format!("iptables -I INPUT -p {} --dport {} -j DROP", target.proto, port),
+            format!("ip6tables -I INPUT -p {} --dport {} -j DROP", target.proto, port),
+        ],
+        (None, None) => Vec::new(),
+    }
+}
+
+
+/// Generates the ufw rules for a target, inserted before the existing (allow) rules.
+fn get_ufw_rules(target: &BlockTarget) -> Vec<String> {
+    if let (Some(address), Some(remote_port)) = (&target.address, target.remote_port) {
+        return vec![
+            format!("ufw insert 1 deny out proto {} to {} port {}", target.proto, address, remote_port),
+            format!("ufw insert 1 deny proto {} from {} port {}", target.proto, address, remote_port),
+        ];
+    }
+    match (&target.address, target.port) {
+        (Some(address), None) => vec![
+            format!("ufw insert 1 deny from {}", address),
+            format!("ufw insert 1 deny out to {}", address),
+        ],
+        (Some(address), Some(port)) => vec![
+            format!("ufw insert 1 deny proto {} from {} to any port {}", target.proto, address, port),
+        ],
+        (None, Some(port)) => vec![
+            format!("ufw deny {}/{}", port, target.proto),
+        ],
+        (None, None) => Vec::new(),
+    }
+}
+
+
+/// Generates ready-to-paste firewall rules which block a remote address, close a local port, block an address on a port or block a remote service.
+///
+/// # Arguments
+/// * `target`: What to block.
+/// * `firewall`: The firewall to generate the rules for.
+///
+/// # Returns
+/// The commands adding the rules.
+pub fn get_block_rules(target: &BlockTarget, firewall: Firewall) -> Vec<String> {
+    match firewall {
+        Firewall::Nftables => get_nftables_rules(target),
+        Firewall::Iptables => get_iptables_rules(target),
+        Firewall::Ufw => get_ufw_rules(target),
+    }
+}
+
+
+/// Prints the firewall rules for a target, for all firewalls if none was chosen.
+///
+/// # Arguments
+/// * `target`: What to block.
+/// * `firewall`: The firewall to print the rules for, if not set the rules of every firewall are printed.
+///
+/// # Returns
+/// None
+pub fn print_block_rules(target: &BlockTarget, firewall: Option<Firewall>) {
+    let firewalls: Vec<Firewall> = match firewall {
+        Some(firewall) => vec![firewall],
+        None => Firewall::value_variants().to_vec(),
+    };
+    for (idx, firewall) in firewalls.iter().enumerate() {
+        if firewalls.len() > 1 {
+            if idx > 0 {
+                sout!("");
+            }
+            sout!("# {}", firewall.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default());
+        }
+        for rule in get_block_rules(target, *firewall) {
+            sout!("{}", rule);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! A builder of connections shared by the tests of the library and the binary and by the benchmarks,
+//! included by each of them, so `crate::connections` etc. have to resolve in all of them.
+
+// every crate including this module only uses some of the setters
+#![allow(dead_code)]
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::address_checkers;
+use crate::connections::{Connection, Family, State};
+use crate::string_utils;
+
+/// Builds a connection, by default an established TCP connection of `curl` (PID 812) from 10.0.0.2:5000 to 10.0.0.7:443.
+pub struct ConnectionFixture {
+    connection: Connection
+}
+
+
+/// Parses an address of a fixture, e.g. "10.0.0.2" or "[::1]".
+fn parse_address(address: &str) -> IpAddr {
+    string_utils::parse_remote_address(address).unwrap_or_else(|| panic!("invalid fixture address '{}'", address))
+}
+
+
+impl ConnectionFixture {
+    /// Starts building the default connection.
+    pub fn new() -> Self {
+        let local_address: IpAddr = parse_address("10.0.0.2");
+        ConnectionFixture {
+            connection: Connection {
+                proto: "tcp".to_string(),
+                family: Family::of(&local_address),
+                local_address,
+                local_port: 5000,
+                remote_address: local_address,
+                remote_port: 0,
+                program: "curl".to_string(),
+                pid: Some(812),
+                ppid: Some(1),
+                pprogram: "systemd".to_string(),
+                state: State::Established,
+                uid: 0,
+                user: "root".to_string(),
+                tty: "-".to_string(),
+                login_session: None,
+                proc_uptime: None,
+                associated_pids: Vec::new(),
+                accept_queue: None,
+                backlog: None,
+                drops: None,
+                tunnel: None,
+                container: None,
+                address_type: address_checkers::IPType::Extern,
+                address_scope: address_checkers::AddressScope::Private,
+                threat_score: None,
+                country: None,
+                city: None,
+                asn: None,
+                org: None,
+                remote_name: None,
+                local_service: None,
+                remote_service: None,
+                extra_fields: Vec::new(),
+                blocked: false,
+                reuseport_group: None,
+                dual_stack: false,
+                tx_rate: None,
+                rx_rate: None
+            }
+        }.remote("10.0.0.7", 443)
+    }
+
+    /// Starts building a TCP listener, its remote address is the unspecified address of its family.
+    pub fn listener(local_address: &str, local_port: u16) -> Self {
+        let fixture: Self = Self::new().local(local_address, local_port).state(State::Listen);
+        let remote_address: IpAddr = match fixture.connection.family {
+            Family::Ipv4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Family::Ipv6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        fixture.remote(&remote_address.to_string(), 0)
+    }
+
+    /// Sets the protocol, e.g. "udp".
+    pub fn proto(mut self, proto: &str) -> Self {
+        self.connection.proto = proto.to_string();
+        self
+    }
+
+    /// Sets the local address and port, the family is derived from the address.
+    pub fn local(mut self, address: &str, port: u16) -> Self {
+        self.connection.local_address = parse_address(address);
+        self.connection.family = Family::of(&self.connection.local_address);
+        self.connection.local_port = port;
+        self
+    }
+
+    /// Sets the remote address and port, the address type and scope are derived from the address.
+    pub fn remote(mut self, address: &str, port: u16) -> Self {
+        self.connection.remote_address = parse_address(address);
+        self.connection.address_type = address_checkers::check_address_type(&self.connection.remote_address);
+        self.connection.address_scope = address_checkers::get_address_scope(&self.connection.remote_address);
+        self.connection.remote_port = port;
+        self
+    }
+
+    /// Sets the program owning the socket and its PID.
+    pub fn program(mut self, program: &str, pid: i32) -> Self {
+        self.connection.program = program.to_string();
+        self.connection.pid = Some(pid);
+        self
+    }
+
+    /// Sets the state of the socket.
+    pub fn state(mut self, state: State) -> Self {
+        self.connection.state = state;
+        self
+    }
+
+    /// Sets the owner of the socket.
+    pub fn user(mut self, uid: u32, user: &str) -> Self {
+        self.connection.uid = uid;
+        self.connection.user = user.to_string();
+        self
+    }
+
+    /// Finishes building the connection.
+    pub fn build(self) -> Connection {
+        self.connection
+    }
+}
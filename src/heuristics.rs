@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::connections;
+use crate::string_utils;
+
+/// Ports of databases and caches which usually shouldn't be reachable from other hosts.
+static DATABASE_PORTS: [(u16, &str); 9] = [
+    (1433, "MSSQL"),
+    (3306, "MySQL"),
+    (5432, "PostgreSQL"),
+    (5984, "CouchDB"),
+    (6379, "Redis"),
+    (9042, "Cassandra"),
+    (9200, "Elasticsearch"),
+    (11211, "Memcached"),
+    (27017, "MongoDB"),
+];
+
+/// Ports of services which transmit credentials in plain text.
+static PLAINTEXT_PORTS: [(u16, &str); 2] = [
+    (21, "FTP"),
+    (23, "Telnet"),
+];
+
+/// Ports of SMB/NetBIOS file sharing.
+static SMB_PORTS: [u16; 2] = [139, 445];
+
+/// Directories which are writable by every user and therefore shouldn't contain the executables of network services.
+static WORLD_WRITABLE_DIRECTORIES: [&str; 3] = ["/tmp/", "/var/tmp/", "/dev/shm/"];
+
+/// Represents a risky finding about a connection.
+#[derive(Debug)]
+pub struct Hint {
+    pub index: usize,
+    pub message: String
+}
+
+
+/// Checks if a connection is a TCP socket listening for incoming connections.
+fn is_listener(connection: &connections::Connection) -> bool {
+    connection.proto == "tcp" && connection.state == connections::State::Listen
+}
+
+
+/// Checks if a socket is bound to all interfaces, i.e. to `0.0.0.0` or `[::]`.
+fn is_bound_publicly(connection: &connections::Connection) -> bool {
+    connection.local_address.is_unspecified()
+}
+
+
+/// Checks a connection for well-known risky findings.
+///
+/// * Telnet or FTP listeners
+/// * databases bound to all interfaces
+/// * SMB bound to all interfaces
+/// * listeners whose executable is located in a world-writable directory like `/tmp`
+///
+/// # Arguments
+/// * `connection`: The connection to check.
+/// * `executable`: The path of the executable of the process owning the connection, if known.
+///
+/// # Returns
+/// A message for every finding.
+pub fn check_connection(connection: &connections::Connection, executable: Option<&Path>) -> Vec<String> {
+    let mut findings: Vec<String> = Vec::new();
+    if !is_listener(connection) {
+        return findings;
+    }
+
+    if let Some((port, service)) = PLAINTEXT_PORTS.iter().find(|(port, _)| *port == connection.local_port) {
+        findings.push(format!("{} listener on port {} transmits credentials unencrypted.", service, port));
+    }
+    if let Some((port, database)) = DATABASE_PORTS.iter().find(|(port, _)| *port == connection.local_port) {
+        if is_bound_publicly(connection) {
+            findings.push(format!("{} on port {} is bound to all interfaces ({}).", database, port, string_utils::format_address(&connection.local_address)));
+        }
+    }
+    if SMB_PORTS.contains(&connection.local_port) && is_bound_publicly(connection) {
+        findings.push(format!("SMB on port {} is exposed on all interfaces ({}).", connection.local_port, string_utils::format_address(&connection.local_address)));
+    }
+    if let Some(executable) = executable {
+        if WORLD_WRITABLE_DIRECTORIES.iter().any(|directory| executable.starts_with(directory)) {
+            findings.push(format!("Listener on port {} runs from a world-writable directory ({}).", connection.local_port, executable.display()));
+        }
+    }
+
+    findings
+}
+
+
+/// Gets the path of the executable of a process.
+///
+/// # Arguments
+/// * `pid`: The PID of the process, `None` if it is unknown.
+///
+/// # Returns
+/// The path of the executable or `None` if the PID is unknown or the executable can't be read (e.g. due to missing permissions).
+fn get_executable(pid: Option<i32>) -> Option<PathBuf> {
+    pid.and_then(|pid| fs::read_link(format!("/proc/{}/exe", pid)).ok())
+}
+
+
+/// Checks all connections for well-known risky findings.
+///
+/// # Arguments
+/// * `connections`: The connections to check, in the order they are displayed in the table.
+///
+/// # Returns
+/// A hint for every finding, referencing the (1-based) row index of the connection in the table.
+pub fn get_hints(connections: &[connections::Connection]) -> Vec<Hint> {
+    let mut hints: Vec<Hint> = Vec::new();
+    for (idx, connection) in connections.iter().enumerate() {
+        let executable = get_executable(connection.pid);
+        for message in check_connection(connection, executable.as_deref()) {
+            hints.push(Hint { index: idx + 1, message });
+        }
+    }
+    hints
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::ConnectionFixture;
+
+    fn listener(local_address: &str, local_port: u16) -> connections::Connection {
+        ConnectionFixture::listener(local_address, local_port).build()
+    }
+
+    #[test]
+    fn test_plaintext_listeners() {
+        assert_eq!(check_connection(&listener("127.0.0.1", 23), None).len(), 1);
+        assert_eq!(check_connection(&listener("0.0.0.0", 21), None).len(), 1);
+        assert!(check_connection(&listener("0.0.0.0", 22), None).is_empty());
+    }
+
+    #[test]
+    fn test_databases_bound_publicly() {
+        assert_eq!(check_connection(&listener("0.0.0.0", 5432), None).len(), 1);
+        assert_eq!(check_connection(&listener("[::]", 6379), None).len(), 1);
+        assert!(check_connection(&listener("127.0.0.1", 5432), None).is_empty());
+        assert!(check_connection(&listener("[::1]", 27017), None).is_empty());
+    }
+
+    #[test]
+    fn test_smb_bound_publicly() {
+        assert_eq!(check_connection(&listener("0.0.0.0", 445), None).len(), 1);
+        assert!(check_connection(&listener("192.168.0.2", 445), None).is_empty());
+    }
+
+    #[test]
+    fn test_listeners_from_world_writable_directories() {
+        let connection = listener("127.0.0.1", 8080);
+        assert_eq!(check_connection(&connection, Some(Path::new("/tmp/.x/miner"))).len(), 1);
+        assert_eq!(check_connection(&connection, Some(Path::new("/dev/shm/agent"))).len(), 1);
+        assert!(check_connection(&connection, Some(Path::new("/usr/bin/python3"))).is_empty());
+        assert!(check_connection(&connection, Some(Path::new("/tmpfoo/bin"))).is_empty());
+    }
+
+    #[test]
+    fn test_only_listeners_are_checked() {
+        let connection = ConnectionFixture::listener("0.0.0.0", 23).state(connections::State::Established).build();
+        assert!(check_connection(&connection, Some(Path::new("/tmp/telnetd"))).is_empty());
+
+        let connection = ConnectionFixture::listener("0.0.0.0", 5432).proto("udp").build();
+        assert!(check_connection(&connection, None).is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::time::SystemTime;
+
+use serde_json::{json, Map, Value};
+
+use crate::connections;
+use crate::string_utils;
+use crate::templates;
+
+/// The version of the JSON envelope, increased whenever existing keys change in an incompatible way.
+static ENVELOPE_VERSION: u32 = 1;
+
+
+/// Gets the name of the operating system from `/etc/os-release`, e.g. "Debian GNU/Linux 12 (bookworm)".
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The pretty name of the OS, or its name, or `None` if it isn't known.
+fn get_os_name() -> Option<String> {
+    let os_release: String = fs::read_to_string("/etc/os-release").or_else(|_| fs::read_to_string("/usr/lib/os-release")).ok()?;
+    let get_value = |key: &str| os_release.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        .map(|value| value.trim_matches('"').to_string());
+    get_value("PRETTY_NAME").or_else(|| get_value("NAME"))
+}
+
+
+/// Gets the metadata of the machine, so that output collected from many machines can be told apart:
+/// the hostname, the OS, the kernel version and the version of somo. Values which can't be read are `null`.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The metadata, e.g. `{"host": "web-01", "os": "Debian GNU/Linux 12 (bookworm)", "kernel": "6.1.0-18-amd64", "somo_version": "1.3.0"}`.
+pub fn get_machine_metadata() -> Map<String, Value> {
+    let kernel: Option<String> = fs::read_to_string("/proc/sys/kernel/osrelease").ok().map(|kernel| kernel.trim().to_string());
+    let mut metadata: Map<String, Value> = Map::new();
+    metadata.insert("host".to_string(), json!(string_utils::get_hostname()));
+    metadata.insert("os".to_string(), json!(get_os_name()));
+    metadata.insert("kernel".to_string(), json!(kernel));
+    metadata.insert("somo_version".to_string(), json!(env!("CARGO_PKG_VERSION")));
+    metadata
+}
+
+
+/// Converts the filters the connections were selected with into a JSON object, unset filters are `null`.
+///
+/// # Arguments
+/// * `filter_options`: The filters.
+///
+/// # Returns
+/// The JSON object, e.g. `{"proto": "tcp", "open": true, ...}`.
+fn filter_options_to_json(filter_options: &connections::FilterOptions) -> Value {
+    json!({
+        "proto": filter_options.by_proto,
+        "program": filter_options.by_program,
+        "pid": filter_options.by_pid,
+        "remote_address": filter_options.by_remote_address,
+        "remote_port": filter_options.by_remote_port,
+        "local_port": filter_options.by_local_port,
+        "open": filter_options.by_open,
+        "states": filter_options.by_states,
+        "uid": filter_options.by_uid,
+        "scope": filter_options.by_scope,
+        "exclude_ipv6": filter_options.exclude_ipv6,
+        "optional_protocols": filter_options.optional_protocols,
+    })
+}
+
+
+/// Prints the connections as a versioned JSON envelope, e.g. `{"version": 1, "generated_at": "...", "collected_at": "...", "host": "...", "os": "...", "kernel": "...", "somo_version": "...", "filters": {...}, "connections": [...]}`.
+/// The connections contain the fields listed by `somo fields`.
+///
+/// # Arguments
+/// * `all_connections`: The connections to print.
+/// * `filter_options`: The filters the connections were selected with.
+/// * `collected_at`: The time the connections were collected as an RFC 3339 timestamp.
+///
+/// # Returns
+/// None
+pub fn print_json_envelope(all_connections: &[connections::Connection], filter_options: &connections::FilterOptions, collected_at: &str) {
+    let mut envelope: Map<String, Value> = Map::new();
+    envelope.insert("version".to_string(), json!(ENVELOPE_VERSION));
+    envelope.insert("generated_at".to_string(), json!(humantime::format_rfc3339_seconds(SystemTime::now()).to_string()));
+    envelope.insert("collected_at".to_string(), json!(collected_at));
+    envelope.extend(get_machine_metadata());
+    envelope.insert("filters".to_string(), filter_options_to_json(filter_options));
+    envelope.insert("connections".to_string(), all_connections.iter().map(templates::connection_to_json).collect());
+    sout!("{}", serde_json::to_string_pretty(&envelope).unwrap_or_default());
+}
@@ -0,0 +1,37 @@
+//! Collects the TCP, UDP and UDP-Lite sockets of a Linux system from `/proc`, together with the processes owning them.
+//!
+//! The `somo` binary is built on top of this library. Using it as a dependency without the CLI-only
+//! dependencies (clap, inquire, termimad, ...) works by disabling the default features:
+//! ```toml
+//! somo = { version = "0.2", default-features = false }
+//! ```
+//!
+//! # Example
+//! ```no_run
+//! use somo::FilterOptions;
+//!
+//! let filter_options = FilterOptions::new().proto("tcp").open(true);
+//! for connection in somo::get_all_connections(&filter_options)? {
+//!     println!("{} listens on port {}", connection.program, connection.local_port);
+//! }
+//! # Ok::<(), somo::Error>(())
+//! ```
+
+#[macro_use]
+mod macros;
+pub mod address_checkers;
+pub mod address_lookups;
+pub mod connections;
+pub mod docker;
+#[cfg(test)]
+mod fixtures;
+pub mod mdns;
+mod netlink;
+pub mod services;
+pub mod sources;
+pub mod string_utils;
+pub mod tunnels;
+pub mod vsock;
+
+pub use connections::{get_all_connections, get_all_connections_timed, Connection, Error, FilterOptions, Result, Timings};
+pub use sources::{ConnectionSource, FixtureSource, ProcfsSource};
@@ -0,0 +1,22 @@
+/// Prints a line to stdout like `println!`, but exits quietly instead of panicking if stdout is closed,
+/// e.g. when the output is piped into `head`.
+macro_rules! sout {
+    ($($arg:tt)*) => {{
+        use std::io::Write;
+        if let Err(error) = writeln!(std::io::stdout(), $($arg)*) {
+            if error.kind() == std::io::ErrorKind::BrokenPipe {
+                std::process::exit(0);
+            }
+        }
+    }};
+}
+
+
+/// Prints a line to stderr like `eprintln!`, but ignores write errors instead of panicking, e.g. if stderr is closed.
+/// Used for diagnostics (errors, warnings) so that they never end up in the output piped into other programs.
+macro_rules! serr {
+    ($($arg:tt)*) => {{
+        use std::io::Write;
+        let _ = writeln!(std::io::stderr(), $($arg)*);
+    }};
+}
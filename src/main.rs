@@ -1,17 +1,146 @@
-mod connections;
-mod address_checkers;
-mod string_utils;
+#[macro_use]
+mod macros;
+use somo::{address_checkers, address_lookups, connections, docker, mdns, services, string_utils, tunnels, vsock};
+use somo::{ConnectionSource, FixtureSource, ProcfsSource};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 mod table;
 mod cli;
+mod columns;
+mod completions;
+mod config;
+mod exposure;
+mod firewall;
+#[cfg(test)]
+mod fixtures;
+mod heuristics;
+mod json_output;
+mod pager;
+mod record;
+mod snapshot;
+mod system_log;
+mod templates;
+mod version;
+mod watch;
+#[cfg(feature = "plugins")]
+mod plugins;
+#[cfg(feature = "throughput")]
+mod top;
 
 
 #[tokio::main]
 async fn main() {
 
-    let mut args: cli::FlagValues = cli::cli();
+    let args: cli::FlagValues = cli::cli();
+    string_utils::set_quiet(args.quiet);
+    string_utils::set_colors(cli::use_colors(args.color));
+    string_utils::set_ip_format(args.ip_format);
+
+    let table_options: table::TableOptions = table::TableOptions {
+        borders: if args.ascii { table::Borders::Ascii } else { table::Borders::Unicode },
+        summary: args.summary,
+        theme: cli::get_theme(&args.state_colors, args.color_rows, args.zebra),
+        width: args.width,
+        truncate: args.truncate,
+        no_truncate: args.no_truncate,
+        // within the groups of `--group-by`, the rows are in the order of `--group-sort` if it's set
+        sorted_by: args.group_sort.filter(|_| args.group_by.is_some()).or(args.sort).map(|key| (key.column, key.is_descending(args.reverse)))
+    };
+
+    if let Some(cli::Commands::FreePort { range, proto }) = args.command {
+        match connections::get_free_port(range.start, range.end, proto) {
+            Ok(Some(port)) => sout!("{}", port),
+            Ok(None) => {
+                string_utils::print_error("no_free_port", &format!("No free port between {} and {}.", range.start, range.end));
+                std::process::exit(1);
+            }
+            Err(error) => {
+                string_utils::print_error("connections", &format!("Couldn't read the sockets: {}", error));
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(cli::Commands::Version { json }) = args.command {
+        version::print_version(json);
+        return;
+    }
+    if let Some(cli::Commands::Lookup { query, proto }) = &args.command {
+        let override_path: Option<PathBuf> = config::get_services_override_path();
+        let services: services::Services = services::load_services(args.services_file.as_deref().map(Path::new), override_path.as_deref()).with_extended_ports();
+        let matches: Vec<(u16, &str, &str)> = services.lookup(query.trim()).into_iter()
+            .filter(|(_, service_proto, _)| proto.as_ref().is_none_or(|proto| proto == service_proto))
+            .collect();
+        if matches.is_empty() {
+            string_utils::print_error("unknown_service", &format!("No service is known for '{}'.", query));
+            std::process::exit(1);
+        }
+        for (port, proto, name) in matches {
+            sout!("{:<12}{}", format!("{}/{}", port, proto), name);
+        }
+        return;
+    }
+    if let Some(cli::Commands::Fields) = args.command {
+        templates::print_fields();
+        return;
+    }
+    if let Some(cli::Commands::SuggestBlock { ip, index, id, port, proto, firewall }) = &args.command {
+        // a connection of the previous output is resolved like `somo kill 7` or `somo kill --id`
+        let snapshot_connection: Option<Result<connections::Connection, String>> = match (index, id) {
+            (Some(index), _) => Some(snapshot::get_snapshot_connection(*index)),
+            (None, Some(id)) => Some(snapshot::get_snapshot_connection_by_id(id)),
+            (None, None) => None,
+        };
+        let target: firewall::BlockTarget = match snapshot_connection {
+            Some(Ok(connection)) => {
+                // the connection is incoming if its local port is still listened on
+                let listeners: connections::FilterOptions = connections::FilterOptions::new().proto(&connection.proto).local_port(&connection.local_port.to_string()).skip_processes(true);
+                let incoming: bool = connections::get_all_connections(&listeners).is_ok_and(|sockets| sockets.iter().any(connections::is_listening));
+                let mut target: firewall::BlockTarget = firewall::BlockTarget::from_connection(&connection, incoming);
+                // a given port replaces the one chosen for the connection
+                if port.is_some() {
+                    target.port = *port;
+                    target.remote_port = None;
+                }
+                target
+            }
+            Some(Err(error)) => {
+                string_utils::print_error("snapshot", &error);
+                std::process::exit(1);
+            }
+            None => firewall::BlockTarget { address: *ip, port: *port, remote_port: None, proto: proto.to_string() },
+        };
+        firewall::print_block_rules(&target, *firewall);
+        return;
+    }
+    if let Some(cli::Commands::GenerateCompletions { shell }) = args.command {
+        completions::print_completions(shell, cli::get_command());
+        return;
+    }
+    if let Some(cli::Commands::Config { action }) = args.command {
+        match action {
+            cli::ConfigAction::Edit => config::edit_config(),
+            cli::ConfigAction::Show => config::print_config(args.profile.as_deref()),
+            cli::ConfigAction::Validate => cli::validate_config(),
+        }
+        return;
+    }
+    if let Some(cli::Commands::Vsock) = args.command {
+        match vsock::get_vsock_sockets() {
+            Ok(sockets) => table::get_vsock_table(&sockets, &table_options),
+            Err(error) => {
+                string_utils::print_error("connections", &format!("Couldn't read the vsock sockets: {}", error));
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // counting connections doesn't need to know which processes own them, unless they are filtered by it
+    let skip_processes: bool = args.count && args.program.is_none() && args.pid.is_none() && args.command.is_none();
 
     // example filter option: Some("tcp".to_string())
-    let filter_options: connections::FilterOptions = connections::FilterOptions { 
+    let mut filter_options: connections::FilterOptions = connections::FilterOptions { 
         by_proto: args.proto,
         by_remote_address: args.ip,
         by_remote_port: args.port, 
@@ -19,29 +148,316 @@ async fn main() {
         by_program: args.program,
         by_pid: args.pid,
         by_open: args.open,
-        exclude_ipv6: args.exclude_ipv6
+        by_states: args.state,
+        by_uid: connections::get_restricted_uid(args.own_only && !args.all_users),
+        by_scope: args.scope,
+        exclude_ipv6: args.exclude_ipv6,
+        collapse_v4mapped: args.collapse_v4mapped,
+        skip_processes,
+        // only counting doesn't show the other processes sharing a socket, so the processes which can't match aren't inspected
+        narrow_processes: args.count,
+        optional_protocols: [(args.raw, "raw"), (args.sctp, "sctp"), (args.dccp, "dccp")].into_iter().filter(|(enabled, _)| *enabled).map(|(_, proto)| proto.to_string()).collect()
     };
 
-    // sanity-check if the AbuseIPDB is usable, if not: don't check remote addresses and print an error
-    if args.check {
+    // the filters of `somo kill` narrow down the ones given before the subcommand
+    if let Some(cli::Commands::Kill { port, program, proto, .. }) = &args.command {
+        filter_options.by_local_port = port.clone().or(filter_options.by_local_port);
+        filter_options.by_program = program.clone().or(filter_options.by_program);
+        filter_options.by_proto = proto.clone().or(filter_options.by_proto);
+    }
+
+    // without filters for specific connections, only new listening sockets are reported
+    if let Some(cli::Commands::Watch { interval, notify, on_new_connection, on_closed_connection, webhook, log_target }) = args.command {
+        let only_listening: bool = [&filter_options.by_remote_address, &filter_options.by_remote_port, &filter_options.by_local_port, &filter_options.by_program, &filter_options.by_pid].iter().all(|filter| filter.is_none());
+        let actions: watch::WatchActions = watch::WatchActions { log_target, notify, on_new_connection, on_closed_connection, webhook };
+        watch::watch_connections(&filter_options, only_listening, interval, &actions);
+    }
+
+    if let Some(cli::Commands::Top { interval }) = args.command {
+        #[cfg(feature = "throughput")]
+        {
+            let use_templates: bool = args.format.is_some() || args.format_file.is_some() || args.format_header.is_some() || args.format_footer.is_some();
+            let top_options: top::TopOptions = top::TopOptions {
+                interval,
+                columns: columns::get_columns(if args.columns.is_empty() { &top::TOP_COLUMNS } else { &args.columns }, args.session, !args.no_index, args.id),
+                sort: args.sort,
+                reverse: args.reverse,
+                limit: args.limit,
+                templates: use_templates.then(|| templates::get_output_templates(&args.format, &args.format_file, &args.format_header, &args.format_footer)).transpose().unwrap_or_else(|error| {
+                    string_utils::print_error("template", &error);
+                    std::process::exit(1);
+                }),
+                table_options
+            };
+            top::show_top(&filter_options, &top_options);
+        }
+        #[cfg(not(feature = "throughput"))]
+        {
+            let _ = interval;
+            string_utils::pretty_print_error("Throughput sampling isn't supported by this build, reinstall somo with `--features throughput`.");
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(cli::Commands::Record { out, interval, rotate, retention }) = args.command {
+        let options: record::RecordOptions = record::RecordOptions { out, interval, rotate, retention };
+        record::record_connections(&filter_options, &options);
+    }
+
+    // get the connections of the system or replay them from a file or the previous output
+    let replay_snapshot: bool = matches!(args.command, Some(cli::Commands::Last));
+    let source: Result<Box<dyn ConnectionSource>, somo::Error> = match &args.replay {
+        Some(path) => FixtureSource::from_file(path).map(|source| Box::new(source) as Box<dyn ConnectionSource>),
+        None if replay_snapshot => match snapshot::get_snapshot_source() {
+            Ok(source) => Ok(Box::new(source)),
+            Err(error) => {
+                string_utils::print_error("snapshot", &error);
+                std::process::exit(1);
+            }
+        },
+        None => Ok(Box::new(ProcfsSource)),
+    };
+    let mut timings: connections::Timings = connections::Timings::default();
+    let mut all_connections: Vec<connections::Connection> = match source.and_then(|source| source.get_connections_timed(&filter_options, &mut timings)) {
+        Ok(all_connections) => all_connections,
+        Err(error) => {
+            string_utils::print_error("connections", &format!("Couldn't get the connections: {}", error));
+            std::process::exit(1);
+        }
+    };
+    let collected_at: String = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+
+    let enrichment_start: Instant = Instant::now();
+
+    // mark sockets which belong to ssh port forwardings and docker-proxy sockets with their containers (replayed connections and snapshots already contain them)
+    if args.replay.is_none() && !replay_snapshot {
+        tunnels::annotate_tunnels(&mut all_connections);
+        docker::annotate_containers(&mut all_connections);
+    }
+
+    // show programs which listen on the same port for IPv4 and IPv6 in one row
+    if args.merge_dual_stack {
+        all_connections = connections::merge_dual_stack_listeners(all_connections);
+    }
+
+    // hide connections to addresses on the allowlist
+    if let Some(path) = &args.allowlist {
+        match address_checkers::load_address_list(path) {
+            Ok(allowlist) => all_connections.retain(|connection| !allowlist.contains(&connection.remote_address)),
+            Err(error) => string_utils::print_error("address_list", &format!("Couldn't read allowlist '{}': {}", path, error)),
+        }
+    }
+
+    // mark connections to addresses on the blocklist
+    if let Some(path) = &args.blocklist {
+        match address_checkers::load_address_list(path) {
+            Ok(blocklist) => {
+                for connection in all_connections.iter_mut() {
+                    connection.blocked = blocklist.contains(&connection.remote_address);
+                }
+            }
+            Err(error) => string_utils::print_error("address_list", &format!("Couldn't read blocklist '{}': {}", path, error)),
+        }
+        if args.only_blocked {
+            all_connections.retain(|connection| connection.blocked);
+        }
+    }
+
+    // kill the process behind a row of the previous output, if it still owns the connection
+    let snapshot_index: Option<usize> = match &args.command {
+        Some(cli::Commands::Kill { index, .. }) => *index,
+        _ => args.kill_index,
+    };
+    if let Some(index) = snapshot_index {
+        let snapshot_connection: connections::Connection = snapshot::get_snapshot_connection(index).unwrap_or_else(|error| {
+            string_utils::print_error("snapshot", &error);
+            std::process::exit(1);
+        });
+        let snapshot_id: String = connections::get_connection_id(&snapshot_connection);
+        all_connections.retain(|connection| connections::get_connection_id(connection) == snapshot_id && connection.pid == snapshot_connection.pid);
+        if all_connections.is_empty() {
+            string_utils::print_error("snapshot", &format!("The connection #{} of the previous output doesn't exist anymore.", index));
+            std::process::exit(1);
+        }
+    }
+    if args.kill_index.is_some() {
+        cli::kill_matching_processes(&all_connections, args.signal.as_deref().unwrap_or("TERM"), args.yes, args.dry_run);
+        return;
+    }
+
+    if let Some(cli::Commands::Kill { signal, yes, dry_run, id, .. }) = &args.command {
+        if let Some(id) = id {
+            all_connections.retain(|connection| connections::get_connection_id(connection) == id.to_lowercase());
+        }
+        cli::kill_matching_processes(&all_connections, signal, *yes, *dry_run);
+        return;
+    }
+
+    // only print the amount of matching connections, exiting with 1 if there are none
+    if args.count {
+        if args.timing {
+            string_utils::print_timings(&[("process scan", timings.process_scan), ("socket read", timings.socket_read), ("filtering", timings.filtering), ("enrichment", enrichment_start.elapsed())]);
+        }
+        sout!("{}", all_connections.len());
+        std::process::exit(if all_connections.is_empty() { 1 } else { 0 });
+    }
+
+    // add a threat score from AbuseIPDB.com to extern remote addresses
+    if args.check_abuse {
         string_utils::pretty_print_info("Checking IPs using AbuseIPDB.com...");
-        let abuse_result = address_checkers::check_address_for_abuse(&("127.0.0.1".to_string()), true).await.unwrap();
-        match abuse_result {
-            Some(_) => { }
-            None => {
-                string_utils::pretty_print_error("Cancelling check for malicious IPs.");
-                args.check = false;
+        address_checkers::annotate_threat_scores(&mut all_connections).await;
+    }
+
+    // add the country and city of extern remote addresses using a GeoLite2 database
+    if args.geo {
+        if let Some(reader) = address_lookups::open_geoip_database(&args.geoip_db) {
+            address_lookups::annotate_locations(&mut all_connections, &reader);
+        }
+    }
+
+    // add the names of LAN peers which answer mDNS or LLMNR queries
+    if args.mdns {
+        mdns::annotate_lan_names(&mut all_connections, Duration::from_millis(500));
+    }
+
+    // add the autonomous system and organization of extern remote addresses using a GeoLite2 ASN database
+    if args.asn {
+        if let Some(reader) = address_lookups::open_asn_database(&args.asn_db) {
+            address_lookups::annotate_asns(&mut all_connections, &reader);
+        }
+    }
+
+    // add the names of the services using the local and remote ports from /etc/services and the user's overrides
+    let show_exposure: bool = matches!(args.command, Some(cli::Commands::Exposure));
+    if args.annotate_ports || show_exposure {
+        let override_path: Option<PathBuf> = config::get_services_override_path();
+        let mut services: services::Services = services::load_services(args.services_file.as_deref().map(Path::new), override_path.as_deref());
+        if args.extended_ports {
+            services = services.with_extended_ports();
+        }
+        // the range of this host is used, unless another range is set (e.g. when replaying the connections of another host)
+        if let Some(range) = args.ephemeral_range.map(|range| range.start..=range.end).or_else(services::get_ephemeral_port_range) {
+            services = services.with_ephemeral_ports(range);
+        }
+        services::annotate_services(&mut all_connections, &services);
+    }
+
+    // add extra fields provided by WASM plugins
+    if !args.plugins.is_empty() {
+        #[cfg(feature = "plugins")]
+        {
+            let mut loaded_plugins: Vec<plugins::Plugin> = Vec::new();
+            for path in &args.plugins {
+                match plugins::load_plugin(path) {
+                    Ok(plugin) => loaded_plugins.push(plugin),
+                    Err(error) => string_utils::print_error("plugin", &format!("Couldn't load plugin '{}': {}", path, error)),
+                }
             }
-        } 
+            plugins::apply_plugins(&mut all_connections, &mut loaded_plugins);
+        }
+        #[cfg(not(feature = "plugins"))]
+        string_utils::pretty_print_error("Plugins aren't supported by this build, reinstall somo with `--features plugins`.");
     }
 
-    // get running processes
-    let all_connections: Vec<connections::Connection> = connections::get_all_connections(&filter_options, args.check).await;
-    
-    table::get_connections_table(&all_connections);
+    if let Some(sort_by) = &args.sort {
+        columns::sort_connections(&mut all_connections, sort_by, args.reverse);
+    }
+
+    // only show the first or last rows, so that quick checks on busy servers don't print thousands of lines
+    let total_connections: usize = all_connections.len();
+    if let Some(limit) = args.limit {
+        all_connections.truncate(limit);
+    }
+    if let Some(tail) = args.tail {
+        all_connections.drain(..total_connections.saturating_sub(tail));
+    }
+
+    let enrichment: Duration = enrichment_start.elapsed();
+    let rendering_start: Instant = Instant::now();
+
+    // only page tables, since machine-readable output is meant for other programs and the interactive prompts need the terminal
+    let use_templates: bool = args.format.is_some() || args.format_file.is_some() || args.format_header.is_some() || args.format_footer.is_some();
+    if !use_templates && !args.json_envelope && !args.kill && !args.kill_all && !args.close_socket {
+        pager::start_paging(args.paging, args.pager.as_deref(), args.pager_min_lines);
+    }
+
+    if show_exposure {
+        table::get_exposure_table(&exposure::get_exposed_sockets(&all_connections), &table_options);
+        return;
+    }
+
+    if args.json_envelope {
+        json_output::print_json_envelope(&all_connections, &filter_options, &collected_at);
+    }
+
+    // print the connections using a handlebars template instead of a table
+    if use_templates {
+        let result: Result<(), String> = templates::get_output_templates(&args.format, &args.format_file, &args.format_header, &args.format_footer).and_then(|output_templates| templates::print_formatted_connections(&all_connections, &output_templates, &collected_at));
+        if let Err(error) = result {
+            string_utils::print_error("template", &error);
+            std::process::exit(1);
+        }
+    }
+
+    // remember the output, so that `somo last` can show it again and e.g. `somo kill 7` can refer to its rows
+    if args.replay.is_none() {
+        snapshot::save_snapshot(&all_connections);
+    }
+
+    let table_columns: Vec<columns::Column> = columns::get_columns(&args.columns, args.session, !args.no_index, args.id);
+    match &args.group_by {
+        _ if use_templates || args.json_envelope => { }
+        _ if args.tree => table::get_connections_tree(&all_connections, args.replay.is_none() && !replay_snapshot, &table_options),
+        _ if args.by_remote => table::get_remote_hosts_table(&all_connections, &table_options),
+        Some(group_by) => table::get_grouped_connections_tables(&all_connections, &table_columns, group_by, args.group_sort.as_ref().map(|key| (key, args.reverse)), &table_options),
+        None => table::get_connections_table(&all_connections, &table_columns, &table_options),
+    }
+
+    if all_connections.len() < total_connections && !use_templates && !args.json_envelope {
+        let flag: &str = if args.limit.is_some() { "--limit" } else { "--tail" };
+        string_utils::pretty_print_info(&format!("{} of {} connections hidden by `{}`.", total_connections - all_connections.len(), total_connections, flag));
+    }
+
+    if args.timing {
+        string_utils::print_timings(&[("process scan", timings.process_scan), ("socket read", timings.socket_read), ("filtering", timings.filtering), ("enrichment", enrichment), ("rendering", rendering_start.elapsed())]);
+    }
+
+    // warn about risky findings like databases listening on all interfaces
+    if args.hints {
+        let hints: Vec<heuristics::Hint> = heuristics::get_hints(&all_connections);
+        if hints.is_empty() {
+            string_utils::pretty_print_info("No risky findings.");
+        }
+        for hint in hints {
+            string_utils::pretty_print_warning(&format!("**#{}**: {}", hint.index, hint.message));
+        }
+    }
+
+    // suggest sudo if the owners of most connections are unknown, since other users' processes can't be inspected
+    if !args.no_permission_hint && args.replay.is_none() && !replay_snapshot && connections::get_effective_uid() != 0 {
+        let owned_sockets: Vec<&connections::Connection> = all_connections.iter().filter(|connection| connection.state != connections::State::TimeWait).collect();
+        let unknown_owners: usize = owned_sockets.iter().filter(|connection| connection.pid.is_none()).count();
+        if unknown_owners > 0 && unknown_owners * 2 >= owned_sockets.len() {
+            string_utils::pretty_print_warning(&format!(
+                "The program and PID of {} of {} connections are unknown because they belong to other users. Run somo using sudo to see them (hide this hint with `--no-permission-hint`).",
+                unknown_owners, owned_sockets.len()
+            ));
+        }
+    }
 
     if args.kill {
-        cli::interactve_process_kill(&all_connections);
+        cli::interactve_process_kill(&all_connections, &args.signal);
+    }
+    if args.kill_all {
+        cli::kill_all_processes(&all_connections, &args.signal);
+    }
+    if args.close_socket {
+        cli::interactive_socket_close(&all_connections);
+    }
+
+    // let scripts and health checks react to the result without parsing the output
+    if (args.fail_if_none && all_connections.is_empty()) || (args.fail_if_any && !all_connections.is_empty()) {
+        std::process::exit(1);
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,211 @@
+use log::debug;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::connections;
+
+/// The multicast groups of mDNS (e.g. printers, NAS boxes and Apple devices) and LLMNR (Windows hosts).
+/// Queries are sent from an ephemeral port, so responders answer directly to it ("legacy unicast", RFC 6762 section 6.7).
+static MULTICAST_RESPONDERS: [(Ipv4Addr, u16); 2] = [(Ipv4Addr::new(224, 0, 0, 251), 5353), (Ipv4Addr::new(224, 0, 0, 252), 5355)];
+
+/// The DNS record type of a pointer, which maps a reverse name (e.g. "20.1.168.192.in-addr.arpa") to a host name.
+const TYPE_PTR: u16 = 12;
+
+/// The size of the header of a DNS message.
+const HEADER_SIZE: usize = 12;
+
+
+/// Builds a DNS query for the pointer record of an IPv4 address, i.e. its reverse name.
+///
+/// # Arguments
+/// * `id`: The ID of the query, which the response repeats.
+/// * `address`: The address to resolve.
+///
+/// # Returns
+/// The DNS message.
+fn build_query(id: u16, address: &Ipv4Addr) -> Vec<u8> {
+    let mut query: Vec<u8> = Vec::with_capacity(48);
+    // the header with one question
+    query.extend(id.to_be_bytes());
+    query.extend([0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+
+    let octets: Vec<String> = address.octets().iter().rev().map(u8::to_string).collect();
+    for label in octets.iter().map(String::as_str).chain(["in-addr", "arpa"]) {
+        query.push(label.len() as u8);
+        query.extend(label.as_bytes());
+    }
+    query.push(0);
+    query.extend(TYPE_PTR.to_be_bytes());
+    // class IN
+    query.extend(1u16.to_be_bytes());
+    query
+}
+
+
+/// Reads a `u16` in network byte order from a DNS message.
+fn read_u16(message: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*message.get(offset)?, *message.get(offset + 1)?]))
+}
+
+
+/// Reads a (possibly compressed) name of a DNS message.
+///
+/// # Arguments
+/// * `message`: The DNS message.
+/// * `offset`: The offset of the name.
+///
+/// # Returns
+/// The name (without the trailing dot) and the offset after it or `None` if the name is malformed.
+fn read_name(message: &[u8], offset: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut position: usize = offset;
+    let mut end: Option<usize> = None;
+    // limits the pointers followed, so that malicious messages can't loop forever
+    for _ in 0..64 {
+        let length: usize = *message.get(position)? as usize;
+        match length {
+            0 => return Some((labels.join("."), end.unwrap_or(position + 1))),
+            // a pointer to a name earlier in the message
+            _ if length & 0xc0 == 0xc0 => {
+                end.get_or_insert(position + 2);
+                position = (read_u16(message, position)? & 0x3fff) as usize;
+            }
+            _ => {
+                labels.push(String::from_utf8_lossy(message.get(position + 1..position + 1 + length)?).to_string());
+                position += 1 + length;
+            }
+        }
+    }
+    None
+}
+
+
+/// Parses the response to a pointer query.
+///
+/// # Arguments
+/// * `message`: The DNS message.
+///
+/// # Returns
+/// The ID of the query and the name of the first pointer record or `None` if the message isn't such a response.
+fn parse_response(message: &[u8]) -> Option<(u16, String)> {
+    let id: u16 = read_u16(message, 0)?;
+    let flags: u16 = read_u16(message, 2)?;
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    let questions: u16 = read_u16(message, 4)?;
+    let answers: u16 = read_u16(message, 6)?;
+
+    let mut offset: usize = HEADER_SIZE;
+    for _ in 0..questions {
+        offset = read_name(message, offset)?.1 + 4;
+    }
+    for _ in 0..answers {
+        offset = read_name(message, offset)?.1;
+        let record_type: u16 = read_u16(message, offset)?;
+        let data_length: usize = read_u16(message, offset + 8)? as usize;
+        if record_type == TYPE_PTR {
+            return Some((id, read_name(message, offset + 10)?.0));
+        }
+        offset += 10 + data_length;
+    }
+    None
+}
+
+
+/// Resolves the names of IPv4 addresses using mDNS and LLMNR, waiting for responses until the timeout.
+///
+/// # Arguments
+/// * `addresses`: The addresses to resolve.
+/// * `timeout`: How long to wait for responses in total.
+///
+/// # Returns
+/// The names by address or an error if the queries can't be sent.
+fn resolve_names(addresses: &[Ipv4Addr], timeout: Duration) -> io::Result<HashMap<Ipv4Addr, String>> {
+    let socket: UdpSocket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    for (id, address) in addresses.iter().enumerate() {
+        let query: Vec<u8> = build_query(id as u16, address);
+        for (group, port) in MULTICAST_RESPONDERS {
+            socket.send_to(&query, SocketAddr::new(IpAddr::V4(group), port))?;
+        }
+    }
+
+    let mut names: HashMap<Ipv4Addr, String> = HashMap::new();
+    let deadline: Instant = Instant::now() + timeout;
+    let mut buffer: [u8; 9000] = [0; 9000];
+    while names.len() < addresses.len() {
+        let Some(remaining) = deadline.checked_duration_since(Instant::now()).filter(|remaining| !remaining.is_zero()) else {
+            break;
+        };
+        socket.set_read_timeout(Some(remaining))?;
+        let Ok((length, _)) = socket.recv_from(&mut buffer) else {
+            break;
+        };
+        if let Some((id, name)) = parse_response(&buffer[..length]) {
+            if let Some(address) = addresses.get(id as usize) {
+                names.entry(*address).or_insert(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+
+/// Adds the names of LAN peers (private IPv4 remote addresses, e.g. printers or NAS boxes) resolved using mDNS (`.local`) and LLMNR.
+/// Peers which don't answer within the timeout stay without a name.
+///
+/// # Arguments
+/// * `connections`: The connections to annotate.
+/// * `timeout`: How long to wait for the peers to answer.
+///
+/// # Returns
+/// None
+pub fn annotate_lan_names(connections: &mut [connections::Connection], timeout: Duration) {
+    let mut addresses: Vec<Ipv4Addr> = connections.iter()
+        .filter_map(|connection| match connection.remote_address.to_canonical() {
+            IpAddr::V4(address) if address.is_private() => Some(address),
+            _ => None,
+        })
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+    if addresses.is_empty() {
+        return;
+    }
+
+    let names: HashMap<Ipv4Addr, String> = match resolve_names(&addresses, timeout) {
+        Ok(names) => names,
+        Err(error) => {
+            debug!("Couldn't send the mDNS queries: {}", error);
+            return;
+        }
+    };
+    debug!("Resolved {} of {} LAN address(es) using mDNS and LLMNR", names.len(), addresses.len());
+    for connection in connections.iter_mut() {
+        if let IpAddr::V4(address) = connection.remote_address.to_canonical() {
+            connection.remote_name = names.get(&address).cloned();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        // the response repeats the question and answers it, referring to the question's name by a pointer
+        let mut response: Vec<u8> = build_query(7, &Ipv4Addr::new(192, 168, 1, 20));
+        response[2..4].copy_from_slice(&0x8400u16.to_be_bytes());
+        response[6..8].copy_from_slice(&1u16.to_be_bytes());
+        response.extend([0xc0, 0x0c, 0, 12, 0, 1, 0, 0, 0, 120, 0, 15]);
+        response.extend(b"\x07printer\x05local\x00");
+
+        assert_eq!(read_name(&response, HEADER_SIZE).map(|(name, _)| name).as_deref(), Some("20.1.168.192.in-addr.arpa"));
+        assert_eq!(parse_response(&response), Some((7, "printer.local".to_string())));
+        assert_eq!(parse_response(&build_query(7, &Ipv4Addr::new(192, 168, 1, 20))), None);
+    }
+}
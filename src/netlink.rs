@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+/// The netlink message type for requesting the sockets of an address family from the `sock_diag` interface.
+pub(crate) const SOCK_DIAG_BY_FAMILY: u16 = 20;
+
+/// The netlink message type for closing a socket using the `sock_diag` interface (like `ss -K`).
+const SOCK_DESTROY: u16 = 21;
+
+/// The size of a `struct nlmsghdr`, which precedes every netlink message.
+const NLMSG_HEADER_SIZE: usize = 16;
+
+/// The size of a `struct inet_diag_msg` returned by the kernel for every TCP socket.
+const INET_DIAG_MSG_SIZE: usize = 72;
+
+/// The number of the `LISTEN` state of TCP in the kernel, used to only dump listening sockets.
+const TCP_LISTEN: u32 = 10;
+
+/// The type of the attribute containing the `struct tcp_info` of a socket, requested using the bit `1 << (INET_DIAG_INFO - 1)`.
+#[cfg(feature = "throughput")]
+const INET_DIAG_INFO: u16 = 2;
+
+/// The size of a `struct tcp_info` up to and including `tcpi_bytes_received`, older kernels send a shorter one without the byte counters.
+#[cfg(feature = "throughput")]
+const TCP_INFO_BYTES_SIZE: usize = 136;
+
+/// A socket reported by the `sock_diag` interface, for protocols without a socket table in `/proc/net`.
+pub(crate) struct DiagSocket {
+    pub(crate) local_address: SocketAddr,
+    pub(crate) remote_address: SocketAddr,
+    /// The number of the state, using the numbering of the TCP states.
+    pub(crate) state: u8,
+    pub(crate) uid: u32,
+    pub(crate) inode: u64
+}
+
+/// A netlink socket connected to the `sock_diag` interface of the kernel, which lists and closes sockets.
+pub(crate) struct SockDiag {
+    socket: OwnedFd
+}
+
+
+/// Reads a native-endian `u32` from a netlink message.
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+
+/// Reads a native-endian `u64` from a netlink message.
+#[cfg(feature = "throughput")]
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut value: [u8; 8] = [0; 8];
+    value.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_ne_bytes(value)
+}
+
+
+impl SockDiag {
+    /// Opens a netlink socket for the `sock_diag` interface.
+    ///
+    /// # Returns
+    /// The socket or an error if it can't be created.
+    pub(crate) fn open() -> io::Result<Self> {
+        let raw_fd: i32 = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, libc::NETLINK_SOCK_DIAG) };
+        if raw_fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the socket was just created and isn't owned by anything else, `OwnedFd` closes it when dropped
+        Ok(SockDiag { socket: unsafe { OwnedFd::from_raw_fd(raw_fd) } })
+    }
+
+    /// Sends a request to the kernel.
+    ///
+    /// # Arguments
+    /// * `message_type`: The type of the netlink message, e.g. `SOCK_DIAG_BY_FAMILY`.
+    /// * `flags`: The netlink flags in addition to `NLM_F_REQUEST`, e.g. `NLM_F_DUMP`.
+    /// * `payload`: The request following the netlink header.
+    ///
+    /// # Returns
+    /// An error if the request can't be sent.
+    pub(crate) fn send(&self, message_type: u16, flags: i32, payload: &[u8]) -> io::Result<()> {
+        let mut message: Vec<u8> = Vec::with_capacity(NLMSG_HEADER_SIZE + payload.len());
+        message.extend(((NLMSG_HEADER_SIZE + payload.len()) as u32).to_ne_bytes());
+        message.extend(message_type.to_ne_bytes());
+        message.extend(((libc::NLM_F_REQUEST | flags) as u16).to_ne_bytes());
+        message.extend(1u32.to_ne_bytes());
+        message.extend(0u32.to_ne_bytes());
+        message.extend(payload);
+
+        // SAFETY: an all-zero `sockaddr_nl` is valid, it addresses the kernel
+        let mut kernel: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        kernel.nl_family = libc::AF_NETLINK as u16;
+        let sent: isize = unsafe {
+            libc::sendto(
+                self.socket.as_raw_fd(), message.as_ptr().cast(), message.len(), 0,
+                (&kernel as *const libc::sockaddr_nl).cast(), std::mem::size_of::<libc::sockaddr_nl>() as u32
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Receives the responses to a request until the kernel signals the end of a dump or acknowledges the request.
+    ///
+    /// # Arguments
+    /// * `on_message`: Called with the payload of every message which isn't a control message.
+    ///
+    /// # Returns
+    /// An error if the kernel reports one (e.g. `ENOENT` if a diag module isn't loaded) or if the response can't be read.
+    pub(crate) fn receive(&self, mut on_message: impl FnMut(&[u8])) -> io::Result<()> {
+        let mut buffer: Vec<u8> = vec![0; 32768];
+        loop {
+            let received: isize = unsafe { libc::recv(self.socket.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len(), 0) };
+            if received < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut messages: &[u8] = &buffer[..received as usize];
+
+            // every message is aligned to 4 bytes
+            while messages.len() >= NLMSG_HEADER_SIZE {
+                let length: usize = read_u32(messages, 0) as usize;
+                let message_type: u16 = u16::from_ne_bytes([messages[4], messages[5]]);
+                if length < NLMSG_HEADER_SIZE || length > messages.len() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed sock_diag response"));
+                }
+                let payload: &[u8] = &messages[NLMSG_HEADER_SIZE..length];
+
+                match message_type as i32 {
+                    libc::NLMSG_DONE => return Ok(()),
+                    // an error of 0 acknowledges the request
+                    libc::NLMSG_ERROR => return match -(read_u32(payload, 0) as i32) {
+                        0 => Ok(()),
+                        errno => Err(io::Error::from_raw_os_error(errno))
+                    },
+                    _ => on_message(payload)
+                }
+                messages = &messages[(length + 3) & !3..];
+            }
+        }
+    }
+}
+
+
+/// Appends an address of a `struct inet_diag_sockid`, which always has room for an IPv6 address.
+fn push_address(request: &mut Vec<u8>, address: &IpAddr) {
+    match address {
+        IpAddr::V4(address) => {
+            request.extend(address.octets());
+            request.extend([0u8; 12]);
+        }
+        IpAddr::V6(address) => request.extend(address.octets()),
+    }
+}
+
+
+/// Closes a TCP socket using `SOCK_DESTROY`, the socket's process gets an error (`ECONNABORTED`) on its next use of it.
+/// This requires `CAP_NET_ADMIN` and a kernel built with `CONFIG_INET_DIAG_DESTROY`.
+///
+/// # Arguments
+/// * `local_address`: The local address and port of the socket.
+/// * `remote_address`: The remote address and port of the socket.
+///
+/// # Returns
+/// An error if the socket can't be closed, e.g. `EOPNOTSUPP` if the kernel doesn't support it or `EPERM` without root.
+pub(crate) fn destroy_tcp_socket(local_address: &SocketAddr, remote_address: &SocketAddr) -> io::Result<()> {
+    // struct inet_diag_req_v2 with the struct inet_diag_sockid of the socket
+    let mut request: Vec<u8> = Vec::with_capacity(56);
+    let family: i32 = if local_address.is_ipv4() { libc::AF_INET } else { libc::AF_INET6 };
+    request.extend([family as u8, libc::IPPROTO_TCP as u8, 0, 0]);
+    request.extend(u32::MAX.to_ne_bytes());
+    request.extend(local_address.port().to_be_bytes());
+    request.extend(remote_address.port().to_be_bytes());
+    push_address(&mut request, &local_address.ip());
+    push_address(&mut request, &remote_address.ip());
+    // any interface and no cookie
+    request.extend(0u32.to_ne_bytes());
+    request.extend([0xff; 8]);
+
+    let sock_diag: SockDiag = SockDiag::open()?;
+    sock_diag.send(SOCK_DESTROY, libc::NLM_F_ACK, &request)?;
+    sock_diag.receive(|_| { })
+}
+
+
+/// Gets the accept queues of all listening TCP sockets from the `sock_diag` interface. For listening sockets, the kernel reports
+/// the amount of connections waiting to be accepted as the receive queue and the backlog (see `man listen`) as the send queue.
+///
+/// # Returns
+/// The length of the accept queue and the backlog by the inode of the socket or an error if the interface can't be queried.
+pub(crate) fn get_tcp_listen_queues() -> io::Result<HashMap<u64, (u32, u32)>> {
+    let sock_diag: SockDiag = SockDiag::open()?;
+    let mut listen_queues: HashMap<u64, (u32, u32)> = HashMap::new();
+    for family in [libc::AF_INET, libc::AF_INET6] {
+        // struct inet_diag_req_v2 matching every listening socket of the family
+        let mut request: Vec<u8> = Vec::with_capacity(56);
+        request.extend([family as u8, libc::IPPROTO_TCP as u8, 0, 0]);
+        request.extend((1u32 << TCP_LISTEN).to_ne_bytes());
+        request.extend([0u8; 48]);
+
+        sock_diag.send(SOCK_DIAG_BY_FAMILY, libc::NLM_F_DUMP, &request)?;
+        sock_diag.receive(|message| {
+            if message.len() >= INET_DIAG_MSG_SIZE {
+                listen_queues.insert(read_u32(message, 68) as u64, (read_u32(message, 56), read_u32(message, 60)));
+            }
+        })?;
+    }
+    Ok(listen_queues)
+}
+
+
+/// Reads an address and port of the `struct inet_diag_sockid` of an `inet_diag_msg`.
+///
+/// # Arguments
+/// * `message`: The `inet_diag_msg`.
+/// * `port_offset`: The offset of the port (in network byte order).
+/// * `address_offset`: The offset of the address, IPv4 addresses only use the first 4 of its 16 bytes.
+///
+/// # Returns
+/// The address and port.
+fn read_socket_address(message: &[u8], port_offset: usize, address_offset: usize) -> SocketAddr {
+    let port: u16 = u16::from_be_bytes([message[port_offset], message[port_offset + 1]]);
+    let address: IpAddr = if message[0] as i32 == libc::AF_INET {
+        IpAddr::from([message[address_offset], message[address_offset + 1], message[address_offset + 2], message[address_offset + 3]])
+    } else {
+        let mut octets: [u8; 16] = [0; 16];
+        octets.copy_from_slice(&message[address_offset..address_offset + 16]);
+        IpAddr::from(octets)
+    };
+    SocketAddr::new(address, port)
+}
+
+
+/// Gets all DCCP sockets from the `sock_diag` interface, since the kernel has no socket table for DCCP in `/proc/net`.
+///
+/// # Returns
+/// The sockets or an error if the interface can't be queried, e.g. `ENOENT` if the `dccp_diag` kernel module isn't loaded.
+pub(crate) fn get_dccp_sockets() -> io::Result<Vec<DiagSocket>> {
+    let sock_diag: SockDiag = SockDiag::open()?;
+    let mut sockets: Vec<DiagSocket> = Vec::new();
+    for family in [libc::AF_INET, libc::AF_INET6] {
+        // struct inet_diag_req_v2 matching every socket of the family
+        let mut request: Vec<u8> = Vec::with_capacity(56);
+        request.extend([family as u8, libc::IPPROTO_DCCP as u8, 0, 0]);
+        request.extend(u32::MAX.to_ne_bytes());
+        request.extend([0u8; 48]);
+
+        sock_diag.send(SOCK_DIAG_BY_FAMILY, libc::NLM_F_DUMP, &request)?;
+        sock_diag.receive(|message| {
+            if message.len() >= INET_DIAG_MSG_SIZE {
+                sockets.push(DiagSocket {
+                    local_address: read_socket_address(message, 4, 8),
+                    remote_address: read_socket_address(message, 6, 24),
+                    state: message[1],
+                    uid: read_u32(message, 64),
+                    inode: read_u32(message, 68) as u64
+                });
+            }
+        })?;
+    }
+    Ok(sockets)
+}
+
+
+/// Gets the amount of bytes every TCP connection has sent (and got acknowledged) and received so far,
+/// from the `struct tcp_info` the `sock_diag` interface reports with `INET_DIAG_INFO`. Listening sockets are skipped.
+///
+/// # Returns
+/// The sent and received bytes by the local and remote address of the connection or an error if the interface can't be queried.
+#[cfg(feature = "throughput")]
+pub(crate) fn get_tcp_byte_counters() -> io::Result<HashMap<(SocketAddr, SocketAddr), (u64, u64)>> {
+    let sock_diag: SockDiag = SockDiag::open()?;
+    let mut byte_counters: HashMap<(SocketAddr, SocketAddr), (u64, u64)> = HashMap::new();
+    for family in [libc::AF_INET, libc::AF_INET6] {
+        // struct inet_diag_req_v2 matching every socket of the family except listeners, requesting their tcp_info
+        let mut request: Vec<u8> = Vec::with_capacity(56);
+        request.extend([family as u8, libc::IPPROTO_TCP as u8, 1 << (INET_DIAG_INFO - 1), 0]);
+        request.extend((!(1u32 << TCP_LISTEN)).to_ne_bytes());
+        request.extend([0u8; 48]);
+
+        sock_diag.send(SOCK_DIAG_BY_FAMILY, libc::NLM_F_DUMP, &request)?;
+        sock_diag.receive(|message| {
+            if message.len() < INET_DIAG_MSG_SIZE {
+                return;
+            }
+            // IPv4-mapped IPv6 addresses are converted to IPv4 addresses, like the addresses of the connections
+            let canonical = |address: SocketAddr| SocketAddr::new(address.ip().to_canonical(), address.port());
+            let local_address: SocketAddr = canonical(read_socket_address(message, 4, 8));
+            let remote_address: SocketAddr = canonical(read_socket_address(message, 6, 24));
+
+            // the attributes following the message are aligned to 4 bytes, each starts with its length and type
+            let mut attributes: &[u8] = &message[INET_DIAG_MSG_SIZE..];
+            while attributes.len() >= 4 {
+                let length: usize = u16::from_ne_bytes([attributes[0], attributes[1]]) as usize;
+                let attribute_type: u16 = u16::from_ne_bytes([attributes[2], attributes[3]]);
+                if length < 4 || length > attributes.len() {
+                    break;
+                }
+                if attribute_type == INET_DIAG_INFO && length >= 4 + TCP_INFO_BYTES_SIZE {
+                    let tcp_info: &[u8] = &attributes[4..length];
+                    byte_counters.insert((local_address, remote_address), (read_u64(tcp_info, 120), read_u64(tcp_info, 128)));
+                }
+                attributes = &attributes[((length + 3) & !3).min(attributes.len())..];
+            }
+        })?;
+    }
+    Ok(byte_counters)
+}
@@ -0,0 +1,136 @@
+use clap::ValueEnum;
+use std::env;
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::process::{self, Child, Stdio};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+
+/// The pager used if neither `--pager` nor `$PAGER` is set.
+static DEFAULT_PAGER: &str = "less";
+
+/// The thread forwarding the output to the pager, it is joined when somo exits.
+static PAGER_THREAD: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// Represents when the output should be shown in a pager.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PagingMode {
+    /// Page if the output has at least as many lines as the terminal (or `--pager-min-lines`)
+    Auto,
+    Always,
+    Never,
+}
+
+
+/// Starts the pager command, writing to the terminal. `less` is told to quit if the output fits on one screen,
+/// to pass colors through and to not clear the screen (unless `$LESS` is set).
+///
+/// # Arguments
+/// * `command`: The pager command, optionally with arguments, e.g. "less -S".
+/// * `terminal`: The original stdout of somo.
+///
+/// # Returns
+/// The pager process or `None` if it can't be started, e.g. if it isn't installed.
+fn spawn_pager(command: &str, terminal: &OwnedFd) -> Option<Child> {
+    let mut command_parts = command.split_whitespace();
+    let mut pager = process::Command::new(command_parts.next()?);
+    pager.args(command_parts).stdin(Stdio::piped()).stdout(Stdio::from(terminal.try_clone().ok()?));
+    if env::var_os("LESS").is_none() {
+        pager.env("LESS", "FRX");
+    }
+    pager.spawn().ok()
+}
+
+
+/// Forwards the output of somo to the pager once it has enough lines, or else directly to the terminal.
+/// If the pager can't be started the output is printed directly as well.
+///
+/// # Arguments
+/// * `output`: The reading end of the pipe which replaced stdout.
+/// * `terminal`: The original stdout of somo.
+/// * `command`: The pager command.
+/// * `min_lines`: The amount of lines from which on the output is paged.
+///
+/// # Returns
+/// None
+fn forward_output(mut output: File, terminal: OwnedFd, command: String, min_lines: usize) {
+    let mut buffered: Vec<u8> = Vec::new();
+    let mut chunk: [u8; 8192] = [0; 8192];
+    while buffered.iter().filter(|byte| **byte == b'\n').count() < min_lines {
+        match output.read(&mut chunk) {
+            Ok(0) | Err(_) => {
+                let _ = File::from(terminal).write_all(&buffered);
+                return;
+            }
+            Ok(read) => buffered.extend_from_slice(&chunk[..read]),
+        }
+    }
+
+    let Some(mut pager) = spawn_pager(&command, &terminal) else {
+        let mut terminal: File = File::from(terminal);
+        let _ = terminal.write_all(&buffered);
+        let _ = io::copy(&mut output, &mut terminal);
+        return;
+    };
+    // the pager's input is closed when `stdin` is dropped, it stops early if the user quits the pager
+    if let Some(mut stdin) = pager.stdin.take() {
+        let _ = stdin.write_all(&buffered).and_then(|_| io::copy(&mut output, &mut stdin));
+    }
+    let _ = pager.wait();
+}
+
+
+/// Closes stdout so that the pager receives the end of the output, then waits until the user quits it.
+/// Registered using `atexit`, so that it also runs if somo exits using `process::exit`.
+extern "C" fn finish_paging() {
+    let _ = io::stdout().flush();
+    unsafe { libc::close(libc::STDOUT_FILENO) };
+    if let Some(thread) = PAGER_THREAD.lock().ok().and_then(|mut thread| thread.take()) {
+        let _ = thread.join();
+    }
+}
+
+
+/// Redirects everything printed to stdout from now on into a pager, if stdout is a terminal and the paging mode allows it.
+/// In `auto` mode the output is buffered until it's known to be long enough, shorter output is printed directly.
+///
+/// # Arguments
+/// * `mode`: When to page.
+/// * `command`: The pager command, if not set `$PAGER` or else `less` is used.
+/// * `min_lines`: The amount of lines from which on the output is paged in `auto` mode, if not set the height of the terminal.
+///
+/// # Returns
+/// None
+pub fn start_paging(mode: PagingMode, command: Option<&str>, min_lines: Option<usize>) {
+    if mode == PagingMode::Never || !io::stdout().is_terminal() {
+        return;
+    }
+    let min_lines: usize = match mode {
+        PagingMode::Always => 0,
+        _ => min_lines.or_else(|| terminal_size::terminal_size().map(|(_, height)| height.0 as usize)).unwrap_or(usize::MAX),
+    };
+    let command: String = command.map(String::from)
+        .or_else(|| env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()))
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+
+    let Ok(terminal) = io::stdout().as_fd().try_clone_to_owned() else {
+        return;
+    };
+    let mut pipe: [i32; 2] = [0; 2];
+    if unsafe { libc::pipe2(pipe.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return;
+    }
+    // SAFETY: both ends of the pipe were just created and aren't owned by anything else
+    let (output, input) = unsafe { (OwnedFd::from_raw_fd(pipe[0]), OwnedFd::from_raw_fd(pipe[1])) };
+    let _ = io::stdout().flush();
+    if unsafe { libc::dup2(input.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        return;
+    }
+
+    let thread: JoinHandle<()> = thread::spawn(move || forward_output(File::from(output), terminal, command, min_lines));
+    if let Ok(mut pager_thread) = PAGER_THREAD.lock() {
+        *pager_thread = Some(thread);
+    }
+    unsafe { libc::atexit(finish_paging) };
+}
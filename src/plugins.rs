@@ -0,0 +1,98 @@
+use serde_json::Value;
+use std::error::Error;
+use std::fs::File;
+use wasmi::{Engine, Linker, Memory, Module, Store, TypedFunc};
+
+use crate::connections;
+use crate::string_utils;
+use crate::templates;
+
+/// Represents a loaded WASM plugin which adds extra fields to connections.
+///
+/// A plugin module has to export the following items:
+/// * `memory`: The linear memory used to exchange data.
+/// * `alloc(len: i32) -> i32`: Allocates `len` bytes and returns a pointer to them.
+/// * `enrich(ptr: i32, len: i32) -> i64`: Receives a connection as a JSON object and returns a JSON object with
+///   string values, which is encoded as the pointer (upper 32 bits) and length (lower 32 bits) of the result.
+pub struct Plugin {
+    name: String,
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    enrich: TypedFunc<(i32, i32), i64>,
+}
+
+
+/// Loads and instantiates a WASM plugin from a file.
+///
+/// # Arguments
+/// * `path`: The path to the `.wasm` file.
+///
+/// # Returns
+/// The instantiated plugin or an error if the module couldn't be loaded or doesn't export the expected items.
+pub fn load_plugin(path: &str) -> Result<Plugin, Box<dyn Error>> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, File::open(path)?)?;
+    let mut store = Store::new(&engine, ());
+    let linker = <Linker<()>>::new(&engine);
+    let instance = linker.instantiate(&mut store, &module)?.start(&mut store)?;
+
+    let memory = instance.get_memory(&store, "memory").ok_or("plugin doesn't export `memory`")?;
+    let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc")?;
+    let enrich = instance.get_typed_func::<(i32, i32), i64>(&store, "enrich")?;
+
+    Ok(Plugin { name: path.to_string(), store, memory, alloc, enrich })
+}
+
+
+impl Plugin {
+    /// Passes a connection to the plugin and collects the fields it returns.
+    ///
+    /// # Arguments
+    /// * `connection`: The connection to enrich.
+    ///
+    /// # Returns
+    /// The key/value pairs returned by the plugin or an error if the plugin trapped or returned invalid JSON.
+    fn enrich_connection(&mut self, connection: &connections::Connection) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let input = templates::connection_to_json(connection).to_string();
+
+        let input_ptr = self.alloc.call(&mut self.store, input.len() as i32)?;
+        self.memory.write(&mut self.store, input_ptr as usize, input.as_bytes()).map_err(|error| error.to_string())?;
+
+        let result = self.enrich.call(&mut self.store, (input_ptr, input.len() as i32))? as u64;
+        let (output_ptr, output_len) = ((result >> 32) as usize, (result & 0xFFFF_FFFF) as usize);
+        let mut output = vec![0u8; output_len];
+        self.memory.read(&self.store, output_ptr, &mut output).map_err(|error| error.to_string())?;
+
+        let fields: Value = serde_json::from_slice(&output)?;
+        let fields = fields.as_object().ok_or("plugin didn't return a JSON object")?;
+
+        Ok(fields.iter()
+            .map(|(key, value)| (key.to_string(), value.as_str().map(String::from).unwrap_or_else(|| value.to_string())))
+            .collect())
+    }
+}
+
+
+/// Runs every plugin on every connection and stores the returned fields in the connection.
+/// A plugin which fails on a connection is reported once and skipped for the remaining connections.
+///
+/// # Arguments
+/// * `connections`: The connections to enrich.
+/// * `plugins`: The loaded plugins.
+///
+/// # Returns
+/// None
+pub fn apply_plugins(connections: &mut [connections::Connection], plugins: &mut [Plugin]) {
+    for plugin in plugins.iter_mut() {
+        for connection in connections.iter_mut() {
+            match plugin.enrich_connection(connection) {
+                Ok(fields) => connection.extra_fields.extend(fields),
+                Err(error) => {
+                    string_utils::pretty_print_error(&format!("Plugin '{}' failed: {}", plugin.name, error));
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::connections::{self, FilterOptions};
+use crate::string_utils;
+use crate::json_output;
+
+/// The prefix and extension of the files written by `somo record`, only such files are deleted by the retention.
+static FILE_PREFIX: &str = "somo-";
+static FILE_EXTENSION: &str = "ndjson";
+
+/// Represents how often `somo record` starts a new file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Rotation {
+    Hourly,
+    Daily,
+}
+
+/// Contains the options of `somo record`.
+#[derive(Debug)]
+pub struct RecordOptions {
+    /// The directory the files are written to.
+    pub out: PathBuf,
+    /// The time between two samples.
+    pub interval: Duration,
+    /// How often a new file is started.
+    pub rotate: Rotation,
+    /// How long files are kept, if not set they are never deleted.
+    pub retention: Option<Duration>
+}
+
+
+/// Gets the path of the file a sample is appended to, e.g. `somo-2024-05-01.ndjson` or `somo-2024-05-01T13.ndjson` (UTC).
+///
+/// # Arguments
+/// * `out`: The directory of the files.
+/// * `rotate`: How often a new file is started.
+/// * `timestamp`: The time of the sample as an RFC 3339 timestamp, e.g. "2024-05-01T13:37:00Z".
+///
+/// # Returns
+/// The path of the file.
+fn get_file_path(out: &Path, rotate: Rotation, timestamp: &str) -> PathBuf {
+    let period: &str = match rotate {
+        Rotation::Daily => &timestamp[..10],
+        Rotation::Hourly => &timestamp[..13],
+    };
+    out.join(format!("{}{}.{}", FILE_PREFIX, period, FILE_EXTENSION))
+}
+
+
+/// Appends a sample to its file, one JSON object per connection with the time of the sample as `collected_at`
+/// and the metadata of the machine (e.g. `host`), so that records of many machines can be combined.
+///
+/// # Arguments
+/// * `path`: The file.
+/// * `connections`: The connections of the sample.
+/// * `timestamp`: The time of the sample as an RFC 3339 timestamp.
+/// * `metadata`: The metadata of the machine, see `json_output::get_machine_metadata`.
+///
+/// # Returns
+/// An error if the file can't be written.
+fn write_sample(path: &Path, connections: &[connections::Connection], timestamp: &str, metadata: &Map<String, Value>) -> std::io::Result<()> {
+    let mut lines: String = String::new();
+    for connection in connections {
+        let mut line: Map<String, Value> = Map::new();
+        line.insert("collected_at".to_string(), Value::from(timestamp));
+        line.extend(metadata.clone());
+        if let Ok(Value::Object(fields)) = serde_json::to_value(connection) {
+            line.extend(fields);
+        }
+        lines.push_str(&Value::Object(line).to_string());
+        lines.push('\n');
+    }
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(lines.as_bytes())
+}
+
+
+/// Deletes the files written by `somo record` which weren't modified within the retention period. Other files are kept.
+///
+/// # Arguments
+/// * `out`: The directory of the files.
+/// * `retention`: How long files are kept.
+///
+/// # Returns
+/// None
+fn delete_expired_files(out: &Path, retention: Duration) {
+    let Ok(entries) = fs::read_dir(out) else { return };
+    for entry in entries.flatten() {
+        let name: String = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(FILE_PREFIX) || !name.ends_with(FILE_EXTENSION) {
+            continue;
+        }
+        let age: Option<Duration> = entry.metadata().and_then(|metadata| metadata.modified()).ok().and_then(|modified| modified.elapsed().ok());
+        if age.is_some_and(|age| age > retention) {
+            if let Err(error) = fs::remove_file(entry.path()) {
+                string_utils::pretty_print_warning(&format!("Couldn't delete the expired recording '{}': {}", entry.path().display(), error));
+            }
+        }
+    }
+}
+
+
+/// Samples the connections periodically and appends them to rotating NDJSON files until somo is interrupted.
+///
+/// # Arguments
+/// * `filter_options`: The filter options provided by the user.
+/// * `options`: The output directory, interval, rotation and retention.
+///
+/// # Returns
+/// Never, exits if the output directory can't be created.
+pub fn record_connections(filter_options: &FilterOptions, options: &RecordOptions) -> ! {
+    if let Err(error) = fs::create_dir_all(&options.out) {
+        string_utils::pretty_print_error(&format!("Couldn't create the directory '{}': {}", options.out.display(), error));
+        process::exit(1);
+    }
+    string_utils::pretty_print_info(&format!("Recording the connections to {}, press Ctrl+C to stop.", options.out.display()));
+
+    let metadata: Map<String, Value> = json_output::get_machine_metadata();
+    loop {
+        match connections::get_all_connections(filter_options) {
+            Ok(connections) => {
+                let timestamp: String = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+                let path: PathBuf = get_file_path(&options.out, options.rotate, &timestamp);
+                if let Err(error) = write_sample(&path, &connections, &timestamp, &metadata) {
+                    string_utils::pretty_print_warning(&format!("Couldn't write to '{}': {}", path.display(), error));
+                }
+            }
+            Err(error) => string_utils::pretty_print_warning(&format!("Couldn't get the connections: {}", error)),
+        }
+        if let Some(retention) = options.retention {
+            delete_expired_files(&options.out, retention);
+        }
+        thread::sleep(options.interval);
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+use crate::connections;
+use crate::string_utils;
+
+include!(concat!(env!("OUT_DIR"), "/fallback_services.rs"));
+
+/// The default location of the services database which maps ports to the names of the services using them.
+static SERVICES_PATH: &str = "/etc/services";
+
+/// The range of local ports the kernel picks from for outgoing connections (and unbound sockets), e.g. "32768 60999".
+static LOCAL_PORT_RANGE_PATH: &str = "/proc/sys/net/ipv4/ip_local_port_range";
+
+/// TCP ports commonly used by containers, development servers and cluster components which have no (or only a generic) IANA name.
+static EXTENDED_PORTS: [(u16, &str); 32] = [
+    (2375, "docker api"),
+    (2376, "docker api tls"),
+    (2379, "etcd"),
+    (2380, "etcd peer"),
+    (3000, "node dev"),
+    (3306, "mysql"),
+    (4200, "angular dev"),
+    (4317, "otlp grpc"),
+    (4318, "otlp http"),
+    (5000, "flask dev"),
+    (5173, "vite dev"),
+    (5432, "postgres"),
+    (5601, "kibana"),
+    (5672, "amqp"),
+    (6379, "redis"),
+    (6443, "kubernetes api"),
+    (8000, "http dev"),
+    (8080, "http alt"),
+    (8200, "vault"),
+    (8443, "https alt"),
+    (8500, "consul"),
+    (8888, "jupyter"),
+    (9090, "prometheus"),
+    (9092, "kafka"),
+    (9100, "node exporter"),
+    (9200, "elasticsearch"),
+    (9300, "elasticsearch cluster"),
+    (10250, "kubelet"),
+    (10256, "kube-proxy health"),
+    (11211, "memcached"),
+    (15672, "rabbitmq management"),
+    (27017, "mongodb"),
+];
+
+/// Maps ports and protocols to the names of the services using them, e.g. 5432/tcp to "postgresql".
+#[derive(Debug, Default)]
+pub struct Services {
+    names: HashMap<(u16, String), String>,
+    ephemeral_ports: Option<RangeInclusive<u16>>
+}
+
+
+/// Parses a services database in the format of `/etc/services`, i.e. one "name port/proto [aliases...]" entry per line.
+/// Comments (starting with `#`) are ignored, as are invalid lines.
+///
+/// # Arguments
+/// * `content`: The content of the database.
+///
+/// # Returns
+/// The names of the services by their port and protocol. If there are multiple entries for a port, the first one is used.
+fn parse_services(content: &str) -> HashMap<(u16, String), String> {
+    let mut names: HashMap<(u16, String), String> = HashMap::new();
+    for line in content.lines() {
+        let mut fields = line.split('#').next().unwrap_or_default().split_whitespace();
+        let (Some(name), Some(port_proto)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some((port, proto)) = port_proto.split_once('/') else {
+            continue;
+        };
+        if let Ok(port) = port.parse::<u16>() {
+            names.entry((port, proto.to_string())).or_insert_with(|| name.to_string());
+        }
+    }
+    names
+}
+
+
+/// Loads the services database, merging the entries of an override file (e.g. with the ports of internal services) over it.
+///
+/// # Arguments
+/// * `database_path`: The path of the database, if not set `$SOMO_SERVICES` or else `/etc/services` is used.
+/// * `override_path`: The path of a file in the same format whose entries replace the ones of the database, it is skipped if it doesn't exist.
+///
+/// # Returns
+/// The services, the compiled common IANA assignments if the database doesn't exist or can't be read (e.g. in minimal containers).
+/// A database which was explicitly set but can't be read is reported as a warning.
+pub fn load_services(database_path: Option<&Path>, override_path: Option<&Path>) -> Services {
+    let explicit_path: Option<PathBuf> = database_path.map(PathBuf::from).or_else(|| env::var_os("SOMO_SERVICES").map(PathBuf::from));
+    let path: PathBuf = explicit_path.clone().unwrap_or_else(|| PathBuf::from(SERVICES_PATH));
+
+    let mut names: HashMap<(u16, String), String> = match fs::read_to_string(&path) {
+        Ok(content) => parse_services(&content),
+        Err(error) => {
+            if explicit_path.is_some() {
+                string_utils::pretty_print_warning(&format!("Couldn't read services database '{}': {}", path.display(), error));
+            }
+            debug!("Using the compiled services database, {} can't be read: {}", path.display(), error);
+            FALLBACK_SERVICES.iter().map(|(port, proto, name)| ((*port, proto.to_string()), name.to_string())).collect()
+        }
+    };
+    if let Some(content) = override_path.and_then(|path| fs::read_to_string(path).ok()) {
+        names.extend(parse_services(&content));
+    }
+    Services { names, ephemeral_ports: None }
+}
+
+
+/// Gets the range of ephemeral ports of this host, i.e. the local ports the kernel assigns to outgoing connections.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The range or `None` if it can't be read.
+pub fn get_ephemeral_port_range() -> Option<RangeInclusive<u16>> {
+    let content: String = fs::read_to_string(LOCAL_PORT_RANGE_PATH).ok()?;
+    let mut ports = content.split_whitespace().map(|port| port.parse::<u16>());
+    match (ports.next()?, ports.next()?) {
+        (Ok(start), Ok(end)) => Some(start..=end),
+        _ => None
+    }
+}
+
+
+impl Services {
+    /// Adds the names of common container, development and cluster ports (e.g. 3000 "node dev" or 6443 "kubernetes api") for TCP ports which have no entry yet.
+    ///
+    /// # Returns
+    /// The services including the extended ports.
+    pub fn with_extended_ports(mut self) -> Self {
+        for (port, name) in EXTENDED_PORTS {
+            self.names.entry((port, "tcp".to_string())).or_insert_with(|| name.to_string());
+        }
+        self
+    }
+
+    /// Names the local ports of connections (but not of listeners) in a range "ephemeral" if they have no other name.
+    ///
+    /// # Arguments
+    /// * `range`: The range of ephemeral ports, e.g. of `get_ephemeral_port_range`.
+    ///
+    /// # Returns
+    /// The services including the ephemeral ports.
+    pub fn with_ephemeral_ports(mut self, range: RangeInclusive<u16>) -> Self {
+        self.ephemeral_ports = Some(range);
+        self
+    }
+
+    /// Finds the services of a port or the ports of a service name (case-insensitive), e.g. "5432" or "postgresql".
+    ///
+    /// # Arguments
+    /// * `query`: The port or the name of the service.
+    ///
+    /// # Returns
+    /// The matching ports, protocols and names, ordered by port and protocol.
+    pub fn lookup(&self, query: &str) -> Vec<(u16, &str, &str)> {
+        let port: Option<u16> = query.parse().ok();
+        let mut services: Vec<(u16, &str, &str)> = self.names.iter()
+            .filter(|((service_port, _), name)| Some(*service_port) == port || name.eq_ignore_ascii_case(query))
+            .map(|((service_port, proto), name)| (*service_port, proto.as_str(), name.as_str()))
+            .collect();
+        services.sort();
+        services
+    }
+
+    /// Gets the name of the service using a port.
+    ///
+    /// # Arguments
+    /// * `port`: The port.
+    /// * `proto`: The protocol of the socket, UDP-Lite sockets use the names of UDP.
+    ///
+    /// # Returns
+    /// The name of the service or `None` if the port isn't known.
+    pub fn get(&self, port: u16, proto: &str) -> Option<&str> {
+        let proto: &str = if proto == "udplite" { "udp" } else { proto };
+        self.names.get(&(port, proto.to_string())).map(String::as_str)
+    }
+}
+
+
+/// Adds the names of the services using the local and remote ports to the connections, e.g. "postgresql" for 5432.
+/// Local ports of connections in the range of ephemeral ports (if set) without another name are named "ephemeral",
+/// unless a socket listens on them, e.g. the accepted connections of a server listening on a port in the range.
+/// Raw sockets are skipped since their ports are protocol numbers, as are unset ports (0).
+///
+/// # Arguments
+/// * `connections`: The connections to annotate.
+/// * `services`: The services database.
+///
+/// # Returns
+/// None
+pub fn annotate_services(connections: &mut [connections::Connection], services: &Services) {
+    let listening_ports: HashSet<(String, u16)> = connections.iter()
+        .filter(|connection| connections::is_listening(connection))
+        .map(|connection| (connection.proto.clone(), connection.local_port))
+        .collect();
+    for connection in connections.iter_mut().filter(|connection| connection.proto != "raw") {
+        let get_name = |port: u16| if port == 0 { None } else { services.get(port, &connection.proto).map(String::from) };
+        let is_ephemeral: bool = !listening_ports.contains(&(connection.proto.clone(), connection.local_port))
+            && services.ephemeral_ports.as_ref().is_some_and(|range| range.contains(&connection.local_port));
+        connection.local_service = get_name(connection.local_port).or_else(|| is_ephemeral.then(|| "ephemeral".to_string()));
+        connection.remote_service = get_name(connection.remote_port);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_services() {
+        let services = Services { ephemeral_ports: None, names: parse_services(concat!(
+            "# Network services, Internet style\n",
+            "ssh             22/tcp                          # SSH Remote Login Protocol\n",
+            "postgresql      5432/tcp        postgres\n",
+            "domain          53/udp\n",
+            "invalid         port/tcp\n",
+            "alias           22/tcp\n",
+        )) };
+        assert_eq!(services.get(22, "tcp"), Some("ssh"));
+        assert_eq!(services.get(5432, "tcp"), Some("postgresql"));
+        assert_eq!(services.get(53, "udplite"), Some("domain"));
+        assert_eq!(services.get(53, "tcp"), None);
+    }
+}
@@ -0,0 +1,104 @@
+use log::debug;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::connections::{self, Connection};
+use crate::FixtureSource;
+
+/// Gets the path of the file storing the last printed connections of the current terminal session,
+/// i.e. `$XDG_CACHE_HOME/somo/snapshots/<session id>.json` or `~/.cache/somo/snapshots/<session id>.json`.
+/// The session ID (see `man getsid`) is shared by all processes started from the same terminal.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The path of the snapshot or `None` if neither `XDG_CACHE_HOME` nor `HOME` is set.
+fn get_snapshot_path() -> Option<PathBuf> {
+    let cache_directory = match env::var("XDG_CACHE_HOME") {
+        Ok(directory) => PathBuf::from(directory),
+        Err(_) => PathBuf::from(env::var("HOME").ok()?).join(".cache"),
+    };
+    let session_id: i32 = unsafe { libc::getsid(0) };
+    Some(cache_directory.join("somo").join("snapshots").join(format!("{}.json", session_id)))
+}
+
+
+/// Stores the printed connections in their order, so that a later run can refer to them by their index, e.g. using `somo kill 7`.
+/// Failing to write the snapshot is not considered an error.
+///
+/// # Arguments
+/// * `connections`: The connections as printed.
+///
+/// # Returns
+/// None
+pub fn save_snapshot(connections: &[Connection]) {
+    let Some(path) = get_snapshot_path() else { return };
+    let Ok(content) = serde_json::to_string(connections) else { return };
+
+    if let Some(directory) = path.parent() {
+        let _ = fs::create_dir_all(directory);
+    }
+    if let Err(error) = fs::write(&path, content) {
+        debug!("Couldn't write the snapshot {}: {}", path.display(), error);
+    }
+}
+
+
+/// Reads the snapshot of the current terminal session.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The path and content of the snapshot or an error if there is none.
+fn read_snapshot() -> Result<(PathBuf, String), String> {
+    let path: PathBuf = get_snapshot_path().ok_or("Couldn't find the cache directory, neither XDG_CACHE_HOME nor HOME is set.")?;
+    let content: String = fs::read_to_string(&path).map_err(|_| "There is no previous output in this terminal session, run somo first.".to_string())?;
+    Ok((path, content))
+}
+
+
+/// Gets the connections of the last output of the current terminal session as a source, used by `somo last`.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The source or an error if there is no snapshot or it can't be read.
+pub fn get_snapshot_source() -> Result<FixtureSource, String> {
+    let (path, content) = read_snapshot()?;
+    debug!("Replaying the snapshot {}", path.display());
+    FixtureSource::from_json(&content).map_err(|error| format!("Couldn't read the snapshot '{}': {}", path.display(), error))
+}
+
+
+/// Gets a connection of the last output of the current terminal session by its index.
+///
+/// # Arguments
+/// * `index`: The (1-based) index of the connection, as shown in the `#` column.
+///
+/// # Returns
+/// The connection or an error if there is no snapshot or it has no such row.
+pub fn get_snapshot_connection(index: usize) -> Result<Connection, String> {
+    let source: FixtureSource = get_snapshot_source()?;
+    let connections: &[Connection] = source.connections();
+
+    index.checked_sub(1).and_then(|idx| connections.get(idx)).cloned()
+        .ok_or(format!("The previous output has no connection #{}, it has {} connection(s).", index, connections.len()))
+}
+
+
+/// Gets a connection of the last output of the current terminal session by its ID, as shown with `--id`.
+///
+/// # Arguments
+/// * `id`: The ID of the connection, e.g. "3fa2b1c0".
+///
+/// # Returns
+/// The connection or an error if there is no snapshot or it has no connection with the ID.
+pub fn get_snapshot_connection_by_id(id: &str) -> Result<Connection, String> {
+    let source: FixtureSource = get_snapshot_source()?;
+    source.connections().iter().find(|connection| connections::get_connection_id(connection) == id.to_lowercase()).cloned()
+        .ok_or(format!("The previous output has no connection with the ID {}.", id))
+}
@@ -0,0 +1,159 @@
+use std::fs;
+use std::time::Instant;
+
+use log::info;
+use serde_json::Value;
+
+use crate::address_checkers;
+use crate::connections::{self, Connection, FilterOptions, Result, Timings};
+use crate::string_utils;
+
+/// A backend which collects the connections of the system, e.g. the Linux `/proc` filesystem.
+/// Other backends can be added by implementing this trait, e.g. for other operating systems or for tests.
+pub trait ConnectionSource {
+    /// Gets all connections which match the filter options.
+    ///
+    /// # Arguments
+    /// * `filter_options`: The filter options provided by the user.
+    ///
+    /// # Returns
+    /// The matching connections or an error if the backend can't be read.
+    fn get_connections(&self, filter_options: &FilterOptions) -> Result<Vec<Connection>>;
+
+    /// Like `get_connections`, but also measures how long each phase took.
+    /// Backends which can't tell the phases apart count all of the time as filtering.
+    ///
+    /// # Arguments
+    /// * `filter_options`: The filter options provided by the user.
+    /// * `timings`: The durations of the phases are added to it.
+    ///
+    /// # Returns
+    /// The matching connections or an error if the backend can't be read.
+    fn get_connections_timed(&self, filter_options: &FilterOptions, timings: &mut Timings) -> Result<Vec<Connection>> {
+        let start: Instant = Instant::now();
+        let connections = self.get_connections(filter_options);
+        timings.filtering += start.elapsed();
+        connections
+    }
+}
+
+
+/// Collects the connections from the socket tables and processes in `/proc`.
+#[derive(Debug, Default)]
+pub struct ProcfsSource;
+
+impl ConnectionSource for ProcfsSource {
+    fn get_connections(&self, filter_options: &FilterOptions) -> Result<Vec<Connection>> {
+        connections::get_all_connections(filter_options)
+    }
+
+    fn get_connections_timed(&self, filter_options: &FilterOptions, timings: &mut Timings) -> Result<Vec<Connection>> {
+        info!("Collecting the connections from /proc");
+        connections::get_all_connections_timed(filter_options, timings)
+    }
+}
+
+
+/// Replays previously collected connections, e.g. the output of `--json-envelope`, for deterministic tests or to inspect another machine.
+/// ```
+/// use somo::{ConnectionSource, FilterOptions, FixtureSource};
+///
+/// let fixture = FixtureSource::from_json(r#"[{
+///     "proto": "tcp", "family": "ipv4", "local_address": "0.0.0.0", "local_port": "22", "remote_address": "0.0.0.0", "remote_port": "0",
+///     "program": "sshd", "pid": "812", "ppid": "1", "pprogram": "systemd", "state": "listen", "uid": 0, "user": "root", "tty": "-",
+///     "login_session": null, "tunnel": null, "container": null, "address_type": "unspecified",
+///     "address_scope": "unspecified", "threat_score": null,
+///     "country": null, "city": null, "asn": null, "org": null, "blocked": false
+/// }]"#)?;
+/// assert_eq!(fixture.get_connections(&FilterOptions::new().local_port("22"))?.len(), 1);
+/// assert!(fixture.get_connections(&FilterOptions::new().proto("udp"))?.is_empty());
+/// # Ok::<(), somo::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct FixtureSource {
+    connections: Vec<Connection>,
+}
+
+impl FixtureSource {
+    /// Parses the connections from JSON, either an array of connections or an object containing them as `connections` (like the output of `--json-envelope`).
+    ///
+    /// # Arguments
+    /// * `json`: The JSON text.
+    ///
+    /// # Returns
+    /// The source or an error if the JSON doesn't contain valid connections.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut value: Value = serde_json::from_str(json)?;
+        if let Some(connections) = value.get_mut("connections") {
+            value = connections.take();
+        }
+        // connections collected by older versions have no family and address scope, they're derived from their addresses
+        for connection in value.as_array_mut().into_iter().flatten().filter_map(Value::as_object_mut) {
+            if !connection.contains_key("family") {
+                let local_address: &str = connection.get("local_address").and_then(Value::as_str).unwrap_or_default();
+                let family: &str = if local_address.contains(':') { "ipv6" } else { "ipv4" };
+                connection.insert("family".to_string(), Value::from(family));
+            }
+            if !connection.contains_key("address_scope") {
+                let remote_address: Option<&str> = connection.get("remote_address").and_then(Value::as_str);
+                if let Some(remote_address) = remote_address.and_then(string_utils::parse_remote_address) {
+                    connection.insert("address_scope".to_string(), Value::from(address_checkers::get_address_scope(&remote_address).as_str()));
+                }
+            }
+        }
+        Ok(FixtureSource { connections: serde_json::from_value(value)? })
+    }
+
+    /// Reads the connections from a JSON file, see `from_json`.
+    ///
+    /// # Arguments
+    /// * `path`: The path of the file.
+    ///
+    /// # Returns
+    /// The source or an error if the file can't be read or doesn't contain valid connections.
+    pub fn from_file(path: &str) -> Result<Self> {
+        info!("Replaying the connections of {}", path);
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Gets all connections of the source in their original order, without applying any filter options (see `get_connections`).
+    pub fn connections(&self) -> &[Connection] {
+        &self.connections
+    }
+}
+
+impl ConnectionSource for FixtureSource {
+    fn get_connections(&self, filter_options: &FilterOptions) -> Result<Vec<Connection>> {
+        Ok(self.connections.iter()
+            .filter(|connection| filter_options.reads_protocol(&connection.proto))
+            .cloned()
+            .map(|mut connection| {
+                if filter_options.collapse_v4mapped {
+                    connections::collapse_v4mapped_addresses(&mut connection);
+                }
+                connection
+            })
+            .filter(|connection| !(filter_options.exclude_ipv6 && connection.local_address.is_ipv6()))
+            .filter(|connection| !connections::filter_out_connection(connection, filter_options))
+            .collect())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address_checkers::AddressScope;
+    use crate::connections::Family;
+
+    #[test]
+    fn test_from_json_backfills_fields_of_older_versions() {
+        // a connection written before the family and the address scope existed, e.g. in an old snapshot
+        let fixture = FixtureSource::from_json(r#"[{
+            "proto": "tcp", "local_address": "[::ffff:10.0.0.2]", "local_port": "5000", "remote_address": "[::ffff:100.72.1.9]", "remote_port": "443",
+            "program": "curl", "pid": "812", "state": "established", "uid": 0, "user": "root", "tty": "-", "address_type": "extern", "blocked": false
+        }]"#).unwrap();
+        assert_eq!(fixture.connections()[0].family, Family::Ipv6);
+        assert_eq!(fixture.connections()[0].address_scope, AddressScope::Cgnat);
+    }
+}
@@ -1,5 +1,34 @@
+#[cfg(feature = "cli")]
 use termimad::crossterm::style::{Color::*, Attribute::*};
+#[cfg(feature = "cli")]
 use termimad::*;
+use std::net::IpAddr;
+use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// If set, infos and warnings aren't printed so that only the actual output (e.g. the table) remains.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// If not set, all output is printed without colors and other ANSI styles.
+static COLORS: AtomicBool = AtomicBool::new(true);
+
+/// If set, errors are printed as JSON objects, so that programs consuming machine-readable output can handle them.
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// The format of IPv6 addresses in all output, stored as the discriminant of `IpFormat`.
+static IP_FORMAT: AtomicU8 = AtomicU8::new(IpFormat::Bracketed as u8);
+
+/// Represents how IPv6 addresses are displayed, IPv4 addresses are always displayed the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum IpFormat {
+    /// Compressed and wrapped in brackets, e.g. "[::1]"
+    Bracketed,
+    /// All eight groups without brackets, e.g. "0:0:0:0:0:0:0:1"
+    Plain,
+    /// Compressed as recommended by RFC 5952 without brackets, e.g. "::1"
+    Compressed,
+}
 
 
 /// Splits a string combined of an IP address and port with a ":" delimiter into two parts.
@@ -9,11 +38,10 @@ use termimad::*;
 /// 
 /// # Example
 /// ```
-/// let address_port_1 = "127.0.0.1:5432".to_string();
-/// assert_eq!(split_address(address_port_1), Some(("5432", "127.0.0.1")));
+/// use somo::string_utils::split_address;
 /// 
-/// let address_port_2 = "fails.com".to_string();
-/// assert_eq!(split_address(address_port_2), None);
+/// assert_eq!(split_address("127.0.0.1:5432"), Some(("127.0.0.1", "5432")));
+/// assert_eq!(split_address("fails.com"), None);
 /// ```
 /// 
 /// # Retunrs
@@ -37,11 +65,10 @@ pub fn split_address(address: &str) -> Option<(&str, &str)> {
 /// 
 /// # Example
 /// ```
-/// let address_port_1 = "127.0.0.1:5432".to_string();
-/// assert_eq!(get_address_parts(address_port_1), ("5432", "127.0.0.1"));
+/// use somo::string_utils::get_address_parts;
 /// 
-/// let address_port_2 = "fails.com".to_string();
-/// assert_eq!(get_address_parts(address_port_1), ("-", "127.0.0.1"));
+/// assert_eq!(get_address_parts("127.0.0.1:5432"), ("127.0.0.1".to_string(), "5432".to_string()));
+/// assert_eq!(get_address_parts("fails.com"), ("fails.com".to_string(), "-".to_string()));
 /// ```
 /// 
 /// # Returns
@@ -61,57 +88,299 @@ pub fn get_address_parts(address: &str) -> (String, String) {
 }
 
 
-/// Converts an array of bytes in to string character.
+/// Parses a remote address as displayed in the table (IPv6 addresses are wrapped in brackets) or as written elsewhere into an `IpAddr`.
+/// IPv6 addresses may also be bare and contain a zone ID (e.g. "fe80::1%eth0"), which is dropped since sockets are matched by address only.
 /// 
 /// # Arguments
-/// * `char_bytes`: The character encoding as an UTF-8 byte sequence.
+/// * `remote_address`: The address to parse, e.g "127.0.0.1", "[::1]" or "::1".
+/// 
+/// # Example
+/// ```
+/// use std::net::{IpAddr, Ipv6Addr};
+/// use somo::string_utils::parse_remote_address;
+/// 
+/// assert_eq!(parse_remote_address("[::1]"), Some(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+/// assert_eq!(parse_remote_address("::1"), parse_remote_address("[::1]"));
+/// assert_eq!(parse_remote_address("[fe80::1%eth0]"), "fe80::1".parse().ok());
+/// assert_eq!(parse_remote_address("fails.com"), None);
+/// ```
 /// 
 /// # Returns
-/// The string decoded from the UTF-8 byte sequence.
-pub fn str_from_bytes(char_bytes: &[u8]) -> String {
-    let s = std::str::from_utf8(char_bytes).expect("Invalid UTF-8 sequence");
-    return s.chars().next().expect("Empty string").to_string();
+/// The parsed IP address or `None` if the string isn't a valid address.
+pub fn parse_remote_address(remote_address: &str) -> Option<IpAddr> {
+    let address: &str = remote_address.trim_start_matches('[').trim_end_matches(']');
+    match address.split_once('%') {
+        Some((address, _zone_id)) if address.contains(':') => address.parse().ok(),
+        _ => address.parse().ok()
+    }
 }
 
 
-/// creates a row which consists of empty characters to fill out the terminal width 
-/// with respect to how much space each column should receive based on the content length
+/// Normalizes an IP address for comparing it to other addresses, i.e. IPv4-mapped IPv6 addresses (e.g. "::ffff:10.0.0.1" of dual-stack sockets) become IPv4 addresses.
+/// 
+/// # Arguments
+/// * `address`: The address to normalize.
+/// 
+/// # Example
+/// ```
+/// use somo::string_utils::{normalize_address, parse_remote_address};
+/// 
+/// assert_eq!(normalize_address(&parse_remote_address("[::ffff:10.0.0.1]").unwrap()), parse_remote_address("10.0.0.1").unwrap());
+/// ```
+/// 
+/// # Returns
+/// The normalized address.
+pub fn normalize_address(address: &IpAddr) -> IpAddr {
+    address.to_canonical()
+}
+
+
+/// Formats an IP address as displayed in all output, i.e. IPv6 addresses in the format selected with `set_ip_format` (by default wrapped in brackets).
+/// 
+/// # Arguments
+/// * `address`: The address to format.
+/// 
+/// # Example
+/// ```
+/// use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+/// use somo::string_utils::format_address;
+/// 
+/// assert_eq!(format_address(&IpAddr::V4(Ipv4Addr::LOCALHOST)), "127.0.0.1");
+/// assert_eq!(format_address(&IpAddr::V6(Ipv6Addr::LOCALHOST)), "[::1]");
+/// ```
+/// 
+/// # Returns
+/// The formatted address.
+pub fn format_address(address: &IpAddr) -> String {
+    let ip_format: IpFormat = match IP_FORMAT.load(Ordering::Relaxed) {
+        value if value == IpFormat::Plain as u8 => IpFormat::Plain,
+        value if value == IpFormat::Compressed as u8 => IpFormat::Compressed,
+        _ => IpFormat::Bracketed,
+    };
+    format_address_as(address, ip_format)
+}
+
+
+/// Formats an IP address in a specific format, independent of the format selected for the output.
+/// 
+/// # Arguments
+/// * `address`: The address to format.
+/// * `ip_format`: The format of IPv6 addresses.
+/// 
+/// # Example
+/// ```
+/// use std::net::{IpAddr, Ipv6Addr};
+/// use somo::string_utils::{format_address_as, IpFormat};
 /// 
+/// let address = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+/// assert_eq!(format_address_as(&address, IpFormat::Bracketed), "[2001:db8::1]");
+/// assert_eq!(format_address_as(&address, IpFormat::Plain), "2001:db8:0:0:0:0:0:1");
+/// assert_eq!(format_address_as(&address, IpFormat::Compressed), "2001:db8::1");
+/// ```
+/// 
+/// # Returns
+/// The formatted address.
+pub fn format_address_as(address: &IpAddr, ip_format: IpFormat) -> String {
+    match (address, ip_format) {
+        (IpAddr::V4(address), _) => address.to_string(),
+        (IpAddr::V6(address), IpFormat::Bracketed) => format!("[{}]", address),
+        (IpAddr::V6(address), IpFormat::Plain) => address.segments().iter().map(|segment| format!("{:x}", segment)).collect::<Vec<String>>().join(":"),
+        (IpAddr::V6(address), IpFormat::Compressed) => address.to_string(),
+    }
+}
 
 
-/// Creates a Markdown table row with just empty characters with the width of the terminal window.
+/// Formats an optional PID as displayed in the table, i.e. "-" if the PID is unknown.
 /// 
-/// # Argument
-/// * `terminal_width`: The current width of the terminal.
-/// * `max_column_spaces`: An array in which the values represent the max-width of each of the 7 Markdown table rows.
+/// # Arguments
+/// * `pid`: The PID or `None` if it is unknown.
 /// 
 /// # Returns
-/// A Markdown table row string in which each column is filled with as much empty characters needed to fit in content and as well fill out the terminal width.
-pub fn fill_terminal_width(terminal_width: u16, max_column_spaces: [u16; 7]) -> String {
-    let total_column_spaces: u16 = max_column_spaces.iter().sum();
+/// The formatted PID.
+pub fn format_pid(pid: Option<i32>) -> String {
+    pid.map_or("-".to_string(), |pid| pid.to_string())
+}
+
+
+/// Formats an uptime in seconds with its two largest units, e.g. "3h 12m", or "-" if it is unknown.
+/// ```
+/// assert_eq!(somo::string_utils::format_uptime(Some(11_520)), "3h 12m");
+/// assert_eq!(somo::string_utils::format_uptime(Some(42)), "42s");
+/// ```
+///
+/// # Arguments
+/// * `uptime`: The uptime in seconds or `None` if it is unknown.
+///
+/// # Returns
+/// The formatted uptime.
+pub fn format_uptime(uptime: Option<u64>) -> String {
+    let Some(seconds) = uptime else {
+        return "-".to_string();
+    };
+    let units: [(u64, &str); 4] = [(seconds / 86400, "d"), (seconds / 3600 % 24, "h"), (seconds / 60 % 60, "m"), (seconds % 60, "s")];
+    let first: usize = units.iter().position(|(value, _)| *value > 0).unwrap_or(units.len() - 1);
+    units[first..].iter().take(2)
+        .filter(|(value, _)| *value > 0 || first == units.len() - 1)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
 
-    let calculate_column_width = |column_space: u16| ((column_space as f64 / total_column_spaces as f64) * (terminal_width as f64));
-    let empty_character: String = str_from_bytes(&[0xE2, 0xA0, 0x80]);
 
-    let mut row: String = String::new();
-    for &max_column_space in &max_column_spaces {
-        row.push_str(&format!("| {} ", empty_character.repeat(calculate_column_width(max_column_space) as usize)));
+/// Formats a throughput in bytes per second with a binary unit, e.g. "1.5 KiB/s", or "-" if it is unknown.
+/// ```
+/// assert_eq!(somo::string_utils::format_byte_rate(Some(1536)), "1.5 KiB/s");
+/// assert_eq!(somo::string_utils::format_byte_rate(Some(512)), "512 B/s");
+/// ```
+///
+/// # Arguments
+/// * `rate`: The bytes per second or `None` if it is unknown.
+///
+/// # Returns
+/// The formatted throughput.
+pub fn format_byte_rate(rate: Option<u64>) -> String {
+    let Some(rate) = rate else {
+        return "-".to_string();
+    };
+    let units: [&str; 4] = ["KiB/s", "MiB/s", "GiB/s", "TiB/s"];
+    if rate < 1024 {
+        return format!("{} B/s", rate);
     }
-    row.push_str("|\n");
+    let mut value: f64 = rate as f64 / 1024.0;
+    let mut unit: usize = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
+
 
-    row
+/// Gets the hostname of the machine.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The hostname or `None` if it can't be read.
+pub fn get_hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname").ok().map(|hostname| hostname.trim().to_string())
 }
 
 
-/// prints text with a green "Info" prefix
-/// a new style is given to common Markdown syntax:
-/// **bold** text -> bold and white
-/// *italic* text -> gray and not italic
-/// ~~strikeout~~ text -> green and not striked out
+/// Prints how long each phase of a run took to stderr, e.g. "Timing: process scan 4.21ms, socket read 0.35ms".
 /// 
+/// # Arguments
+/// * `phases`: The names and durations of the phases.
 /// 
+/// # Returns
+/// None
+pub fn print_timings(phases: &[(&str, Duration)]) {
+    let phases: Vec<String> = phases.iter().map(|(name, duration)| format!("{} {:.2?}", name, duration)).collect();
+    serr!("Timing: {}", phases.join(", "));
+}
+
+
+/// Suppresses all following infos and warnings, errors are still printed.
+/// 
+/// # Arguments
+/// * `quiet`: If `true` infos and warnings are suppressed.
+/// 
+/// # Returns
+/// None
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+
+/// Enables or disables colors and other ANSI styles for all following output.
+/// 
+/// # Arguments
+/// * `colors`: If `false` all output is printed without colors.
+/// 
+/// # Returns
+/// None
+pub fn set_colors(colors: bool) {
+    COLORS.store(colors, Ordering::Relaxed);
+}
+
+
+/// Selects the format of IPv6 addresses for all following output.
+/// 
+/// # Arguments
+/// * `ip_format`: The format of IPv6 addresses.
+/// 
+/// # Returns
+/// None
+pub fn set_ip_format(ip_format: IpFormat) {
+    IP_FORMAT.store(ip_format as u8, Ordering::Relaxed);
+}
+
+
+/// Prints all following errors as JSON objects instead of styled text, e.g. if the output is machine-readable.
+/// 
+/// # Arguments
+/// * `json_errors`: If `true` errors are printed as JSON.
+/// 
+/// # Returns
+/// None
+pub fn set_json_errors(json_errors: bool) {
+    JSON_ERRORS.store(json_errors, Ordering::Relaxed);
+}
+
+
+/// Prints out an error with a machine-readable code to stderr, as `{"error": {"code": "...", "message": "..."}}` if JSON errors are enabled.
+/// 
+/// # Arguments
+/// * `code`: The code identifying the kind of error, e.g. "connections".
+/// * `text`: The text to print to the console.
+/// 
+/// # Example
+/// ```
+/// use somo::string_utils::print_error;
+/// 
+/// // prints: Error: Couldn't get the connections: /proc isn't mounted
+/// print_error("connections", "Couldn't get the connections: /proc isn't mounted");
+/// ```
+/// 
+/// # Returns
+/// None
+pub fn print_error(code: &str, text: &str) {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        serr!("{}", serde_json::json!({ "error": { "code": code, "message": text } }));
+        return;
+    }
+    print_styled_error(text);
+}
+
+
+/// Checks if colors and other ANSI styles are enabled.
+pub fn colors_enabled() -> bool {
+    COLORS.load(Ordering::Relaxed)
+}
+
+
+/// Replaces a termimad "skin" with an unstyled one (keeping its alignments) if colors are disabled.
+/// 
+/// # Arguments
+/// * `skin`: The styled skin.
+/// 
+/// # Returns
+/// The styled skin if colors are enabled, an unstyled skin otherwise.
+#[cfg(feature = "cli")]
+pub fn apply_color_mode(skin: MadSkin) -> MadSkin {
+    if colors_enabled() {
+        return skin;
+    }
+    let mut plain_skin = MadSkin::no_style();
+    plain_skin.paragraph.align = skin.paragraph.align;
+    plain_skin.table.align = skin.table.align;
+    plain_skin
+}
+
 
 /// Prints out Markdown formatted text using a custom appearence / termimad "skin".
+/// Nothing is printed in quiet mode.
 /// 
 /// # Appearence
 /// * **bold** text -> bold and white
@@ -123,17 +392,22 @@ pub fn fill_terminal_width(terminal_width: u16, max_column_spaces: [u16; 7]) ->
 /// 
 /// # Returns
 /// None
+#[cfg(feature = "cli")]
 pub fn pretty_print_info(text: &str) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
     let mut skin = MadSkin::default();
     skin.bold.set_fg(White);
     skin.italic = CompoundStyle::new(Some(gray(11)), None, Encircled.into());
     skin.strikeout = CompoundStyle::new(Some(DarkGreen), None, Encircled.into());
 
+    let skin: MadSkin = apply_color_mode(skin);
     let markdown: String = format!("~~Info~~: *{}*", text);
-    print!("{}", skin.term_text(&markdown));
+    sout!("{}", skin.inline(&markdown));
 }
 
-/// Prints out Markdown formatted text using a custom appearence / termimad "skin".
+/// Prints out Markdown formatted text to stderr using a custom appearence / termimad "skin".
 /// 
 /// # Appearence
 /// * **bold** text -> bold and white
@@ -145,17 +419,20 @@ pub fn pretty_print_info(text: &str) {
 /// 
 /// # Returns
 /// None
-pub fn pretty_print_error(text: &str) {
+#[cfg(feature = "cli")]
+fn print_styled_error(text: &str) {
     let mut skin = MadSkin::default();
     skin.bold.set_fg(White);
     skin.italic = CompoundStyle::new(Some(gray(11)), None, Encircled.into());
     skin.strikeout = CompoundStyle::new(Some(Red), None, Encircled.into());
 
+    let skin: MadSkin = apply_color_mode(skin);
     let markdown: String = format!("~~Error~~: *{}*", text);
-    print!("{}", skin.term_text(&markdown));
+    serr!("{}", skin.inline(&markdown));
 }
 
-/// Prints out Markdown formatted text using a custom appearence / termimad "skin".
+/// Prints out Markdown formatted text to stderr using a custom appearence / termimad "skin".
+/// Nothing is printed in quiet mode.
 /// 
 /// # Appearence
 /// * **bold** text -> bold and white
@@ -167,12 +444,54 @@ pub fn pretty_print_error(text: &str) {
 /// 
 /// # Returns
 /// None
+#[cfg(feature = "cli")]
 pub fn pretty_print_warning(text: &str) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
     let mut skin = MadSkin::default();
     skin.bold.set_fg(White);
     skin.italic = CompoundStyle::new(Some(gray(11)), None, Encircled.into());
     skin.strikeout = CompoundStyle::new(Some(Yellow), None, Encircled.into());
 
+    let skin: MadSkin = apply_color_mode(skin);
     let markdown: String = format!("~~Warning~~: *{}*", text);
-    print!("{}", skin.term_text(&markdown));
-}
\ No newline at end of file
+    serr!("{}", skin.inline(&markdown));
+}
+
+
+/// Prints out an error to stderr, as JSON with the generic code "error" if JSON errors are enabled (see `print_error`).
+/// 
+/// # Arguments
+/// * `text`: The text to print to the console.
+/// 
+/// # Returns
+/// None
+pub fn pretty_print_error(text: &str) {
+    print_error("error", text);
+}
+
+
+/// Prints out an info without styling, since termimad is only available with the "cli" feature.
+/// Nothing is printed in quiet mode.
+#[cfg(not(feature = "cli"))]
+pub fn pretty_print_info(text: &str) {
+    if !QUIET.load(Ordering::Relaxed) {
+        sout!("Info: {}", text);
+    }
+}
+
+/// Prints out an error to stderr without styling, since termimad is only available with the "cli" feature.
+#[cfg(not(feature = "cli"))]
+fn print_styled_error(text: &str) {
+    serr!("Error: {}", text);
+}
+
+/// Prints out a warning to stderr without styling, since termimad is only available with the "cli" feature.
+/// Nothing is printed in quiet mode.
+#[cfg(not(feature = "cli"))]
+pub fn pretty_print_warning(text: &str) {
+    if !QUIET.load(Ordering::Relaxed) {
+        serr!("Warning: {}", text);
+    }
+}
@@ -0,0 +1,143 @@
+use clap::ValueEnum;
+use std::ffi::CString;
+use std::os::unix::net::UnixDatagram;
+use std::sync::Once;
+
+use crate::connections::Connection;
+use crate::string_utils;
+
+/// The socket on which journald receives entries using its native protocol.
+pub static JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Makes sure that `openlog` is only called once.
+static SYSLOG_INIT: Once = Once::new();
+
+/// Represents where connection events are written to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LogTarget {
+    Stdout,
+    /// The system logger, using the `daemon` facility
+    Syslog,
+    /// The systemd journal, with the connection as structured fields (e.g. `SOMO_PROGRAM`)
+    Journald,
+}
+
+/// Represents the priority of an event in the system log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    Notice,
+    Info,
+}
+
+impl Priority {
+    /// Gets the syslog priority value, which journald uses as well.
+    fn get_value(&self) -> i32 {
+        match self {
+            Priority::Notice => libc::LOG_NOTICE,
+            Priority::Info => libc::LOG_INFO,
+        }
+    }
+}
+
+
+/// Writes a message to syslog, identified as "somo" with its PID.
+///
+/// # Arguments
+/// * `priority`: The priority of the message.
+/// * `message`: The message.
+///
+/// # Returns
+/// None
+fn write_syslog(priority: Priority, message: &str) {
+    SYSLOG_INIT.call_once(|| unsafe { libc::openlog(c"somo".as_ptr(), libc::LOG_PID, libc::LOG_DAEMON) });
+    let Ok(message) = CString::new(message.replace('\0', "")) else {
+        return;
+    };
+    // the message is passed as an argument, so that `%` in it isn't interpreted as a format
+    unsafe { libc::syslog(priority.get_value(), c"%s".as_ptr(), message.as_ptr()) };
+}
+
+
+/// Appends a field to a journald entry. Values containing newlines are encoded with their length, as required by the native protocol.
+fn push_journald_field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+
+/// Gets the structured fields of a connection for the journal.
+///
+/// # Arguments
+/// * `event`: The event, e.g. "new" or "closed".
+/// * `connection`: The connection.
+///
+/// # Returns
+/// The field names and values.
+fn get_journald_fields(event: &str, connection: &Connection) -> Vec<(&'static str, String)> {
+    vec![
+        ("SOMO_EVENT", event.to_string()),
+        ("SOMO_PROTO", connection.proto.clone()),
+        ("SOMO_LOCAL_ADDRESS", string_utils::format_address(&connection.local_address)),
+        ("SOMO_LOCAL_PORT", connection.local_port.to_string()),
+        ("SOMO_REMOTE_ADDRESS", string_utils::format_address(&connection.remote_address)),
+        ("SOMO_REMOTE_PORT", connection.remote_port.to_string()),
+        ("SOMO_PROGRAM", connection.program.clone()),
+        ("SOMO_PID", connection.pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string())),
+        ("SOMO_STATE", connection.state.to_string()),
+        ("SOMO_USER", connection.user.clone()),
+    ]
+}
+
+
+/// Writes an entry to the systemd journal using its native protocol.
+///
+/// # Arguments
+/// * `priority`: The priority of the entry.
+/// * `message`: The message of the entry.
+/// * `fields`: Additional structured fields.
+///
+/// # Returns
+/// An error if the journal isn't available.
+fn write_journald(priority: Priority, message: &str, fields: &[(&str, String)]) -> std::io::Result<()> {
+    let mut entry: Vec<u8> = Vec::new();
+    push_journald_field(&mut entry, "MESSAGE", message);
+    push_journald_field(&mut entry, "PRIORITY", &priority.get_value().to_string());
+    push_journald_field(&mut entry, "SYSLOG_IDENTIFIER", "somo");
+    for (name, value) in fields {
+        push_journald_field(&mut entry, name, value);
+    }
+    UnixDatagram::unbound()?.send_to(&entry, JOURNALD_SOCKET)?;
+    Ok(())
+}
+
+
+/// Writes a connection event to a log target. On stdout the message is prefixed with the current time,
+/// in the journal the connection is attached as structured fields.
+///
+/// # Arguments
+/// * `target`: Where to write the event.
+/// * `priority`: The priority of the event in the system log.
+/// * `event`: The event, e.g. "new" or "closed".
+/// * `message`: The description of the event.
+/// * `connection`: The connection of the event.
+///
+/// # Returns
+/// None
+pub fn log_event(target: LogTarget, priority: Priority, event: &str, message: &str, connection: &Connection) {
+    match target {
+        LogTarget::Stdout => sout!("{} {}", humantime::format_rfc3339_seconds(std::time::SystemTime::now()), message),
+        LogTarget::Syslog => write_syslog(priority, message),
+        LogTarget::Journald => {
+            if let Err(error) = write_journald(priority, message, &get_journald_fields(event, connection)) {
+                string_utils::pretty_print_warning(&format!("Couldn't write to the journal: {}", error));
+            }
+        }
+    }
+}
@@ -1,9 +1,17 @@
-use termimad::crossterm::style::{Color::*, Attribute::*};
+use clap::ValueEnum;
+use termimad::crossterm::style::{Color, Color::*, Attribute::*};
+use termimad::minimad::Composite;
 use termimad::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
 
+use crate::columns::{Column, SortKey};
 use crate::connections;
 use crate::address_checkers;
+use crate::exposure::BindScope;
 use crate::string_utils;
+use crate::vsock;
 
 
 /// Uses the termimad crate to create a custom appearence for Mardown text in the console.
@@ -28,56 +36,199 @@ fn create_table_style() -> MadSkin {
     skin.table.align = Alignment::Center;
     skin.inline_code = CompoundStyle::new(Some(Yellow), None, Encircled.into());
 
-    skin
+    string_utils::apply_color_mode(skin)
 }
 
 
-/// Adds abusiveness information to the remote address based on the abuse score.
+/// The background of every second row if rows are striped, a dark gray which keeps all state colors readable.
+static ZEBRA_BACKGROUND: Color = AnsiValue(236);
+
+/// The width columns are shrunk to at most if the table doesn't fit, unless their content is narrower.
+static MIN_COLUMN_WIDTH: usize = 5;
+
+/// Represents a row of a table: the Markdown formatted cells, each with the color of its unstyled text (if any).
+type TableRow = Vec<(String, Option<Color>)>;
+
+/// Represents the characters used to draw the borders of tables and the branches of the process tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Borders {
+    Unicode,
+    Ascii
+}
+
+impl Borders {
+    /// Gets the characters of the top, middle and bottom rule of a table, each as the left end, the line, the junction and the right end.
+    fn rule_chars(&self) -> [[char; 4]; 3] {
+        match self {
+            Borders::Unicode => [['┌', '─', '┬', '┐'], ['├', '─', '┼', '┤'], ['└', '─', '┴', '┘']],
+            Borders::Ascii => [['+', '-', '+', '+']; 3]
+        }
+    }
+
+    /// Gets the character separating the cells of a row.
+    fn vertical_char(&self) -> char {
+        match self {
+            Borders::Unicode => '│',
+            Borders::Ascii => '|'
+        }
+    }
+
+    /// Gets the arrow between the local and the remote address of a connection in the process tree.
+    fn arrow(&self) -> &'static str {
+        match self {
+            Borders::Unicode => "→",
+            Borders::Ascii => "->"
+        }
+    }
+
+    /// Gets the arrow shown in the header of the column the table is sorted by, pointing up if it's sorted in ascending order.
+    fn sort_arrow(&self, descending: bool) -> &'static str {
+        match (self, descending) {
+            (Borders::Unicode, false) => "▲",
+            (Borders::Unicode, true) => "▼",
+            (Borders::Ascii, false) => "^",
+            (Borders::Ascii, true) => "v"
+        }
+    }
+
+    /// Gets the branches of the process tree: the branch and indentation of a child followed by those of the last child.
+    fn tree_branches(&self) -> [&'static str; 4] {
+        match self {
+            Borders::Unicode => ["├─", "│  ", "└─", "   "],
+            Borders::Ascii => ["|-", "|  ", "`-", "   "]
+        }
+    }
+}
+
+/// Represents how much the summary printed above the connections contains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SummaryMode {
+    /// The amount of connections per protocol and state, e.g. "148 connections (tcp 120 / udp 28) — 12 listen, 97 established"
+    Full,
+    /// Only the amount of connections
+    Count,
+    Off,
+}
+
+/// Represents the colors used to highlight connections by their state.
+#[derive(Debug)]
+pub struct Theme {
+    pub state_colors: Vec<(connections::State, Color)>,
+    pub color_rows: bool,
+    /// If `true` every second row of the tables is shaded.
+    pub zebra: bool
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            state_colors: vec![
+                (connections::State::Established, Green),
+                (connections::State::Listen, Blue),
+                (connections::State::TimeWait, Yellow),
+                (connections::State::SynSent, Red),
+            ],
+            color_rows: false,
+            zebra: false
+        }
+    }
+}
+
+/// Contains options for rendering the connections.
+#[derive(Debug)]
+pub struct TableOptions {
+    pub borders: Borders,
+    pub summary: SummaryMode,
+    pub theme: Theme,
+    pub width: Option<u16>,
+    pub truncate: Vec<(Column, usize)>,
+    /// If `true` tables are as wide as their content instead of being fitted to the width, and `truncate` is ignored.
+    pub no_truncate: bool,
+    /// The column the rows are sorted by and whether it's in descending order, marked with an arrow in its header.
+    pub sorted_by: Option<(Column, bool)>
+}
+
+impl TableOptions {
+    /// Gets the width tables are fitted to, i.e. the width selected by the user or the width of the terminal.
+    fn get_width(&self) -> u16 {
+        self.width.unwrap_or_else(|| terminal_size().0)
+    }
+
+    /// Gets the max length of the values of a column selected by the user, if any.
+    fn get_max_length(&self, column: Column) -> Option<usize> {
+        if self.no_truncate {
+            return None;
+        }
+        self.truncate.iter().find(|(truncated_column, _)| *truncated_column == column).map(|(_, max_length)| *max_length)
+    }
+}
+
+
+/// Adds abusiveness information to the remote address based on the threat score.
 /// 
-/// * `abuse_score` >= 50 -> high abuse confidence
-/// * `abuse_score` >= 25 -> moderate abuse confidence
-/// * `abuse_score` >=  1 -> low abuse confidence
-/// * `abuse_score` ==  0 -> no abuse danger
+/// * `threat_score` >= 50 -> high abuse confidence (the whole row is additionally colored red by `format_flagged_row`)
+/// * `threat_score` >= 25 -> moderate abuse confidence
+/// * `threat_score` >=  1 -> low abuse confidence
+/// * `threat_score` ==  0 -> no abuse danger
 /// 
 /// # Arguments
 /// * `remote_address`: The remote address checked for abusivness.
-/// * `abuse_score`: The abuse score delivered by AbuseIPDB.com
+/// * `threat_score`: The abuse score delivered by AbuseIPDB.com
 /// 
 /// 
-/// # Example
-/// ```
-/// let address = "127.0.0.1".to_string();
-/// let score = Some(75);
-/// let formatted = format_abuse_checked_address(&address, score);
-/// assert_eq!(formatted, "127.0.0.1 ~~high abuse score: 75~~"); 
-/// ```
-/// 
 /// # Returns
 /// A Markdown formatted string containing the remote address and abusiveness information.
-fn format_abuse_checked_address(remote_address: &String, abuse_score: Option<i64>) -> String {
-    let checked_remote_address: String;
-    if abuse_score >= Some(50) {
-        checked_remote_address = format!("{} ~~high abuse score: {}~~", remote_address, abuse_score.unwrap());
-    }
-    else if abuse_score > Some(25) {
-        checked_remote_address = format!("{} `moderate abuse score: {}`", remote_address, abuse_score.unwrap());
-    }
-    else if abuse_score >= Some(1) {
-        checked_remote_address = format!("{} *low abuse score: {}*", remote_address, abuse_score.unwrap());
+fn format_abuse_checked_address(remote_address: &String, threat_score: Option<i64>) -> String {
+    match threat_score {
+        Some(score) if score >= 50 => format!("{} *high abuse score: {}*", remote_address, score),
+        Some(score) if score >= 25 => format!("{} `moderate abuse score: {}`", remote_address, score),
+        Some(score) if score >= 1 => format!("{} *low abuse score: {}*", remote_address, score),
+        Some(_) => format!("{} **✓**", remote_address),
+        None => remote_address.to_string()
     }
-    else if abuse_score == Some(0) {
-        checked_remote_address = format!("{} **✓**", remote_address);
-    }
-    else {
-        checked_remote_address = (&remote_address).to_string();
+}
+
+
+/// Marks a remote address which is on the blocklist using Markdown formatting.
+/// 
+/// # Arguments
+/// * `remote_address`: The remote address.
+/// * `blocked`: If the address is on the blocklist.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the remote address and the blocklist information.
+fn format_blocked_address(remote_address: &String, blocked: bool) -> String {
+    if blocked {
+        return format!("{} *blocklisted*", remote_address);
     }
+    remote_address.to_string()
+}
+
 
-    checked_remote_address
+/// Checks if a connection was flagged, i.e. if the remote address has a high threat score (>= 50) or is on the blocklist.
+fn is_flagged(connection: &connections::Connection) -> bool {
+    connection.blocked || connection.threat_score >= Some(50)
 }
 
 
-/// Marks localhost and unspecified IP addresses (ie. 0.0.0.0) using Markdown formatting.
+/// Colors all cells of a table row red if its connection was flagged, i.e. if the remote address has a high threat score (>= 50) or is on the blocklist.
+/// 
+/// # Arguments
+/// * `cells`: The Markdown formatted cells of the row.
+/// * `connection`: The connection represented by the row.
+/// 
+/// # Returns
+/// The cells, each wrapped in a red style if the connection was flagged.
+fn format_flagged_row(cells: Vec<String>, connection: &connections::Connection) -> Vec<String> {
+    if is_flagged(connection) {
+        return cells.iter().map(|cell| format!("~~{}~~", cell)).collect();
+    }
+    cells
+}
+
 
+/// Marks localhost and unspecified IP addresses (ie. 0.0.0.0) using Markdown formatting.
+/// 
 /// * `address_type` == Localhost -> *italic* + "localhost" 
 /// * `address_type` == Unspecified -> *italic*
 /// * `address_type` == Extern -> not formatted
@@ -86,14 +237,6 @@ fn format_abuse_checked_address(remote_address: &String, abuse_score: Option<i64
 /// * `remote_address`: The remote address.
 /// * `address_type`: The address type as an IPType enum.
 /// 
-/// # Example
-/// ```
-/// let address = "127.0.0.1".to_string();
-/// let address_type = address_checkers::IPType::Localhost;
-/// let formatted = format_known_address(&address, &address_type);
-/// assert_eq!(formatted, "*127.0.0.1 localhost*"); 
-/// ```
-/// 
 /// # Returns
 /// A Markdown formatted string based on the address-type.
 fn format_known_address(remote_address: &String, address_type: &address_checkers::IPType) -> String {
@@ -110,48 +253,886 @@ fn format_known_address(remote_address: &String, address_type: &address_checkers
     }
 }
 
+
+/// Marks remote addresses which aren't on the internet (e.g. a peer in the LAN or behind carrier-grade NAT) with their scope using Markdown formatting.
+/// Loopback and unspecified addresses are already marked by `format_known_address` and public addresses aren't formatted.
+/// 
+/// # Arguments
+/// * `remote_address`: The remote address.
+/// * `address_scope`: The scope of the remote address.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the remote address and its scope.
+fn format_scope_address(remote_address: &String, address_scope: &address_checkers::AddressScope) -> String {
+    match address_scope {
+        address_checkers::AddressScope::LinkLocal | address_checkers::AddressScope::Private
+            | address_checkers::AddressScope::Cgnat | address_checkers::AddressScope::Multicast => {
+            format!("{} *({})*", remote_address, address_scope.as_str())
+        }
+        _ => remote_address.to_string()
+    }
+}
+
+
+/// Adds the name of a LAN peer (resolved using mDNS or LLMNR) to the remote address using Markdown formatting.
+/// 
+/// # Arguments
+/// * `remote_address`: The remote address.
+/// * `remote_name`: The name of the peer.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the remote address and the name of the peer.
+fn format_lan_name_address(remote_address: &String, remote_name: &Option<String>) -> String {
+    match remote_name {
+        Some(remote_name) => format!("{} *({})*", remote_address, remote_name),
+        None => remote_address.to_string()
+    }
+}
+
+/// Adds the country and city (if known) of the remote address using Markdown formatting.
+/// 
+/// # Arguments
+/// * `remote_address`: The remote address.
+/// * `country`: The ISO code of the country the address is located in.
+/// * `city`: The name of the city the address is located in.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the remote address and its location.
+fn format_location_address(remote_address: &String, country: &Option<String>, city: &Option<String>) -> String {
+    match (country, city) {
+        (Some(country), Some(city)) => format!("{} *({}, {})*", remote_address, country, city),
+        (Some(country), None) => format!("{} *({})*", remote_address, country),
+        _ => remote_address.to_string()
+    }
+}
+
+/// Adds the autonomous system number and organization (if known) of the remote address using Markdown formatting.
+/// 
+/// # Arguments
+/// * `remote_address`: The remote address.
+/// * `asn`: The number of the autonomous system the address belongs to.
+/// * `org`: The organization owning the autonomous system.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the remote address and its autonomous system.
+fn format_asn_address(remote_address: &String, asn: Option<u32>, org: &Option<String>) -> String {
+    match (asn, org) {
+        (Some(asn), Some(org)) => format!("{} *AS{} {}*", remote_address, asn, org),
+        (Some(asn), None) => format!("{} *AS{}*", remote_address, asn),
+        _ => remote_address.to_string()
+    }
+}
+
+/// Formats the controlling terminal and login session of the process owning a connection.
+/// 
+/// # Arguments
+/// * `tty`: The name of the controlling terminal, "-" if there is none.
+/// * `login_session`: The ID of the login session.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the terminal and the session.
+fn format_session(tty: &String, login_session: Option<u32>) -> String {
+    match login_session {
+        Some(login_session) => format!("{} *(session {})*", tty, login_session),
+        None => tty.to_string()
+    }
+}
+
+/// Marks the PID of a socket which is shared by several processes with the amount of other processes using Markdown formatting.
+/// 
+/// # Arguments
+/// * `pid`: The (formatted) PID of the owner of the socket.
+/// * `associated_pids`: The PIDs of all processes holding the socket.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the PID and the amount of other processes.
+fn format_shared_pid(pid: &String, associated_pids: &[i32]) -> String {
+    match associated_pids.len() {
+        0 | 1 => pid.to_string(),
+        amount => format!("{} *(shared +{})*", pid, amount - 1)
+    }
+}
+
+/// Marks the accept queue of a listener which is full, i.e. which drops new connections, using Markdown formatting.
+/// 
+/// # Arguments
+/// * `queue`: The (formatted) length of the accept queue and the backlog.
+/// * `accept_queue`: The amount of connections waiting to be accepted.
+/// * `backlog`: The maximum length of the accept queue.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the accept queue and whether it's full.
+fn format_accept_queue(queue: &String, accept_queue: Option<u32>, backlog: Option<u32>) -> String {
+    match (accept_queue, backlog) {
+        (Some(accept_queue), Some(backlog)) if accept_queue >= backlog => format!("{} ~~full~~", queue),
+        _ => queue.to_string()
+    }
+}
+
+/// Marks the port of a listener which belongs to a `SO_REUSEPORT` group with the group using Markdown formatting.
+/// 
+/// # Arguments
+/// * `port`: The (formatted) local port of the listener.
+/// * `reuseport_group`: The number of the group.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the port and the group.
+fn format_reuseport_group(port: &String, reuseport_group: Option<u32>) -> String {
+    match reuseport_group {
+        Some(group) => format!("{} *(reuseport #{})*", port, group),
+        None => port.to_string()
+    }
+}
+
+/// Adds the forwarding spec to the program of a socket which belongs to an ssh port forwarding using Markdown formatting.
+/// 
+/// # Arguments
+/// * `program`: The (formatted) program owning the socket.
+/// * `tunnel`: The forwarding spec of the ssh tunnel.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the program and the forwarding spec.
+fn format_tunnel_program(program: &String, tunnel: &Option<String>) -> String {
+    match tunnel {
+        Some(tunnel) => format!("{} `tunnel {}`", program, tunnel),
+        None => program.to_string()
+    }
+}
+
+/// Adds the container and target port a `docker-proxy` socket forwards to, to its program.
+/// 
+/// # Arguments
+/// * `program`: The (Markdown formatted) program.
+/// * `container`: The container and target port, e.g. "webapp:8080".
+/// * `borders`: The borders of the table, which determine the arrow.
+/// 
+/// # Returns
+/// A Markdown formatted string containing the program and the container.
+fn format_container_program(program: &String, container: &Option<String>, borders: &Borders) -> String {
+    match container {
+        Some(container) => format!("{} {} **{}**", program, borders.arrow(), container),
+        None => program.to_string()
+    }
+}
+
+/// Formats the header of a column using Markdown formatting.
+/// 
+/// # Arguments
+/// * `column`: The column.
+/// 
+/// # Returns
+/// The header in bold, the "/pid" part of the combined program/pid column in italic.
+fn format_header(column: &Column) -> String {
+    match column {
+        Column::ProgramPid => "**program***/pid*".to_string(),
+        _ => format!("**{}**", column.header())
+    }
+}
+
+/// Shortens a value to a max length (in characters), replacing the cut off end with an ellipsis.
+/// 
+/// # Arguments
+/// * `value`: The value to shorten.
+/// * `max_length`: The max length including the ellipsis, if the value should be shortened.
+/// 
+/// # Returns
+/// The shortened value.
+fn truncate_value(value: &str, max_length: Option<usize>) -> String {
+    match max_length {
+        Some(max_length) if value.chars().count() > max_length => {
+            let mut truncated: String = value.chars().take(max_length.saturating_sub(1)).collect();
+            truncated.push('…');
+            truncated
+        }
+        _ => value.to_string()
+    }
+}
+
+/// Adds the name of the service using a port to the port, e.g. "5432 *(postgresql)*".
+/// 
+/// # Arguments
+/// * `port`: The port.
+/// * `service`: The name of the service, if known.
+/// 
+/// # Returns
+/// The Markdown formatted port.
+fn format_service_port(port: &str, service: &Option<String>) -> String {
+    match service {
+        Some(service) => format!("{} *({})*", port, service),
+        None => port.to_string()
+    }
+}
+
+/// Formats the cell of a column for a connection using Markdown formatting.
+/// 
+/// # Arguments
+/// * `column`: The column.
+/// * `connection`: The connection represented by the row.
+/// * `index`: The (1-based) row index.
+/// * `options`: The options for rendering the table, containing the max lengths of the values.
+/// 
+/// # Returns
+/// The Markdown formatted cell content.
+fn format_cell(column: &Column, connection: &connections::Connection, index: usize, options: &TableOptions) -> String {
+    let max_length: Option<usize> = options.get_max_length(*column);
+    match column {
+        Column::Index => format!("*{}*", index),
+        Column::Proto if connection.dual_stack => format!("{} *(v4+v6)*", truncate_value(&connection.proto, max_length)),
+        Column::RemoteAddress => {
+            // add location, abusiveness and blocklist information to remote address
+            let remote_address: String = truncate_value(&string_utils::format_address(&connection.remote_address), max_length);
+            let mut formatted_remote_address: String = format_known_address(&remote_address, &connection.address_type);
+            formatted_remote_address = format_scope_address(&formatted_remote_address, &connection.address_scope);
+            formatted_remote_address = format_lan_name_address(&formatted_remote_address, &connection.remote_name);
+            formatted_remote_address = format_location_address(&formatted_remote_address, &connection.country, &connection.city);
+            formatted_remote_address = format_asn_address(&formatted_remote_address, connection.asn, &connection.org);
+            formatted_remote_address = format_abuse_checked_address(&formatted_remote_address, connection.threat_score);
+            format_blocked_address(&formatted_remote_address, connection.blocked)
+        }
+        Column::Program => {
+            let program: String = format_container_program(&truncate_value(&connection.program, max_length), &connection.container, &options.borders);
+            format_tunnel_program(&program, &connection.tunnel)
+        }
+        Column::ProgramPid => {
+            let program: String = truncate_value(&connection.program, max_length.or(options.get_max_length(Column::Program)));
+            let pid: String = format!("{}*/{}*", program, string_utils::format_pid(connection.pid));
+            let program: String = format_container_program(&format_shared_pid(&pid, &connection.associated_pids), &connection.container, &options.borders);
+            format_tunnel_program(&program, &connection.tunnel)
+        }
+        Column::Pid => format_shared_pid(&truncate_value(&column.value(connection, index), max_length), &connection.associated_pids),
+        Column::AcceptQueue => format_accept_queue(&truncate_value(&column.value(connection, index), max_length), connection.accept_queue, connection.backlog),
+        Column::Session => format_session(&truncate_value(&connection.tty, max_length), connection.login_session),
+        Column::LocalPort => {
+            let port: String = format_service_port(&truncate_value(&column.value(connection, index), max_length), &connection.local_service);
+            format_reuseport_group(&port, connection.reuseport_group)
+        }
+        Column::RemotePort => format_service_port(&truncate_value(&column.value(connection, index), max_length), &connection.remote_service),
+        _ => truncate_value(&column.value(connection, index), max_length)
+    }
+}
+
+/// Builds the header and the rows of a table containing the given connections.
+/// The state cell (or the whole row, depending on the theme) is colored by the state of the connection, unless the connection was flagged.
+/// 
+/// # Arguments
+/// * `rows`: The connections to add to the table, each with its (1-based) index.
+/// * `columns`: The columns to render, in order.
+/// * `options`: The options for rendering the table.
+/// 
+/// # Returns
+/// The Markdown formatted headers and rows.
+fn build_connection_rows(rows: &[(usize, &connections::Connection)], columns: &[Column], options: &TableOptions) -> (Vec<String>, Vec<TableRow>) {
+    let mut headers: Vec<String> = columns.iter().map(|column| match options.sorted_by {
+        Some((sorted_column, descending)) if sorted_column == *column => format!("{} {}", format_header(column), options.borders.sort_arrow(descending)),
+        _ => format_header(column),
+    }).collect();
+
+    // collect the names of the extra columns provided by plugins in the order they appear
+    let mut extra_columns: Vec<&String> = Vec::new();
+    for (key, _) in rows.iter().flat_map(|(_, connection)| &connection.extra_fields) {
+        if !extra_columns.contains(&key) {
+            extra_columns.push(key);
+            headers.push(format!("**{}**", key));
+        }
+    }
+
+    let mut table_rows: Vec<TableRow> = Vec::new();
+    for (idx, connection) in rows {
+        let mut cells: Vec<String> = columns.iter().map(|column| format_cell(column, connection, *idx, options)).collect();
+        for extra_column in &extra_columns {
+            let value = connection.extra_fields.iter().find(|(key, _)| &key == extra_column).map_or("-", |(_, value)| value);
+            cells.push(value.to_string());
+        }
+        cells = format_flagged_row(cells, connection);
+
+        let state_color: Option<Color> = options.theme.state_colors.iter()
+            .find(|(state, _)| !is_flagged(connection) && *state == connection.state)
+            .map(|(_, color)| *color);
+        let is_colored = |idx: usize| options.theme.color_rows || columns.get(idx) == Some(&Column::State);
+        table_rows.push(cells.into_iter().enumerate().map(|(idx, cell)| (cell, state_color.filter(|_| is_colored(idx)))).collect());
+    }
+
+    (headers, table_rows)
+}
+
+
+/// Assigns a width to every column so that the table fills the available width exactly.
+/// Spare width is distributed evenly, missing width is taken from the widest columns first (so that e.g. long IPv6
+/// addresses are truncated before short columns like ports), but columns aren't shrunk below `MIN_COLUMN_WIDTH`.
+/// 
+/// # Arguments
+/// * `content_widths`: The width of the widest cell of every column.
+/// * `available_width`: The width available for the content of all columns, i.e. without borders.
+/// 
+/// # Returns
+/// The width of every column.
+fn layout_columns(content_widths: &[usize], available_width: usize) -> Vec<usize> {
+    let mut widths: Vec<usize> = content_widths.to_vec();
+    let mut total_width: usize = widths.iter().sum();
+
+    for idx in 0..available_width.saturating_sub(total_width) {
+        let amount_columns: usize = widths.len();
+        widths[idx % amount_columns] += 1;
+    }
+    while total_width > available_width {
+        let Some((widest, &width)) = widths.iter().enumerate().rev().max_by_key(|(_, width)| **width) else { break };
+        if width <= MIN_COLUMN_WIDTH {
+            break;
+        }
+        widths[widest] -= 1;
+        total_width -= 1;
+    }
+    widths
+}
+
+
+/// Renders a Markdown formatted cell with a fixed width, centering its content or truncating its end with an ellipsis.
+/// 
+/// # Arguments
+/// * `skin`: The termimad skin used for rendering.
+/// * `cell`: The Markdown formatted content of the cell.
+/// * `color`: The color of the unstyled text of the cell, if any.
+/// * `background`: The background of the whole cell, if any.
+/// * `width`: The width of the cell.
+/// 
+/// # Returns
+/// The rendered cell.
+fn render_cell(skin: &MadSkin, cell: &str, color: Option<Color>, background: Option<Color>, width: usize) -> String {
+    let mut colored_skin: MadSkin;
+    let skin: &MadSkin = match (color, background) {
+        (None, None) => skin,
+        _ if !string_utils::colors_enabled() => skin,
+        (color, background) => {
+            colored_skin = skin.clone();
+            if let Some(color) = color {
+                colored_skin.paragraph.compound_style.set_fg(color);
+            }
+            if let Some(background) = background {
+                for style in [&mut colored_skin.paragraph.compound_style, &mut colored_skin.bold, &mut colored_skin.italic, &mut colored_skin.strikeout, &mut colored_skin.inline_code] {
+                    style.set_bg(background);
+                }
+            }
+            &colored_skin
+        }
+    };
+
+    let mut composite: FmtComposite = FmtComposite::from(Composite::from_inline(cell), skin);
+    composite.fit_width(width, Alignment::Left, skin);
+    composite.extend_width(width, Alignment::Center);
+    FmtInline { skin, composite }.to_string()
+}
+
+
+/// Renders a table whose columns are fitted to the width of the terminal (or the width selected by the user),
+/// truncating the widest cells with an ellipsis unless `--no-truncate` is set.
+/// 
+/// # Arguments
+/// * `skin`: The termimad skin used for rendering.
+/// * `headers`: The Markdown formatted headers of the columns.
+/// * `rows`: The rows of the table.
+/// * `options`: The options for rendering the table.
+/// 
+/// # Returns
+/// The rendered table.
+fn render_table(skin: &MadSkin, headers: &[String], rows: &[TableRow], options: &TableOptions) -> String {
+    // measure the content of every column to assign the widths of the columns
+    let visible_length = |cell: &str| skin.inline(cell).composite.visible_length;
+    let mut content_widths: Vec<usize> = headers.iter().map(|header| visible_length(header)).collect();
+    for row in rows {
+        for (content_width, (cell, _)) in content_widths.iter_mut().zip(row) {
+            *content_width = (*content_width).max(visible_length(cell));
+        }
+    }
+    let mut available_width: usize = (options.get_width() as usize).saturating_sub(headers.len() + 1);
+    if options.no_truncate {
+        available_width = available_width.max(content_widths.iter().sum());
+    }
+    let widths: Vec<usize> = layout_columns(&content_widths, available_width);
+
+    let vertical: String = skin.table.compound_style.apply_to(options.borders.vertical_char()).to_string();
+    let rule = |[left, line, junction, right]: [char; 4]| {
+        let lines: Vec<String> = widths.iter().map(|width| line.to_string().repeat(*width)).collect();
+        skin.table.compound_style.apply_to(format!("{}{}{}", left, lines.join(&junction.to_string()), right)).to_string()
+    };
+    let render_row = |cells: Vec<String>| format!("{}{}{}", vertical, cells.join(&vertical), vertical);
+
+    let [top_rule, middle_rule, bottom_rule] = options.borders.rule_chars();
+    let mut lines: Vec<String> = vec![rule(top_rule)];
+    lines.push(render_row(headers.iter().zip(&widths).map(|(header, width)| render_cell(skin, header, None, None, *width)).collect()));
+    for (idx, row) in rows.iter().enumerate() {
+        let background: Option<Color> = if options.theme.zebra && idx % 2 == 1 { Some(ZEBRA_BACKGROUND) } else { None };
+        lines.push(rule(middle_rule));
+        lines.push(render_row(row.iter().zip(&widths).map(|((cell, color), width)| render_cell(skin, cell, *color, background, *width)).collect()));
+    }
+    lines.push(rule(bottom_rule));
+
+    lines.join("\n")
+}
+
+
+/// Counts the values of the connections, e.g. their protocols, the most frequent value first.
+///
+/// # Arguments
+/// * `values`: The value of every connection.
+///
+/// # Returns
+/// The distinct values with their counts.
+fn count_values<T: Ord>(values: &[T]) -> Vec<(&T, usize)> {
+    let mut counts: Vec<(&T, usize)> = Vec::new();
+    for value in values {
+        match counts.iter_mut().find(|(counted_value, _)| *counted_value == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+    counts.sort_by(|(value_a, count_a), (value_b, count_b)| count_b.cmp(count_a).then(value_a.cmp(value_b)));
+    counts
+}
+
+
+/// Prints the summary above the connections (unless it is turned off), i.e. their amount and in `full` mode their amount per protocol and state.
+///
+/// # Arguments
+/// * `all_connections`: The printed connections.
+/// * `options`: The options for rendering the connections.
+///
+/// # Returns
+/// None
+fn print_summary(all_connections: &[connections::Connection], options: &TableOptions) {
+    match options.summary {
+        SummaryMode::Off => { }
+        SummaryMode::Count => string_utils::pretty_print_info(&format!("Connections: **{}**", all_connections.len())),
+        SummaryMode::Full => {
+            let noun: &str = if all_connections.len() == 1 { "connection" } else { "connections" };
+            let mut summary: String = format!("**{}** {}", all_connections.len(), noun);
+            if !all_connections.is_empty() {
+                let protocols: Vec<&String> = all_connections.iter().map(|connection| &connection.proto).collect();
+                let protocols: Vec<String> = count_values(&protocols).into_iter().map(|(proto, count)| format!("{} {}", proto, count)).collect();
+                let states: Vec<String> = all_connections.iter().map(|connection| connection.state.to_string()).collect();
+                let states: Vec<String> = count_values(&states).into_iter().map(|(state, count)| format!("{} {}", count, state)).collect();
+                let dash: &str = if options.borders == Borders::Ascii { "-" } else { "—" };
+                summary.push_str(&format!(" ({}) {} {}", protocols.join(" / "), dash, states.join(", ")));
+            }
+            string_utils::pretty_print_info(&summary);
+        }
+    }
+}
+
+
 /// Prints all current connections in a pretty Markdown table.
 /// 
 /// # Arguments
 /// * `all_connections`: A list containing all current connections as a `Connection` struct.
+/// * `columns`: The columns to render, in order.
+/// * `options`: The options for rendering the table.
 /// 
 /// # Returns
 /// None
-pub fn get_connections_table(all_connections: &Vec<connections::Connection>) {
+pub fn get_connections_table(all_connections: &[connections::Connection], columns: &[Column], options: &TableOptions) {
     let skin: MadSkin = create_table_style();
-    let (terminal_width, _) = terminal_size();
 
     // print amount of connections (after filter)
-    string_utils::pretty_print_info(&format!("Connections: **{}**", all_connections.len()));
+    print_summary(all_connections, options);
+
+    let rows: Vec<(usize, &connections::Connection)> = all_connections.iter().enumerate().map(|(idx, connection)| (idx + 1, connection)).collect();
+    let (headers, table_rows) = build_connection_rows(&rows, columns, options);
+    sout!("{}\n", render_table(&skin, &headers, &table_rows, options));
+}
+
+/// Prints all current connections in one pretty Markdown table per group, sorted by the size of the groups.
+/// The rows keep the index they have in the ungrouped table, also if they are sorted within their group.
+/// 
+/// # Arguments
+/// * `all_connections`: A list containing all current connections as a `Connection` struct.
+/// * `columns`: The columns to render, in order.
+/// * `group_by`: The column whose values are used to group the connections, e.g. the program.
+/// * `group_sort`: The key to sort the rows of each group by and whether it's descending by default (`--reverse`), if not set the rows keep their order.
+/// * `options`: The options for rendering the tables.
+/// 
+/// # Returns
+/// None
+pub fn get_grouped_connections_tables(all_connections: &[connections::Connection], columns: &[Column], group_by: &Column, group_sort: Option<(&SortKey, bool)>, options: &TableOptions) {
+    let skin: MadSkin = create_table_style();
+
+    // print amount of connections (after filter)
+    print_summary(all_connections, options);
+
+    let mut groups: Vec<(String, Vec<(usize, &connections::Connection)>)> = Vec::new();
+    for (idx, connection) in all_connections.iter().enumerate() {
+        let key: String = group_by.value(connection, idx + 1);
+        match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+            Some((_, rows)) => rows.push((idx + 1, connection)),
+            None => groups.push((key, vec![(idx + 1, connection)])),
+        }
+    }
+    groups.sort_by(|(key_a, rows_a), (key_b, rows_b)| rows_b.len().cmp(&rows_a.len()).then(key_a.cmp(key_b)));
+
+    for (key, mut rows) in groups {
+        if let Some((sort_key, reverse)) = group_sort {
+            rows.sort_by(|(_, connection_a), (_, connection_b)| sort_key.compare(connection_a, connection_b, reverse));
+        }
+        let noun: &str = if rows.len() == 1 { "connection" } else { "connections" };
+        string_utils::pretty_print_info(&format!("{}: **{}** ({} {})", group_by.header(), key, rows.len(), noun));
+        let (headers, table_rows) = build_connection_rows(&rows, columns, options);
+        sout!("{}\n", render_table(&skin, &headers, &table_rows, options));
+    }
+}
+
+
+/// Formats a connection as a leaf of the process tree, e.g. "#3 tcp 10.0.0.2:22 → 1.2.3.4:51234 established".
+///
+/// # Arguments
+/// * `index`: The (1-based) position of the connection in the output.
+/// * `connection`: The connection.
+///
+/// # Returns
+/// The formatted connection.
+fn format_tree_connection(index: usize, connection: &connections::Connection, borders: &Borders) -> String {
+    let local: String = format!("{}:{}", string_utils::format_address(&connection.local_address), connection.local_port);
+    if connection.state == connections::State::Listen {
+        return format!("*#{}* {} {} `listen`", index, connection.proto, local);
+    }
+    let remote: String = format!("{}:{}", string_utils::format_address(&connection.remote_address), connection.remote_port);
+    format!("*#{}* {} {} {} {} {}", index, connection.proto, local, borders.arrow(), remote, connection.state)
+}
+
+
+/// Recursively formats the connections and child processes of a process as lines of a tree.
+///
+/// # Arguments
+/// * `pid`: The PID of the process.
+/// * `prefix`: The indentation of the children, containing the branches of the ancestors.
+/// * `processes`: The name and parent PID of all processes in the tree.
+/// * `rows`: The connections owned by each process, with their (1-based) position in the output.
+/// * `borders`: The characters used to draw the branches.
+/// * `lines`: The lines the tree is appended to, each split into the (plain) branches and the Markdown formatted label.
+///
+/// # Returns
+/// None
+fn build_process_tree(pid: i32, prefix: &str, processes: &BTreeMap<i32, (String, i32)>, rows: &BTreeMap<i32, Vec<(usize, &connections::Connection)>>, borders: &Borders, lines: &mut Vec<(String, String)>) {
+    let own_rows: &[(usize, &connections::Connection)] = rows.get(&pid).map_or(&[], |rows| rows.as_slice());
+    let children: Vec<(&i32, &String)> = processes.iter()
+        .filter(|(_, (_, ppid))| *ppid == pid)
+        .map(|(child, (name, _))| (child, name))
+        .collect();
+
+    let amount_children: usize = own_rows.len() + children.len();
+    let [branch, indentation, last_branch, last_indentation] = borders.tree_branches();
+    let branch = |idx: usize| if idx + 1 == amount_children { (last_branch, last_indentation) } else { (branch, indentation) };
+
+    for (idx, (index, connection)) in own_rows.iter().enumerate() {
+        lines.push((format!("{}{} ", prefix, branch(idx).0), format_tree_connection(*index, connection, borders)));
+    }
+    for (idx, (child, name)) in children.into_iter().enumerate() {
+        let (branch, indentation) = branch(own_rows.len() + idx);
+        lines.push((format!("{}{} ", prefix, branch), format!("**{}** *({})*", name, child)));
+        build_process_tree(*child, &format!("{}{}", prefix, indentation), processes, rows, borders, lines);
+    }
+}
+
 
-    // add table headers
-    static CENTER_MARKDOWN_ROW: &str = "| :-: | :-: | :-: | :-: | :-: | :-: | :-: |\n";
-    let mut markdown = CENTER_MARKDOWN_ROW.to_string();
-    markdown.push_str("| **#** | **proto** | **local port** | **remote address** | **remote port** | **program***/pid* | **state** |\n");
+/// Prints the connections nested under their owning process, and the processes nested under their parents.
+///
+/// # Arguments
+/// * `all_connections`: The connections to print.
+/// * `live`: If `true` the connections were just collected, so the further ancestors of their processes are looked up in `/proc`.
+///   Otherwise (e.g. replayed connections of another host) only the recorded parents are shown.
+/// * `options`: The options for rendering the tree.
+///
+/// # Returns
+/// None
+pub fn get_connections_tree(all_connections: &[connections::Connection], live: bool, options: &TableOptions) {
+    let skin: MadSkin = create_table_style();
+
+    // print amount of connections (after filter)
+    print_summary(all_connections, options);
 
-    // iterate over all connections to build the table
+    // resolve the ancestors of all processes owning connections, stopping at the init process
+    let mut processes: BTreeMap<i32, (String, i32)> = BTreeMap::new();
+    let mut rows: BTreeMap<i32, Vec<(usize, &connections::Connection)>> = BTreeMap::new();
+    let mut unowned_rows: Vec<(usize, &connections::Connection)> = Vec::new();
     for (idx, connection) in all_connections.iter().enumerate() {
-        markdown.push_str(CENTER_MARKDOWN_ROW);
- 
-        // check if the remote IP is a DNS server
-        let remote_address = &connection.remote_address;
-
-        // add abusiveness information to remote address
-        let mut formatted_remote_address: String = format_known_address(remote_address, &connection.address_type);
-        formatted_remote_address = format_abuse_checked_address(&formatted_remote_address, connection.abuse_score);
-
-        // add row with connection information
-        markdown.push_str(&format!("| *{}* | {} | {} | {} | {} | {}*/{}* | {} |\n",
-            idx + 1, connection.proto, connection.local_port,  &formatted_remote_address, connection.remote_port, connection.program, connection.pid, connection.state
-        ));
-    }
-
-    // create an empty row that forces the table to fit the terminal with respect to how much space
-    // each column should receive based on the max length of each column (in the array below)
-    let max_column_spaces: [u16; 7] = [5, 5, 7, 32, 7, 24, 13];
-    let terminal_filling_row: String = string_utils::fill_terminal_width(terminal_width, max_column_spaces);
-    markdown.push_str(&terminal_filling_row);
-    markdown.push_str(CENTER_MARKDOWN_ROW);
-
-    println!("{}\n", skin.term_text(&markdown));
-}
\ No newline at end of file
+        let Some(pid) = connection.pid else {
+            unowned_rows.push((idx + 1, connection));
+            continue;
+        };
+        rows.entry(pid).or_default().push((idx + 1, connection));
+
+        let ppid: i32 = connection.ppid.unwrap_or(0);
+        processes.insert(pid, (connection.program.to_string(), ppid));
+        if !live {
+            if ppid > 0 {
+                processes.entry(ppid).or_insert((connection.pprogram.to_string(), 0));
+            }
+            continue;
+        }
+        let mut pid: i32 = ppid;
+        while pid > 0 && !processes.contains_key(&pid) {
+            // the ancestor exited or can't be inspected
+            let (name, ppid) = connections::get_process_parent(pid).unwrap_or(("-".to_string(), 0));
+            processes.insert(pid, (name, ppid));
+            pid = ppid;
+        }
+    }
+
+    let mut lines: Vec<(String, String)> = Vec::new();
+    let roots: Vec<(&i32, &String)> = processes.iter()
+        .filter(|(_, (_, ppid))| !processes.contains_key(ppid))
+        .map(|(pid, (name, _))| (pid, name))
+        .collect();
+    for (pid, name) in roots {
+        lines.push((String::new(), format!("**{}** *({})*", name, pid)));
+        build_process_tree(*pid, "", &processes, &rows, &options.borders, &mut lines);
+    }
+    if !unowned_rows.is_empty() {
+        lines.push((String::new(), "**-** *(unknown process)*".to_string()));
+        let unowned: BTreeMap<i32, Vec<(usize, &connections::Connection)>> = BTreeMap::from([(0, unowned_rows)]);
+        build_process_tree(0, "", &BTreeMap::new(), &unowned, &options.borders, &mut lines);
+    }
+
+    for (branches, label) in lines {
+        sout!("{}{}", branches, skin.inline(&label));
+    }
+    sout!();
+}
+
+
+/// Formats how often each value occurs (see `count_values`), e.g. "established (3), timewait".
+///
+/// # Arguments
+/// * `values`: The values to count.
+///
+/// # Returns
+/// The distinct values with their counts.
+fn format_counted_values<T: Ord + fmt::Display>(values: &[T]) -> String {
+    count_values(values).iter()
+        .map(|(value, count)| if *count == 1 { value.to_string() } else { format!("{} ({})", value, count) })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+
+/// Prints one row per remote address with the amount of connections to it, the local programs using it and their states.
+/// Sockets without a remote address (e.g. listeners) are skipped.
+///
+/// # Arguments
+/// * `all_connections`: The connections to aggregate.
+/// * `options`: The options for rendering the table.
+///
+/// # Returns
+/// None
+pub fn get_remote_hosts_table(all_connections: &[connections::Connection], options: &TableOptions) {
+    let skin: MadSkin = create_table_style();
+
+    let mut remote_hosts: Vec<(&IpAddr, Vec<&connections::Connection>)> = Vec::new();
+    for connection in all_connections.iter().filter(|connection| !matches!(connection.address_type, address_checkers::IPType::Unspecified)) {
+        match remote_hosts.iter_mut().find(|(remote_address, _)| *remote_address == &connection.remote_address) {
+            Some((_, connections)) => connections.push(connection),
+            None => remote_hosts.push((&connection.remote_address, vec![connection])),
+        }
+    }
+    remote_hosts.sort_by(|(address_a, connections_a), (address_b, connections_b)| connections_b.len().cmp(&connections_a.len()).then(address_a.cmp(address_b)));
+
+    // print amount of remote hosts (after filter)
+    string_utils::pretty_print_info(&format!("Remote hosts: **{}**", remote_hosts.len()));
+
+    let headers: Vec<String> = ["**remote address**", "**connections**", "**programs**", "**states**"].map(String::from).to_vec();
+    let mut rows: Vec<TableRow> = Vec::new();
+    for (remote_address, connections) in &remote_hosts {
+        let programs: Vec<&String> = connections.iter().map(|connection| &connection.program).collect();
+        let states: Vec<connections::State> = connections.iter().map(|connection| connection.state).collect();
+        let formatted_remote_address: String = format_known_address(&string_utils::format_address(remote_address), &connections[0].address_type);
+
+        rows.push(vec![
+            (format_scope_address(&formatted_remote_address, &connections[0].address_scope), None),
+            (connections.len().to_string(), None),
+            (format_counted_values(&programs), None),
+            (format_counted_values(&states), None),
+        ]);
+    }
+
+    sout!("{}\n", render_table(&skin, &headers, &rows, options));
+}
+
+
+/// Prints the listening sockets with the scope they are bound to, the owning user and the service, the most exposed first.
+/// The scopes are colored by their severity.
+///
+/// # Arguments
+/// * `exposed_sockets`: The scopes and listening sockets, sorted by their exposure.
+/// * `options`: The options for rendering the table.
+///
+/// # Returns
+/// None
+pub fn get_exposure_table(exposed_sockets: &[(BindScope, &connections::Connection)], options: &TableOptions) {
+    let skin: MadSkin = create_table_style();
+
+    let exposed: usize = exposed_sockets.iter().filter(|(scope, _)| *scope != BindScope::Loopback).count();
+    string_utils::pretty_print_info(&format!("Listening sockets: **{}** ({} reachable from other hosts)", exposed_sockets.len(), exposed));
+
+    let headers: Vec<String> = ["**#**", "**scope**", "**proto**", "**local address**", "**local port**", "**service**", "**program/pid**", "**user**"].map(String::from).to_vec();
+    let mut rows: Vec<TableRow> = Vec::new();
+    for (idx, (scope, connection)) in exposed_sockets.iter().enumerate() {
+        let color: Color = match scope {
+            BindScope::AllInterfaces => Red,
+            BindScope::Lan => Yellow,
+            BindScope::Loopback => Green,
+        };
+        rows.push(vec![
+            ((idx + 1).to_string(), None),
+            (scope.to_string(), Some(color)),
+            (connection.proto.clone(), None),
+            (string_utils::format_address(&connection.local_address), None),
+            (connection.local_port.to_string(), None),
+            (connection.local_service.clone().unwrap_or_else(|| "-".to_string()), None),
+            (format!("{}/{}", connection.program, string_utils::format_pid(connection.pid)), None),
+            (connection.user.clone(), None),
+        ]);
+    }
+
+    sout!("{}\n", render_table(&skin, &headers, &rows, options));
+}
+
+
+/// Prints the vsock sockets, i.e. the sockets between virtual machines and their host, with their CIDs and ports.
+///
+/// # Arguments
+/// * `sockets`: The vsock sockets.
+/// * `options`: The options for rendering the table.
+///
+/// # Returns
+/// None
+pub fn get_vsock_table(sockets: &[vsock::VsockSocket], options: &TableOptions) {
+    let skin: MadSkin = create_table_style();
+
+    string_utils::pretty_print_info(&format!("vsock sockets: **{}**", sockets.len()));
+
+    let headers: Vec<String> = ["**#**", "**type**", "**local cid**", "**local port**", "**remote cid**", "**remote port**", "**program/pid**", "**state**"].map(String::from).to_vec();
+    let mut rows: Vec<TableRow> = Vec::new();
+    for (idx, socket) in sockets.iter().enumerate() {
+        rows.push(vec![
+            ((idx + 1).to_string(), None),
+            (socket.socket_type.clone(), None),
+            (vsock::format_vsock_address(socket.local_cid, true), None),
+            (vsock::format_vsock_address(socket.local_port, false), None),
+            (vsock::format_vsock_address(socket.remote_cid, true), None),
+            (vsock::format_vsock_address(socket.remote_port, false), None),
+            (format!("{}/{}", socket.program, string_utils::format_pid(socket.pid)), None),
+            (socket.state.to_string(), None),
+        ]);
+    }
+
+    sout!("{}\n", render_table(&skin, &headers, &rows, options));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_columns() {
+        // spare width is distributed evenly
+        assert_eq!(layout_columns(&[3, 5], 10), vec![4, 6]);
+        assert_eq!(layout_columns(&[3, 5], 11), vec![5, 6]);
+        assert_eq!(layout_columns(&[3, 5], 8), vec![3, 5]);
+        // missing width is taken from the widest column first
+        assert_eq!(layout_columns(&[3, 39, 5], 20), vec![3, 12, 5]);
+        assert_eq!(layout_columns(&[10, 10], 16), vec![8, 8]);
+        // columns aren't shrunk below the minimum width, even if the table gets wider than the available width
+        assert_eq!(layout_columns(&[8, 9], 6), vec![MIN_COLUMN_WIDTH, MIN_COLUMN_WIDTH]);
+        assert_eq!(layout_columns(&[3, 4], 2), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_format_abuse_checked_address() {
+        let address = "127.0.0.1".to_string();
+        let score = Some(75);
+        let formatted = format_abuse_checked_address(&address, score);
+        assert_eq!(formatted, "127.0.0.1 *high abuse score: 75*");
+    }
+
+    #[test]
+    fn test_format_blocked_address() {
+        let address = "1.2.3.4".to_string();
+        assert_eq!(format_blocked_address(&address, true), "1.2.3.4 *blocklisted*");
+    }
+
+    #[test]
+    fn test_format_known_address() {
+        let address = "127.0.0.1".to_string();
+        let address_type = address_checkers::IPType::Localhost;
+        let formatted = format_known_address(&address, &address_type);
+        assert_eq!(formatted, "*127.0.0.1 localhost*");
+    }
+
+    #[test]
+    fn test_format_scope_address() {
+        let address = "100.72.1.9".to_string();
+        let formatted = format_scope_address(&address, &address_checkers::AddressScope::Cgnat);
+        assert_eq!(formatted, "100.72.1.9 *(cgnat)*");
+    }
+
+    #[test]
+    fn test_format_lan_name_address() {
+        let address = "192.168.1.20".to_string();
+        let formatted = format_lan_name_address(&address, &Some("printer.local".to_string()));
+        assert_eq!(formatted, "192.168.1.20 *(printer.local)*");
+    }
+
+    #[test]
+    fn test_format_location_address() {
+        let address = "8.8.8.8".to_string();
+        let formatted = format_location_address(&address, &Some("US".to_string()), &None);
+        assert_eq!(formatted, "8.8.8.8 *(US)*");
+    }
+
+    #[test]
+    fn test_format_asn_address() {
+        let address = "8.8.8.8".to_string();
+        let formatted = format_asn_address(&address, Some(15169), &Some("GOOGLE".to_string()));
+        assert_eq!(formatted, "8.8.8.8 *AS15169 GOOGLE*");
+    }
+
+    #[test]
+    fn test_format_session() {
+        let formatted = format_session(&"pts/3".to_string(), Some(12));
+        assert_eq!(formatted, "pts/3 *(session 12)*");
+    }
+
+    #[test]
+    fn test_format_shared_pid() {
+        let formatted = format_shared_pid(&"812".to_string(), &[812, 813, 814]);
+        assert_eq!(formatted, "812 *(shared +2)*");
+    }
+
+    #[test]
+    fn test_format_accept_queue() {
+        let formatted = format_accept_queue(&"129/128".to_string(), Some(129), Some(128));
+        assert_eq!(formatted, "129/128 ~~full~~");
+    }
+
+    #[test]
+    fn test_format_reuseport_group() {
+        let formatted = format_reuseport_group(&"8080".to_string(), Some(1));
+        assert_eq!(formatted, "8080 *(reuseport #1)*");
+    }
+
+    #[test]
+    fn test_format_tunnel_program() {
+        let formatted = format_tunnel_program(&"ssh".to_string(), &Some("-L 8080:db:5432".to_string()));
+        assert_eq!(formatted, "ssh `tunnel -L 8080:db:5432`");
+    }
+
+    #[test]
+    fn test_format_container_program() {
+        let formatted = format_container_program(&"docker-proxy".to_string(), &Some("webapp:8080".to_string()), &Borders::Unicode);
+        assert_eq!(formatted, "docker-proxy → **webapp:8080**");
+    }
+
+    #[test]
+    fn test_truncate_value() {
+        assert_eq!(truncate_value("systemd-resolved", Some(8)), "systemd…");
+        assert_eq!(truncate_value("sshd", Some(8)), "sshd");
+    }
+}
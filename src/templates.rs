@@ -0,0 +1,263 @@
+use clap::ValueEnum;
+use std::fs;
+
+use handlebars::{handlebars_helper, Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderErrorReason};
+use serde_json::{json, Value};
+use termimad::crossterm::style::{Color, Stylize};
+
+use crate::columns::Column;
+use crate::config;
+use crate::connections;
+use crate::string_utils;
+
+/// The field of templates which isn't a field of a connection, it's the same for all connections.
+static TIMESTAMP_FIELD: (&str, &str) = ("timestamp", "the time the connections were collected, e.g. 2024-05-01T12:00:00Z (only in templates)");
+
+
+/// Gets the fields of a connection and their descriptions, available as template variables and JSON keys.
+/// They are the fields of the columns (see `Column::field`), so every field can also be selected with `--columns`, `--sort` and `--group-by`.
+/// Fields added by plugins are available as well.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The names and descriptions of the fields, in the order of the columns.
+pub fn get_fields() -> Vec<(&'static str, &'static str)> {
+    let mut fields: Vec<(&str, &str)> = Column::value_variants().iter().filter_map(Column::field).collect();
+    // the timestamp follows the other fields which only exist in templates
+    fields.insert(2, TIMESTAMP_FIELD);
+    fields
+}
+
+
+/// Prints the fields which are available in templates and passed to plugins, used by `somo fields`.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// None
+pub fn print_fields() {
+    let fields: Vec<(&str, &str)> = get_fields();
+    let width: usize = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, description) in fields {
+        sout!("{:<width$}  {}", name, description, width = width);
+    }
+}
+
+
+/// Resolves the template selected with `--format`: either the template itself or, if prefixed with `@`,
+/// the name of a template defined in the `[templates]` section of the config file, e.g. `@brief`.
+///
+/// # Arguments
+/// * `format`: The value of `--format`.
+///
+/// # Returns
+/// The handlebars template or an error message if no template with the name is defined.
+pub fn resolve_template(format: &str) -> Result<String, String> {
+    let Some(name) = format.strip_prefix('@') else {
+        return Ok(format.to_string());
+    };
+
+    config::get_templates()
+        .into_iter()
+        .find(|(template_name, _)| template_name == name)
+        .map(|(_, template)| template)
+        .ok_or(format!("No template named '{}' is defined in the [templates] section of the config file.", name))
+}
+
+
+/// Reads a template selected with `--format-file`. The template can span multiple lines,
+/// only the final line break of the file is removed since every rendered connection ends with a line break anyway.
+///
+/// # Arguments
+/// * `path`: The path of the template file.
+///
+/// # Returns
+/// The handlebars template or an error message if the file can't be read.
+pub fn read_template_file(path: &str) -> Result<String, String> {
+    let template: String = fs::read_to_string(path).map_err(|error| format!("Couldn't read template file '{}': {}", path, error))?;
+    let template: &str = template.strip_suffix('\n').unwrap_or(&template);
+    Ok(template.strip_suffix('\r').unwrap_or(template).to_string())
+}
+
+
+/// Converts a connection into a JSON object containing all of its fields (see `get_fields`, except the index, ID and timestamp) and the fields added by plugins.
+/// It is passed to plugins and, together with the index, used as the variables of templates.
+///
+/// # Arguments
+/// * `connection`: The connection.
+///
+/// # Returns
+/// The JSON object, e.g. `{"proto": "tcp", "local_port": "22", ...}`.
+pub fn connection_to_json(connection: &connections::Connection) -> Value {
+    let mut context: Value = serde_json::to_value(connection).unwrap_or_default();
+    for (key, value) in &connection.extra_fields {
+        context[key] = Value::String(value.to_string());
+    }
+    context
+}
+
+
+/// Converts a template value to the text it is rendered as, e.g. `null` to an empty string and `8080` to "8080".
+///
+/// # Arguments
+/// * `value`: The value of a template variable.
+///
+/// # Returns
+/// The value as a string.
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.to_string(),
+        value => value.to_string(),
+    }
+}
+
+
+handlebars_helper!(upper_helper: |value: Json| value_to_string(value).to_uppercase());
+
+handlebars_helper!(pad_helper: |width: u64, value: Json| format!("{:<width$}", value_to_string(value), width = width as usize));
+
+handlebars_helper!(default_helper: |fallback: Json, value: Json| {
+    let value: String = value_to_string(value);
+    if value.is_empty() || value == "-" { value_to_string(fallback) } else { value }
+});
+
+
+/// Colors a value, e.g. `{{color "red" state}}`. The value is printed without color if colors are disabled (e.g. with `--color never`).
+///
+/// # Arguments
+/// * `helper`: The helper call with the color name and the value as parameters.
+/// * `out`: The output the colored value is written to.
+///
+/// # Returns
+/// An error if the color is missing or unknown.
+fn color_helper(helper: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output) -> HelperResult {
+    let name: &str = helper.param(0).and_then(|param| param.value().as_str()).ok_or(RenderErrorReason::ParamNotFoundForIndex("color", 0))?;
+    let color: Color = Color::try_from(name).map_err(|_| RenderErrorReason::Other(format!("unknown color '{}'", name)))?;
+    let value: String = helper.param(1).map(|param| value_to_string(param.value())).unwrap_or_default();
+
+    if string_utils::colors_enabled() {
+        out.write(&value.with(color).to_string())?;
+    } else {
+        out.write(&value)?;
+    }
+    Ok(())
+}
+
+
+/// Represents the templates used instead of a table.
+#[derive(Debug, Default)]
+pub struct OutputTemplates {
+    pub header: Option<String>,
+    pub connection: Option<String>,
+    pub footer: Option<String>,
+}
+
+
+/// Gets the variables a template can use for a connection, i.e. its fields, its index, its ID and the time it was collected.
+///
+/// # Arguments
+/// * `connection`: The connection.
+/// * `index`: The (1-based) position of the connection in the output.
+/// * `timestamp`: The time the connections were collected as an RFC 3339 timestamp.
+///
+/// # Returns
+/// The variables as a JSON object.
+fn get_template_context(connection: &connections::Connection, index: usize, timestamp: &str) -> Value {
+    let mut context: Value = connection_to_json(connection);
+    context["index"] = json!(index);
+    context["id"] = json!(connections::get_connection_id(connection));
+    context["timestamp"] = json!(timestamp);
+    context
+}
+
+
+/// Resolves the templates selected with `--format`, `--format-file`, `--format-header` and `--format-footer`.
+///
+/// # Arguments
+/// * `format`: The template (or `@name`) for every connection.
+/// * `format_file`: The path of a file with the template for every connection, used if `format` isn't set.
+/// * `header`: The template (or `@name`) printed before the connections.
+/// * `footer`: The template (or `@name`) printed after the connections.
+///
+/// # Returns
+/// The templates or an error message if a template can't be resolved.
+pub fn get_output_templates(format: &Option<String>, format_file: &Option<String>, header: &Option<String>, footer: &Option<String>) -> Result<OutputTemplates, String> {
+    let connection: Option<String> = match (format, format_file) {
+        (Some(format), _) => Some(resolve_template(format)?),
+        (None, Some(path)) => Some(read_template_file(path)?),
+        (None, None) => None,
+    };
+
+    Ok(OutputTemplates {
+        header: header.as_deref().map(resolve_template).transpose()?,
+        connection,
+        footer: footer.as_deref().map(resolve_template).transpose()?,
+    })
+}
+
+
+/// Gets the variables the header and footer templates can use, i.e. aggregates over all connections
+/// and the connections themselves (to iterate over them with `{{#each connections}}`).
+///
+/// # Arguments
+/// * `all_connections`: The connections to print.
+/// * `timestamp`: The time the connections were collected as an RFC 3339 timestamp.
+///
+/// # Returns
+/// The variables as a JSON object, e.g. `{"total": 12, "tcp_count": 10, ...}`.
+fn get_aggregate_context(all_connections: &[connections::Connection], timestamp: &str) -> Value {
+    let count = |predicate: &dyn Fn(&connections::Connection) -> bool| all_connections.iter().filter(|connection| predicate(connection)).count();
+
+    json!({
+        "total": all_connections.len(),
+        "tcp_count": count(&|connection| connection.proto == "tcp"),
+        "udp_count": count(&|connection| connection.proto == "udp"),
+        "listen_count": count(&|connection| connection.state == connections::State::Listen),
+        "established_count": count(&|connection| connection.state == connections::State::Established),
+        "timestamp": timestamp,
+        "connections": all_connections.iter().enumerate().map(|(idx, connection)| get_template_context(connection, idx + 1, timestamp)).collect::<Vec<Value>>(),
+    })
+}
+
+
+/// Prints the connections using handlebars templates: the header, one line per connection (e.g. `{{pid}} {{program}} {{remote_address}}`) and the footer.
+///
+/// # Arguments
+/// * `all_connections`: The connections to print.
+/// * `templates`: The templates, every template is optional.
+/// * `timestamp`: The time the connections were collected as an RFC 3339 timestamp, available as `{{timestamp}}`.
+///
+/// # Returns
+/// An error message if a template is invalid.
+pub fn print_formatted_connections(all_connections: &[connections::Connection], templates: &OutputTemplates, timestamp: &str) -> Result<(), String> {
+    let mut registry: Handlebars = Handlebars::new();
+    registry.register_escape_fn(handlebars::no_escape);
+    registry.register_helper("upper", Box::new(upper_helper));
+    registry.register_helper("pad", Box::new(pad_helper));
+    registry.register_helper("default", Box::new(default_helper));
+    registry.register_helper("color", Box::new(color_helper));
+    for (name, template) in [("header", &templates.header), ("connection", &templates.connection), ("footer", &templates.footer)] {
+        if let Some(template) = template {
+            registry.register_template_string(name, template).map_err(|error| format!("Invalid {} template: {}", name, error))?;
+        }
+    }
+    let render = |name: &str, context: &Value| registry.render(name, context).map_err(|error| format!("Couldn't render {} template: {}", name, error));
+
+    let aggregates: Value = get_aggregate_context(all_connections, timestamp);
+    if templates.header.is_some() {
+        sout!("{}", render("header", &aggregates)?);
+    }
+    if templates.connection.is_some() {
+        for (idx, connection) in all_connections.iter().enumerate() {
+            sout!("{}", render("connection", &get_template_context(connection, idx + 1, timestamp))?);
+        }
+    }
+    if templates.footer.is_some() {
+        sout!("{}", render("footer", &aggregates)?);
+    }
+    Ok(())
+}
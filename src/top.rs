@@ -0,0 +1,117 @@
+use std::io::IsTerminal;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::columns::{self, Column, SortKey};
+use crate::connections::{self, ByteCounters, Connection, FilterOptions};
+use crate::string_utils;
+use crate::table::{self, TableOptions};
+use crate::templates::{self, OutputTemplates};
+
+/// The columns rendered by `somo top` if no columns are selected.
+pub static TOP_COLUMNS: [Column; 8] = [
+    Column::Index, Column::Proto, Column::LocalPort, Column::RemoteAddress, Column::RemotePort, Column::ProgramPid, Column::TxRate, Column::RxRate
+];
+
+/// Contains the options of `somo top`.
+pub struct TopOptions {
+    /// The time between two samples.
+    pub interval: Duration,
+    /// The columns of the table.
+    pub columns: Vec<Column>,
+    /// The column to sort by, by default the busiest connections come first.
+    pub sort: Option<SortKey>,
+    /// If `true` the connections are sorted in descending order, unless the sort key has an order.
+    pub reverse: bool,
+    /// Only show this many connections of every sample.
+    pub limit: Option<usize>,
+    /// Print every sample using these templates instead of a table.
+    pub templates: Option<OutputTemplates>,
+    /// How the table is rendered.
+    pub table_options: TableOptions
+}
+
+
+/// Samples the byte counters of the TCP connections, exits if the `sock_diag` interface can't be queried.
+fn sample_byte_counters() -> ByteCounters {
+    connections::get_tcp_byte_counters().unwrap_or_else(|error| {
+        string_utils::print_error("connections", &format!("Couldn't get the byte counters of the TCP connections: {}", error));
+        process::exit(1);
+    })
+}
+
+
+/// Gets the total bytes per second a connection sent and received, used to show the busiest connections first.
+fn get_total_rate(connection: &Connection) -> u64 {
+    connection.tx_rate.unwrap_or(0) + connection.rx_rate.unwrap_or(0)
+}
+
+
+/// Prints one sample of the connections, replacing the previous one if the output is a terminal.
+///
+/// # Arguments
+/// * `connections`: The connections of the sample, with their throughput.
+/// * `options`: How the sample is printed.
+///
+/// # Returns
+/// None
+fn print_sample(connections: &[Connection], options: &TopOptions) {
+    let collected_at: String = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+    match &options.templates {
+        Some(output_templates) => {
+            if let Err(error) = templates::print_formatted_connections(connections, output_templates, &collected_at) {
+                string_utils::print_error("template", &error);
+                process::exit(1);
+            }
+        }
+        None => {
+            // clear the screen and move the cursor to its top left corner
+            if std::io::stdout().is_terminal() {
+                sout!("\x1b[2J\x1b[H{}", collected_at);
+            }
+            table::get_connections_table(connections, &options.columns, &options.table_options);
+        }
+    }
+}
+
+
+/// Shows the throughput of the (matching) TCP connections every interval until somo is interrupted, like `top` for sockets.
+/// The throughput is the difference of the byte counters the kernel keeps for every TCP connection between two samples,
+/// connections of other protocols and connections which were opened between two samples aren't shown.
+///
+/// # Arguments
+/// * `filter_options`: The filter options provided by the user.
+/// * `options`: The interval and how the samples are printed.
+///
+/// # Returns
+/// Never, exits if the byte counters can't be read.
+pub fn show_top(filter_options: &FilterOptions, options: &TopOptions) -> ! {
+    let mut previous_counters: ByteCounters = sample_byte_counters();
+    let mut sampled_at: Instant = Instant::now();
+
+    loop {
+        thread::sleep(options.interval);
+        let mut all_connections: Vec<Connection> = match connections::get_all_connections(filter_options) {
+            Ok(all_connections) => all_connections,
+            Err(error) => {
+                string_utils::pretty_print_warning(&format!("Couldn't get the connections: {}", error));
+                continue;
+            }
+        };
+        let counters: ByteCounters = sample_byte_counters();
+        connections::annotate_throughput(&mut all_connections, &previous_counters, &counters, sampled_at.elapsed());
+        previous_counters = counters;
+        sampled_at = Instant::now();
+
+        all_connections.retain(|connection| connection.tx_rate.is_some());
+        match &options.sort {
+            Some(sort_by) => columns::sort_connections(&mut all_connections, sort_by, options.reverse),
+            None => all_connections.sort_by_key(|connection| std::cmp::Reverse(get_total_rate(connection))),
+        }
+        if let Some(limit) = options.limit {
+            all_connections.truncate(limit);
+        }
+        print_sample(&all_connections, options);
+    }
+}
@@ -0,0 +1,128 @@
+use procfs::process::Process;
+
+use crate::connections;
+
+/// Options of the ssh client which take an argument, see `man ssh`.
+static SSH_OPTIONS_WITH_ARGUMENT: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// Represents a port forwarding of an ssh client.
+#[derive(Debug)]
+struct Forward {
+    kind: char,
+    listen_port: Option<u16>,
+    target_port: Option<u16>,
+    spec: String
+}
+
+
+/// Splits a forwarding spec at ":", ignoring colons inside of bracketed IPv6 addresses.
+///
+/// # Arguments
+/// * `spec`: The forwarding spec, e.g. "[::1]:8080:localhost:80".
+///
+/// # Returns
+/// The parts of the spec.
+fn split_spec(spec: &str) -> Vec<&str> {
+    let mut parts: Vec<&str> = Vec::new();
+    let (mut start, mut in_brackets) = (0, false);
+    for (idx, character) in spec.char_indices() {
+        match character {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            ':' if !in_brackets => {
+                parts.push(&spec[start..idx]);
+                start = idx + 1;
+            }
+            _ => { }
+        }
+    }
+    parts.push(&spec[start..]);
+    parts
+}
+
+
+/// Parses a forwarding spec of the `-L`, `-R` or `-D` option.
+///
+/// * `-L [bind_address:]port:host:hostport` -> listens on `port`, connects to `hostport`
+/// * `-R [bind_address:]port:host:hostport` -> the server listens on `port`, connects to `hostport`
+/// * `-D [bind_address:]port` -> listens on `port`
+///
+/// # Arguments
+/// * `kind`: The option, i.e. 'L', 'R' or 'D'.
+/// * `spec`: The argument of the option.
+///
+/// # Returns
+/// The forwarding or `None` if it isn't a TCP port forwarding (e.g. a forwarding of a Unix socket).
+fn parse_forward(kind: char, spec: &str) -> Option<Forward> {
+    let parts = split_spec(spec);
+    let (listen_port, target_port) = match (kind, parts.len()) {
+        ('L' | 'R', 3) => (parts[0], Some(parts[2])),
+        ('L' | 'R', 4) => (parts[1], Some(parts[3])),
+        ('D' | 'R', 1) => (parts[0], None),
+        ('D' | 'R', 2) => (parts[1], None),
+        _ => return None
+    };
+    let listen_port: u16 = listen_port.parse().ok()?;
+
+    Some(Forward {
+        kind,
+        listen_port: (kind != 'R').then_some(listen_port),
+        target_port: target_port.and_then(|target_port| target_port.parse().ok()),
+        spec: format!("-{} {}", kind, spec)
+    })
+}
+
+
+/// Gets all port forwardings from the command line of an ssh client, supporting combined options like `-fNL 8080:localhost:80`.
+///
+/// # Arguments
+/// * `cmdline`: The command line arguments of the ssh process, starting with the program name.
+///
+/// # Returns
+/// All TCP port forwardings.
+fn get_forwards(cmdline: &[String]) -> Vec<Forward> {
+    let mut forwards: Vec<Forward> = Vec::new();
+    let mut args = cmdline.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            continue;
+        }
+        for (idx, option) in arg.char_indices().skip(1) {
+            if !SSH_OPTIONS_WITH_ARGUMENT.contains(option) {
+                continue;
+            }
+            // the argument is either the rest of this arg or the next arg
+            let rest = &arg[idx + 1..];
+            let value = if rest.is_empty() { args.next().map(String::as_str) } else { Some(rest) };
+            if let (Some(value), 'L' | 'R' | 'D') = (value, option) {
+                forwards.extend(parse_forward(option, value));
+            }
+            break;
+        }
+    }
+    forwards
+}
+
+
+/// Marks the sockets of ssh clients which belong to port forwardings with their forwarding spec.
+///
+/// * sockets listening on the port of a local (`-L`) or dynamic (`-D`) forwarding
+/// * connections to the target port of a remote (`-R`) forwarding
+///
+/// # Arguments
+/// * `connections`: The connections to annotate.
+///
+/// # Returns
+/// None
+pub fn annotate_tunnels(connections: &mut [connections::Connection]) {
+    for connection in connections.iter_mut().filter(|connection| connection.program == "ssh") {
+        let Some(pid) = connection.pid else { continue };
+        let Ok(cmdline) = Process::new(pid).and_then(|process| process.cmdline()) else { continue };
+
+        let forward = get_forwards(&cmdline).into_iter().find(|forward| match forward.kind {
+            'L' | 'D' => connection.state == connections::State::Listen && forward.listen_port == Some(connection.local_port),
+            _ => connection.state == connections::State::Established && forward.target_port == Some(connection.remote_port),
+        });
+        connection.tunnel = forward.map(|forward| forward.spec);
+    }
+}
@@ -0,0 +1,75 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::{connections, docker};
+use crate::system_log;
+
+/// The cargo features somo can be built with and whether this build has them.
+static FEATURES: [(&str, bool); 4] = [
+    ("cli", cfg!(feature = "cli")),
+    ("plugins", cfg!(feature = "plugins")),
+    ("notifications", cfg!(feature = "notifications")),
+    ("throughput", cfg!(feature = "throughput")),
+];
+
+/// The socket of the system logger used by `--log-target syslog`.
+static SYSLOG_SOCKET: &str = "/dev/log";
+
+
+/// Checks which of the interfaces somo reads from or writes to are available on this system, as the current user.
+///
+/// # Arguments
+/// None
+///
+/// # Returns
+/// The names of the backends and whether they are available.
+fn get_backends() -> Vec<(&'static str, bool)> {
+    let docker_socket: Option<String> = docker::get_docker_socket();
+    vec![
+        ("procfs", fs::File::open("/proc/net/tcp").is_ok()),
+        ("sock_diag", connections::is_sock_diag_available()),
+        ("docker", docker_socket.is_some_and(|socket| Path::new(&socket).exists())),
+        ("journald", Path::new(system_log::JOURNALD_SOCKET).exists()),
+        ("syslog", Path::new(SYSLOG_SOCKET).exists()),
+    ]
+}
+
+
+/// Prints the version of somo with its build metadata, enabled features and available backends, used by `somo version`.
+///
+/// # Arguments
+/// * `json`: If `true` the output is a JSON object, e.g. for wrapper scripts detecting features.
+///
+/// # Returns
+/// None
+pub fn print_version(json: bool) {
+    let version: &str = env!("CARGO_PKG_VERSION");
+    let commit: &str = env!("SOMO_GIT_COMMIT");
+    let build_date: &str = env!("SOMO_BUILD_DATE");
+    let features: Vec<&str> = FEATURES.iter().filter(|(_, enabled)| *enabled).map(|(name, _)| *name).collect();
+    let backends: Vec<(&str, bool)> = get_backends();
+
+    if json {
+        let backends: serde_json::Map<String, Value> = backends.into_iter().map(|(name, available)| (name.to_string(), Value::Bool(available))).collect();
+        let output: Value = json!({
+            "version": version,
+            "commit": commit,
+            "build_date": build_date,
+            "features": features,
+            "backends": backends
+        });
+        sout!("{}", serde_json::to_string_pretty(&output).unwrap_or_default());
+        return;
+    }
+
+    sout!("somo {}", version);
+    sout!("commit:    {}", commit);
+    sout!("built:     {}", build_date);
+    sout!("features:  {}", features.join(", "));
+    sout!("backends:");
+    let width: usize = backends.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, available) in backends {
+        sout!("  {:<width$}  {}", name, if available { "available" } else { "unavailable" }, width = width);
+    }
+}
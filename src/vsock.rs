@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use procfs::process::Stat;
+
+use crate::connections::{self, FilterOptions, Result, State};
+use crate::netlink::{self, read_u32, SockDiag};
+
+/// The size of a `struct vsock_diag_msg` returned by the kernel.
+const VSOCK_DIAG_MSG_SIZE: usize = 32;
+
+/// The CID or port of a socket which isn't bound or connected to a specific one.
+pub const VMADDR_ANY: u32 = u32::MAX;
+
+/// Represents a vsock (`AF_VSOCK`) socket, i.e. a socket between a virtual machine and its host, addressed by a context ID (CID) and a port.
+#[derive(Debug, Clone)]
+pub struct VsockSocket {
+    pub socket_type: String,
+    pub local_cid: u32,
+    pub local_port: u32,
+    pub remote_cid: u32,
+    pub remote_port: u32,
+    pub state: State,
+    pub program: String,
+    pub pid: Option<i32>,
+    pub inode: u64
+}
+
+
+/// Formats a CID or port of a vsock socket, i.e. "*" if it isn't specific and the role of well-known CIDs.
+///
+/// # Arguments
+/// * `value`: The CID or port.
+/// * `is_cid`: If `true` the value is a CID.
+///
+/// # Returns
+/// The formatted value, e.g. "2 (host)".
+///
+/// # Example
+/// ```
+/// use somo::vsock::format_vsock_address;
+///
+/// assert_eq!(format_vsock_address(2, true), "2 (host)");
+/// assert_eq!(format_vsock_address(1024, false), "1024");
+/// assert_eq!(format_vsock_address(u32::MAX, true), "*");
+/// ```
+pub fn format_vsock_address(value: u32, is_cid: bool) -> String {
+    match (value, is_cid) {
+        (VMADDR_ANY, _) => "*".to_string(),
+        (0, true) => "0 (hypervisor)".to_string(),
+        (1, true) => "1 (local)".to_string(),
+        (2, true) => "2 (host)".to_string(),
+        (value, _) => value.to_string()
+    }
+}
+
+
+/// Builds the `sock_diag` request for dumping all vsock sockets, i.e. a `struct vsock_diag_req`.
+fn get_dump_request() -> Vec<u8> {
+    let mut request: Vec<u8> = Vec::with_capacity(24);
+    request.extend([libc::AF_VSOCK as u8, 0, 0, 0]);
+    // all states, any inode, no extra attributes and any cookie
+    request.extend(u32::MAX.to_ne_bytes());
+    request.extend([0u8; 16]);
+    request
+}
+
+
+/// Gets all vsock sockets from the `sock_diag` netlink interface of the kernel.
+///
+/// # Returns
+/// The CIDs, ports, states and inodes of the sockets or an error if the interface can't be queried, e.g. if the `vsock_diag` kernel module isn't loaded.
+fn read_vsock_sockets() -> io::Result<Vec<VsockSocket>> {
+    let sock_diag: SockDiag = SockDiag::open()?;
+    sock_diag.send(netlink::SOCK_DIAG_BY_FAMILY, libc::NLM_F_DUMP, &get_dump_request())?;
+
+    let mut sockets: Vec<VsockSocket> = Vec::new();
+    let result: io::Result<()> = sock_diag.receive(|message| {
+        if message.len() < VSOCK_DIAG_MSG_SIZE {
+            return;
+        }
+        sockets.push(VsockSocket {
+            socket_type: match message[1] as i32 {
+                libc::SOCK_STREAM => "stream",
+                libc::SOCK_DGRAM => "dgram",
+                libc::SOCK_SEQPACKET => "seqpacket",
+                _ => "-"
+            }.to_string(),
+            // the states of vsock are the ones of TCP
+            state: procfs::net::TcpState::from_u8(message[2]).map_or(State::Close, State::from),
+            local_cid: read_u32(message, 4),
+            local_port: read_u32(message, 8),
+            remote_cid: read_u32(message, 12),
+            remote_port: read_u32(message, 16),
+            inode: read_u32(message, 20) as u64,
+            program: "-".to_string(),
+            pid: None
+        });
+    });
+    match result {
+        Ok(()) => Ok(sockets),
+        Err(error) if error.raw_os_error() == Some(libc::ENOENT) => Err(io::Error::new(io::ErrorKind::NotFound, "the vsock_diag kernel module isn't loaded")),
+        Err(error) => Err(error)
+    }
+}
+
+
+/// Gets all vsock sockets together with the processes owning them.
+///
+/// # Returns
+/// The sockets or an error if they can't be read, e.g. if the `vsock_diag` kernel module isn't loaded.
+pub fn get_vsock_sockets() -> Result<Vec<VsockSocket>> {
+    let mut sockets: Vec<VsockSocket> = read_vsock_sockets()?;
+    let all_processes: HashMap<u64, Vec<Arc<Stat>>> = connections::get_processes(&FilterOptions::default())?;
+    for socket in sockets.iter_mut() {
+        if let Some(stat) = all_processes.get(&socket.inode).and_then(|owners| owners.first()) {
+            socket.program = stat.comm.to_string();
+            socket.pid = Some(stat.pid);
+        }
+    }
+    Ok(sockets)
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serde_json::{json, Value};
+
+use crate::connections::{self, Connection, FilterOptions, State};
+use crate::string_utils;
+use crate::system_log::{self, LogTarget, Priority};
+
+/// Identifies a socket across scans by its protocol, addresses, ports and process, independent of its state.
+type SocketKey = (String, IpAddr, u16, IpAddr, u16, Option<i32>);
+
+/// Represents where watched sockets are reported and what else happens when they appear or disappear.
+#[derive(Debug)]
+pub struct WatchActions {
+    /// Where the sockets are reported, closed sockets are only reported to the system log.
+    pub log_target: LogTarget,
+    /// Show a desktop notification for every new socket.
+    pub notify: bool,
+    /// A shell command which is run for every new socket, `{}` is replaced by the connection as JSON.
+    pub on_new_connection: Option<String>,
+    /// A shell command which is run for every closed socket, `{}` is replaced by the connection as JSON.
+    pub on_closed_connection: Option<String>,
+    /// A URL to which every new and closed socket is posted as JSON.
+    pub webhook: Option<String>
+}
+
+
+/// Gets the key identifying the socket of a connection across scans.
+fn get_socket_key(connection: &Connection) -> SocketKey {
+    (
+        connection.proto.to_string(),
+        connection.local_address, connection.local_port,
+        connection.remote_address, connection.remote_port,
+        connection.pid
+    )
+}
+
+
+/// Describes a new socket for the output and notifications, e.g. "nginx (pid 4321) listens on 0.0.0.0:80/tcp".
+///
+/// # Arguments
+/// * `connection`: The connection of the socket.
+///
+/// # Returns
+/// The description.
+fn describe_connection(connection: &Connection) -> String {
+    let process: String = match connection.pid {
+        Some(pid) => format!("{} (pid {})", connection.program, pid),
+        None => "an unknown process".to_string(),
+    };
+    let local: String = format!("{}:{}", string_utils::format_address(&connection.local_address), connection.local_port);
+    match connection.state {
+        State::Listen => format!("{} listens on {}/{}", process, local, connection.proto),
+        _ => format!(
+            "{} connected {} to {}:{}/{}",
+            process, local, string_utils::format_address(&connection.remote_address), connection.remote_port, connection.proto
+        ),
+    }
+}
+
+
+/// Shows a desktop notification about a new socket, failures are reported as a warning.
+#[cfg(feature = "notifications")]
+fn send_notification(description: &str) {
+    let result = notify_rust::Notification::new()
+        .appname("somo")
+        .summary("New socket")
+        .body(description)
+        .show();
+    if let Err(error) = result {
+        string_utils::pretty_print_warning(&format!("Couldn't show a desktop notification: {}", error));
+    }
+}
+
+
+/// Quotes a value for a POSIX shell, so that connection data (e.g. program names) can't inject commands.
+fn quote_shell_argument(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+
+/// Gets the JSON describing an event of a socket, which is passed to hooks and webhooks.
+///
+/// # Arguments
+/// * `event`: The event, "new" or "closed".
+/// * `connection`: The connection of the socket.
+///
+/// # Returns
+/// An object with the event, a description and all fields of the connection.
+fn get_event_json(event: &str, connection: &Connection) -> Value {
+    json!({
+        "event": event,
+        "time": humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        "description": describe_connection(connection),
+        "connection": connection
+    })
+}
+
+
+/// Runs a hook command using `sh` in the background, `{}` is replaced by the (quoted) event JSON and `$SOMO_EVENT` is set to the event.
+/// Hooks which fail are reported as a warning.
+///
+/// # Arguments
+/// * `command`: The shell command.
+/// * `event`: The event, "new" or "closed".
+/// * `event_json`: The JSON describing the event.
+///
+/// # Returns
+/// None
+fn run_hook(command: &str, event: &str, event_json: &Value) {
+    let command: String = command.replace("{}", &quote_shell_argument(&event_json.to_string()));
+    let event: String = event.to_string();
+    // the hook runs in its own thread, so that slow hooks don't delay the next scan
+    thread::spawn(move || {
+        match process::Command::new("sh").arg("-c").arg(&command).env("SOMO_EVENT", event).status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => string_utils::pretty_print_warning(&format!("The hook `{}` failed ({}).", command, status)),
+            Err(error) => string_utils::pretty_print_warning(&format!("Couldn't run the hook `{}`: {}", command, error)),
+        }
+    });
+}
+
+
+/// Posts the JSON describing an event to a webhook in the background, failures are reported as a warning.
+///
+/// # Arguments
+/// * `url`: The URL of the webhook.
+/// * `event_json`: The JSON describing the event.
+///
+/// # Returns
+/// None
+fn post_webhook(url: &str, event_json: Value) {
+    let url: String = url.to_string();
+    // the blocking client can't be used on the async runtime of main, so the request is sent from its own thread
+    thread::spawn(move || {
+        let result = reqwest::blocking::Client::new()
+            .post(&url)
+            .json(&event_json)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .and_then(|response| response.error_for_status());
+        if let Err(error) = result {
+            string_utils::pretty_print_warning(&format!("Couldn't post to the webhook: {}", error));
+        }
+    });
+}
+
+
+/// Reports the new and closed sockets found by a scan compared to the previous scan.
+/// New sockets are reported to the log target, closed ones only if it's the system log. Both trigger the actions.
+///
+/// # Arguments
+/// * `connections`: The connections of the scan.
+/// * `known_sockets`: The sockets of the previous scan.
+/// * `only_listening`: If `true` only listening sockets are reported.
+/// * `actions`: What happens for new and closed sockets.
+///
+/// # Returns
+/// None
+fn report_changes(connections: &HashMap<SocketKey, Connection>, known_sockets: &HashMap<SocketKey, Connection>, only_listening: bool, actions: &WatchActions) {
+    let is_watched = |connection: &&Connection| !only_listening || connection.state == State::Listen;
+    let new_connections = connections.iter().filter(|(key, _)| !known_sockets.contains_key(*key)).map(|(_, connection)| connection).filter(is_watched);
+    let closed_connections = known_sockets.iter().filter(|(key, _)| !connections.contains_key(*key)).map(|(_, connection)| connection).filter(is_watched);
+
+    for connection in new_connections {
+        let description: String = describe_connection(connection);
+        system_log::log_event(actions.log_target, Priority::Notice, "new", &description, connection);
+        if actions.notify {
+            #[cfg(feature = "notifications")]
+            send_notification(&description);
+        }
+        trigger_actions("new", connection, &actions.on_new_connection, actions);
+    }
+    for connection in closed_connections {
+        if actions.log_target != LogTarget::Stdout {
+            let description: String = format!("closed: {}", describe_connection(connection));
+            system_log::log_event(actions.log_target, Priority::Info, "closed", &description, connection);
+        }
+        trigger_actions("closed", connection, &actions.on_closed_connection, actions);
+    }
+}
+
+
+/// Runs the hook and posts to the webhook for an event of a socket, if they are set.
+fn trigger_actions(event: &str, connection: &Connection, hook: &Option<String>, actions: &WatchActions) {
+    if hook.is_none() && actions.webhook.is_none() {
+        return;
+    }
+    let event_json: Value = get_event_json(event, connection);
+    if let Some(command) = hook {
+        run_hook(command, event, &event_json);
+    }
+    if let Some(url) = &actions.webhook {
+        post_webhook(url, event_json);
+    }
+}
+
+
+/// Scans the connections and maps them by the keys identifying their sockets.
+fn scan_connections(filter_options: &FilterOptions) -> connections::Result<HashMap<SocketKey, Connection>> {
+    Ok(connections::get_all_connections(filter_options)?.into_iter().map(|connection| (get_socket_key(&connection), connection)).collect())
+}
+
+
+/// Scans the connections periodically and reports every new socket until somo is interrupted.
+/// Sockets which close and are opened again are reported again.
+///
+/// # Arguments
+/// * `filter_options`: The filter options provided by the user.
+/// * `only_listening`: If `true` only listening sockets are reported, e.g. if the user didn't filter for specific connections.
+/// * `interval`: The time between two scans.
+/// * `actions`: Where the sockets are reported and what happens for new and closed sockets.
+///
+/// # Returns
+/// Never, exits if the connections can't be read initially.
+pub fn watch_connections(filter_options: &FilterOptions, only_listening: bool, interval: Duration, actions: &WatchActions) -> ! {
+    if actions.notify && cfg!(not(feature = "notifications")) {
+        string_utils::pretty_print_error("Desktop notifications aren't supported by this build, reinstall somo with `--features notifications`.");
+        process::exit(1);
+    }
+
+    let mut known_sockets: HashMap<SocketKey, Connection> = match scan_connections(filter_options) {
+        Ok(connections) => connections,
+        Err(error) => {
+            string_utils::pretty_print_error(&format!("Couldn't get the connections: {}", error));
+            process::exit(1);
+        }
+    };
+    let watched: &str = if only_listening { "listening sockets" } else { "matching connections" };
+    string_utils::pretty_print_info(&format!("Watching for new {}, press Ctrl+C to stop.", watched));
+
+    loop {
+        thread::sleep(interval);
+        match scan_connections(filter_options) {
+            Ok(connections) => {
+                report_changes(&connections, &known_sockets, only_listening, actions);
+                known_sockets = connections;
+            }
+            Err(error) => string_utils::pretty_print_warning(&format!("Couldn't get the connections: {}", error)),
+        }
+    }
+}